@@ -3,7 +3,8 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use scrt_exploding_message::msg::{PingResponse, HandleMsg, InitMsg, QueryMsg};
+use scrt_exploding_message::msg::{PingResponse, ConfigResponse, HandleMsg, InitMsg, QueryMsg, HandleAnswer,
+                                  QueryAnswer, ReceiveMsg, SignedSend, SessionPayload};
 use scrt_exploding_message::state::Config;
 
 fn main() {
@@ -17,4 +18,14 @@ fn main() {
     export_schema(&schema_for!(QueryMsg), &out_dir);
     export_schema(&schema_for!(Config), &out_dir);
     export_schema(&schema_for!(PingResponse), &out_dir);
+    export_schema(&schema_for!(ConfigResponse), &out_dir);
+
+    // answers returned in the data of handle responses and by authenticated queries
+    export_schema(&schema_for!(HandleAnswer), &out_dir);
+    export_schema(&schema_for!(QueryAnswer), &out_dir);
+
+    // messages clients build to embed in token sends or to sign offline
+    export_schema(&schema_for!(ReceiveMsg), &out_dir);
+    export_schema(&schema_for!(SignedSend), &out_dir);
+    export_schema(&schema_for!(SessionPayload), &out_dir);
 }
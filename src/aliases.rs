@@ -0,0 +1,227 @@
+use cosmwasm_std::{to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, Querier,
+                   ReadonlyStorage, StdResult, Storage};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use serde::{Deserialize, Serialize};
+
+use secret_toolkit::crypto::sha_256;
+
+use crate::contract::{try_send, SendOptions};
+use crate::msg::{AliasInfo, HandleAnswer, QueryAnswer, ResponseStatus};
+use crate::msg::ResponseStatus::{Success, Failure};
+use crate::msg::{CODE_OK, CODE_INVALID_INPUT, CODE_LIMIT_REACHED, CODE_NOT_FOUND};
+use crate::state::{may_load, save, remove, read_prng_seed, write_prng_seed, read_latest_height};
+
+// keys for aliases take form: b"als{alias}"
+pub static ALIAS_PREFIX: &[u8] = b"als";
+// keys for the aliases of an address take form: b"ali{CanonicalAddr.as_slice().to_vec()}"
+pub static ALIAS_INDEX_PREFIX: &[u8] = b"ali";
+
+/// maximum number of live aliases of an address
+pub const MAX_ALIASES: usize = 16;
+/// prefix of generated aliases
+pub const GENERATED_ALIAS_PREFIX: &str = "alias-";
+
+/// disposable address that delivers to the inbox of its owner, tagged with the alias
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Alias {
+    pub owner: CanonicalAddr,
+    /// the alias stops delivering after this height
+    pub expire_after_height: Option<u64>,
+}
+
+impl Alias {
+    pub fn is_expired(&self, height: u64) -> bool {
+        matches!(self.expire_after_height, Some(expiry) if height > expiry)
+    }
+}
+
+pub fn read_alias<S: ReadonlyStorage>(storage: &S, alias: &str) -> StdResult<Option<Alias>> {
+    may_load(&ReadonlyPrefixedStorage::new(ALIAS_PREFIX, storage), alias.as_bytes())
+}
+
+fn read_alias_index<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> StdResult<Vec<String>> {
+    Ok(may_load(&ReadonlyPrefixedStorage::new(ALIAS_INDEX_PREFIX, storage), owner.as_slice())?.unwrap_or_default())
+}
+
+fn write_alias_index<S: Storage>(storage: &mut S, owner: &CanonicalAddr, aliases: &[String]) -> StdResult<()> {
+    save(&mut PrefixedStorage::new(ALIAS_INDEX_PREFIX, storage), owner.as_slice(), &aliases.to_vec())
+}
+
+/// Removes the owner's expired aliases and returns the remaining ones
+fn prune_aliases<S: Storage>(storage: &mut S, owner: &CanonicalAddr, height: u64) -> StdResult<Vec<String>> {
+    let mut live: Vec<String> = vec![];
+    for alias in read_alias_index(storage, owner)? {
+        match read_alias(storage, &alias)? {
+            Some(record) if !record.is_expired(height) => live.push(alias),
+            _ => remove(&mut PrefixedStorage::new(ALIAS_PREFIX, storage), alias.as_bytes()),
+        }
+    }
+    write_alias_index(storage, owner, &live)?;
+    Ok(live)
+}
+
+/// Generates an alias from the contract's PRNG seed, the block and the sender. Returns the alias
+/// and the seed to store for the next one
+fn generate_alias(env: &Env, seed: &[u8]) -> (String, [u8; 32]) {
+    let mut preimage = seed.to_vec();
+    preimage.extend_from_slice(&env.block.height.to_be_bytes());
+    preimage.extend_from_slice(&env.block.time.to_be_bytes());
+    preimage.extend_from_slice(env.message.sender.0.as_bytes());
+    let next_seed = sha_256(&preimage);
+    // the alias is hashed again so it reveals nothing about the seed
+    let hash = sha_256(&[&next_seed[..], b"alias"].concat());
+    let hex: String = hash[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    (format!("{}{}", GENERATED_ALIAS_PREFIX, hex), next_seed)
+}
+
+/// Mints a new alias for the sender, which stops delivering `ttl` blocks from now if set
+pub fn try_create_alias<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    ttl: Option<u64>,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut alias: Option<String> = None;
+    let mut expire_after_height: Option<u64> = None;
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut aliases = prune_aliases(&mut deps.storage, &sender_address_raw, env.block.height)?;
+    if ttl == Some(0) {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("The lifetime of an alias must be at least one block.");
+    } else if aliases.len() >= MAX_ALIASES {
+        status = Failure;
+        code = CODE_LIMIT_REACHED;
+        response_message.push_str(&format!("You already have {} aliases.", MAX_ALIASES));
+    } else {
+        let (mut new_alias, mut next_seed) = generate_alias(&env, &read_prng_seed(&deps.storage)?);
+        // a live alias is never handed out twice
+        while read_alias(&deps.storage, &new_alias)?.is_some() {
+            let (retry, retry_seed) = generate_alias(&env, &next_seed);
+            new_alias = retry;
+            next_seed = retry_seed;
+        }
+        write_prng_seed(&mut deps.storage, &next_seed)?;
+        expire_after_height = ttl.map(|ttl| env.block.height.saturating_add(ttl));
+        let record = Alias { owner: sender_address_raw.clone(), expire_after_height };
+        save(&mut PrefixedStorage::new(ALIAS_PREFIX, &mut deps.storage), new_alias.as_bytes(), &record)?;
+        aliases.push(new_alias.clone());
+        write_alias_index(&mut deps.storage, &sender_address_raw, &aliases)?;
+        alias = Some(new_alias);
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Alias created.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateAlias {
+            status,
+            message: response_message,
+            code,
+            alias,
+            expire_after_height,
+        })?),
+    })
+}
+
+/// Burns one of the sender's aliases, so it no longer delivers. Messages it delivered stay in the
+/// inbox.
+pub fn try_burn_alias<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    alias: String,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut aliases = prune_aliases(&mut deps.storage, &sender_address_raw, env.block.height)?;
+    match aliases.iter().position(|a| a == &alias) {
+        Some(index) => {
+            aliases.remove(index);
+            write_alias_index(&mut deps.storage, &sender_address_raw, &aliases)?;
+            remove(&mut PrefixedStorage::new(ALIAS_PREFIX, &mut deps.storage), alias.as_bytes());
+            status = Success;
+            code = CODE_OK;
+            response_message.push_str("Alias burned.");
+        }
+        None => {
+            status = Failure;
+            code = CODE_NOT_FOUND;
+            response_message.push_str("Alias not found.");
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::BurnAlias {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+/// Sends a message to the owner of an alias, tagged with the alias. The sender does not learn the
+/// owner's address.
+pub fn try_send_to_alias<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    alias: String,
+    content: String,
+    pow_nonce: Option<u64>,
+) -> StdResult<HandleResponse> {
+    match read_alias(&deps.storage, &alias)? {
+        Some(record) if !record.is_expired(env.block.height) => {
+            let target = deps.api.human_address(&record.owner)?;
+            // the proof of work names the alias, the sender does not know the owner's address, and
+            // the owner handing out the alias stands in for an invite code
+            let options = SendOptions {
+                tags: vec![alias.clone()],
+                hide_target: true,
+                pow_nonce,
+                pow_target: Some(alias),
+                invite_exempt: true,
+                ..SendOptions::default()
+            };
+            try_send(deps, env, content, target, options)
+        }
+        _ => Ok(HandleResponse {
+            messages: vec![],
+            log: vec![],
+            data: Some(to_binary(&HandleAnswer::Send {
+                status: Failure,
+                message: String::from("Alias not found."),
+                code: CODE_NOT_FOUND,
+                message_id: None,
+                receipt: None,
+                recipient_status: None,
+            })?),
+        }),
+    }
+}
+
+pub fn query_aliases<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    let height = read_latest_height(&deps.storage);
+    let mut aliases: Vec<AliasInfo> = vec![];
+    for alias in read_alias_index(&deps.storage, &address_raw)? {
+        if let Some(record) = read_alias(&deps.storage, &alias)? {
+            if !record.is_expired(height) {
+                aliases.push(AliasInfo { alias, expire_after_height: record.expire_after_height });
+            }
+        }
+    }
+
+    to_binary(&QueryAnswer::Aliases { aliases })
+}
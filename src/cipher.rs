@@ -0,0 +1,88 @@
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use cosmwasm_std::{StdError, StdResult};
+use secret_toolkit::crypto::sha_256;
+
+/// size of the synthetic nonce stored in front of encrypted content
+pub const NONCE_SIZE: usize = 24;
+
+/// Encrypts content for storage under `key` with XChaCha20-Poly1305, bound to `context` (the
+/// storage key of the record) as associated data. The contract has no source of randomness, so
+/// the nonce is a keyed hash of the context and the content: two writes only share a nonce if
+/// they store the same content under the same key.
+pub fn seal(key: &[u8; 32], context: &[u8], plaintext: &[u8]) -> StdResult<Vec<u8>> {
+    let nonce = synthetic_nonce(key, context, plaintext);
+    let ciphertext = XChaCha20Poly1305::new(Key::from_slice(key))
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg: plaintext, aad: context })
+        .map_err(|_| StdError::generic_err("Content failed to encrypt."))?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend(ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypts content encrypted with seal, failing if it was not sealed with this key and context
+/// or was tampered with
+pub fn open(key: &[u8; 32], context: &[u8], sealed: &[u8]) -> StdResult<Vec<u8>> {
+    if sealed.len() < NONCE_SIZE {
+        return Err(StdError::generic_err("Stored content is not encrypted."));
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+    XChaCha20Poly1305::new(Key::from_slice(key))
+        .decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad: context })
+        .map_err(|_| StdError::generic_err("Stored content failed to decrypt."))
+}
+
+fn synthetic_nonce(key: &[u8; 32], context: &[u8], plaintext: &[u8]) -> [u8; NONCE_SIZE] {
+    let mut preimage = key.to_vec();
+    preimage.extend_from_slice(b"nonce");
+    preimage.extend_from_slice(&(context.len() as u32).to_be_bytes());
+    preimage.extend_from_slice(context);
+    preimage.extend_from_slice(plaintext);
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce.copy_from_slice(&sha_256(&preimage)[..NONCE_SIZE]);
+    nonce
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [7u8; 32];
+
+    #[test]
+    fn seal_open_round_trip() {
+        for plaintext in [&b""[..], b"hello", &[0u8; 100][..]].iter() {
+            let sealed = seal(&KEY, b"record", plaintext).unwrap();
+            assert_ne!(&sealed[NONCE_SIZE..], *plaintext);
+            assert_eq!(open(&KEY, b"record", &sealed).unwrap(), plaintext.to_vec());
+        }
+    }
+
+    #[test]
+    fn seal_is_deterministic_per_context() {
+        let sealed = seal(&KEY, b"record", b"hello").unwrap();
+        assert_eq!(seal(&KEY, b"record", b"hello").unwrap(), sealed);
+        assert_ne!(seal(&KEY, b"other record", b"hello").unwrap(), sealed);
+    }
+
+    #[test]
+    fn open_rejects_tampering() {
+        let sealed = seal(&KEY, b"record", b"hello").unwrap();
+
+        // every flipped bit, in the nonce, the ciphertext or the tag, fails to decrypt
+        for index in 0..sealed.len() {
+            let mut tampered = sealed.clone();
+            tampered[index] ^= 1;
+            assert!(open(&KEY, b"record", &tampered).is_err(), "byte {}", index);
+        }
+        assert!(open(&KEY, b"record", &sealed[..sealed.len() - 1]).is_err());
+        assert!(open(&KEY, b"record", &sealed[..NONCE_SIZE - 1]).is_err());
+    }
+
+    #[test]
+    fn open_rejects_other_key_or_context() {
+        let sealed = seal(&KEY, b"record", b"hello").unwrap();
+        assert!(open(&[8u8; 32], b"record", &sealed).is_err());
+        assert!(open(&KEY, b"other record", &sealed).is_err());
+    }
+}
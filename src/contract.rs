@@ -1,19 +1,135 @@
-use cosmwasm_std::{to_binary, Api, Binary, Env, Extern, HandleResponse, InitResponse, Querier, Storage, Uint128, HumanAddr, StdResult, StdError};
+use cosmwasm_std::{to_binary, from_binary, from_slice, log, Api, Binary, Env, Extern, HandleResponse, InitResponse, Querier, Storage, ReadonlyStorage,
+                   Uint128, HumanAddr, StdResult, StdError, CanonicalAddr, CosmosMsg, BankMsg, Coin};
 use std::string::String;
 use std::convert::TryFrom;
+use serde::de::IgnoredAny;
 
-use crate::msg::{HandleMsg, InitMsg, QueryMsg, ResponseStatus, HandleAnswer, PingResponse};
-use crate::state::{save, Config, CONFIG_KEY, load, Message, SEQ_KEY, MessageQueueStorage, MessageStorage};
+use crate::msg::{HandleMsg, InitMsg, QueryMsg, DelegatedQuery, BatchOp, SignedSend, SessionPayload, SessionOp, PermitParams, PostageInfo, SendFeeInfo, FeeInfo, ReceiveMsg, QueryAnswer, ResponseStatus, HandleAnswer, PingResponse, MessagePreview,
+                 ConfigResponse, ConfigChangeInfo, MerkleStep, AuditActionInfo, AuditEntryInfo,
+                 ConversationSummary, SenderUnread, PresenceInfo, SentItemSummary, SystemNotice, FolderInfo,
+                 SearchMatch, RelayInfo, ReceiptNftInfo, TokenPermit};
+use crate::state::{save, Config, CONFIG_KEY, load, may_load, Message, MessageQueueStorage, MessageStorage,
+                   ReadonlyMessageQueueStorage, ReadonlyMessageStorage, read_viewing_key, write_viewing_key,
+                   remove_viewing_key, read_viewing_key_labels, write_latest_height, read_latest_height,
+                   record_first_seen, ConversationStorage, ReadonlyConversationStorage, SentItem, SentItemsStorage,
+                   SystemMessage, ReceivedMessage, ReceivedStorage, MessageQueue, ScheduledRelease,
+                   ScheduledReleaseStorage, read_heartbeat, write_heartbeat, Commitment, CommitmentStorage,
+                   MAX_PENDING_COMMITMENTS, PendingTransfer, TransferStorage, write_chunk, take_chunk, remove_chunk, MAX_CHUNKS,
+                   MAX_PENDING_TRANSFERS, TRANSFER_TIMEOUT_BLOCKS, Stats, read_stats, write_stats,
+                   write_state_version, read_state_version, STATE_VERSION, read_user_stats,
+                   write_user_stats, block_sender, unblock_sender, is_blocked, UserSettingsStorage,
+                   ReadonlyUserSettingsStorage, read_blocklist_page, MAX_BLOCKED, BLOCKLIST_PAGE_SIZE,
+                   read_offense_count, write_offense_count, Fee, add_contact, remove_contact, is_contact,
+                   Bond, write_bond, read_bond, take_bond, SendFee, FeeTier, Revenue, REVENUE_KEY, credit_revenue,
+                   CONTRACT_SECRET_KEY, derive_message_id, derive_key, reply_token, read_prng_seed, write_prng_seed,
+                   read_key_generation, write_key_generation, CountFuzz, Postage, read_receiver, write_receiver,
+                   read_sender_callback, write_sender_callback, write_delegated_key, read_delegated_key,
+                   remove_delegated_key, write_signing_key, read_signing_key, write_nonce, read_nonce,
+                   write_permit_epoch, read_permit_epoch, revoke_permit, is_permit_revoked, CONTRACT_ADDRESS_KEY,
+                   Session, SessionPermission, SessionStorage, MAX_SESSIONS, read_sequence, write_sequence,
+                   link_active_queue, unlink_active_queue, first_active_queue, ContractStatus,
+                   ConfigChange, PendingConfigChange, PENDING_CONFIG_KEY, remove, SenderFilter,
+                   write_registered_contract, is_registered_contract, read_tag_index, write_tag_index,
+                   read_flagged_index, write_flagged_index,
+                   Receipt, write_receipt, read_receipt, ReceiptEpoch, RECEIPT_EPOCH_KEY, write_closed_epoch,
+                   read_closed_epoch, AuditAction, AuditEntry, append_audit_entry, audit_log_len,
+                   read_audit_entry, DeadLetter, DeadLetterReason, push_dead_letter, read_oldest_dead_letter,
+                   remove_oldest_dead_letter, PayloadValidation, Presence, QuietHours, QuietWindow, Folder, read_folders,
+                   write_folders, read_folder_queue, write_folder_queue, ReplyToken, write_reply_token,
+                   read_reply_token, remove_reply_token, anonymous_sender, Relay, read_relays, write_relays,
+                   ReadProof, read_proof_hash, write_read_proof, read_read_proof, ReceiptNft};
+use crate::gates::{try_set_token_gate, try_set_nft_gate, try_set_min_sender_age, query_token_gate, query_nft_gate,
+                   query_min_sender_age, token_gate_passed, nft_gate_passed, age_gate_passed, gated};
+use crate::invites::{try_create_invite, try_revoke_invite, try_set_invite_only, query_invites, needs_invite,
+                     invite_valid, redeem_invite};
+use crate::mutes::{try_mute, try_unmute, query_mute_list, is_muted, MutedMessage, write_muted_message,
+                   read_muted_message, forfeit_to, take_forfeited};
+use crate::merkle::{leaf_hash, merkle_root, merkle_proof};
+use crate::names::{try_register_name, try_transfer_name, try_release_name, query_resolve_name, query_name_of,
+                   valid_name_fee, MIN_NAME_SIZE, MAX_NAME_SIZE};
+use crate::notes::{try_read_note, try_delete_note, query_notes, write_note, Note, MAX_NOTES};
+use crate::polls::{try_send_poll, try_vote, query_poll_results, read_poll, remove_ballot, MAX_POLL_QUESTION_SIZE,
+                   MAX_POLL_OPTIONS, MAX_POLL_OPTION_SIZE, MAX_POLL_TARGETS};
+use crate::aliases::{try_create_alias, try_burn_alias, try_send_to_alias, query_aliases, MAX_ALIASES};
+use crate::profile::{try_set_profile, query_profile, query_view_profile, MAX_DISPLAY_NAME_SIZE, MAX_BIO_SIZE};
+use crate::queue::{move_to_front, Eviction, EvictionPolicy};
+use crate::receipt_nft::mint_read_receipt;
+use crate::receiver::ReceiverHandleMsg;
+use crate::viewing_key::{ViewingKey, VIEWING_KEY_SIZE, MAX_VIEWING_KEY_LABELS, MAX_VIEWING_KEY_LABEL_SIZE};
 use crate::msg::ResponseStatus::{Success, Failure};
-use secret_toolkit::utils::{pad_handle_result};
+use crate::msg::{CODE_OK, CODE_TOO_LONG, CODE_NOT_DELIVERED, CODE_INVALID_INPUT, CODE_LIMIT_REACHED, CODE_NOT_FOUND,
+                 CODE_TAKEN, CODE_NO_MESSAGES, CODE_SEALED, CODE_SIGNATURE_REQUIRED, CODE_UNAUTHORIZED, CODE_GATED,
+                 CODE_READ_ONLY, CODE_INSUFFICIENT_FEE, CODE_POW_REQUIRED};
+use secret_toolkit::utils::{pad_handle_result, HandleCallback};
+use secret_toolkit::crypto::sha_256;
+use secret_toolkit::crypto::secp256k1::{PublicKey, Signature};
+use secret_toolkit::snip20::{register_receive_msg, transfer_msg};
 
 /// pad handle responses and log attributes to blocks of 256 bytes to prevent leaking info based on
 /// response size
 pub const BLOCK_SIZE: usize = 256;
 
+/// maximum size of a reaction in bytes
+pub const MAX_REACTION_SIZE: usize = 16;
+/// maximum size of a content type in bytes
+pub const MAX_CONTENT_TYPE_SIZE: usize = 64;
+/// maximum number of tags on a message
+pub const MAX_TAGS: usize = 4;
+/// maximum size of a tag in bytes
+pub const MAX_TAG_SIZE: usize = 32;
+/// maximum size of a status text in bytes
+pub const MAX_STATUS_SIZE: usize = 64;
+/// minimum size of the salt of a message receipt in bytes
+pub const MIN_RECEIPT_SALT_SIZE: usize = 16;
+/// blocks after which a receipt epoch is closed, about an hour
+pub const RECEIPT_EPOCH_BLOCKS: u64 = 600;
+/// receipts after which a receipt epoch is closed, keeps folding it within the gas limit
+pub const MAX_RECEIPTS_PER_EPOCH: usize = 256;
+
+/// maximum number of expired messages deleted as a side effect of a single recv or size request
+pub const MAX_PRUNED_PER_CALL: u32 = 8;
+
+/// maximum number of operations in a batch
+pub const MAX_BATCH_OPS: usize = 16;
+
+/// maximum number of messages deleted by a single admin purge or clear all
+pub const MAX_PURGED_PER_CALL: u32 = 64;
+/// maximum number of pinned messages in a queue
+pub const MAX_PINNED_MESSAGES: u32 = 8;
+/// maximum number of reads a message can be sent with before it explodes
+pub const MAX_VIEWS: u32 = 64;
+/// maximum number of relay contracts the admin can sanction
+pub const MAX_RELAYS: u32 = 16;
+/// maximum number of leading zero bits the proof of work of a send can be required to have
+pub const MAX_POW_DIFFICULTY: u8 = 32;
+/// maximum number of folders per address
+pub const MAX_FOLDERS: u32 = 8;
+/// maximum size of a folder name in bytes
+pub const MAX_FOLDER_NAME_SIZE: usize = 32;
+/// name of the folder holding archived messages, reserved for it
+pub const ARCHIVE_FOLDER: &str = "archive";
+/// maximum number of messages in the archive, which refuses more instead of evicting
+pub const MAX_ARCHIVED_MESSAGES: u32 = 16;
+/// number of blocks an archived message is kept, about a week
+pub const ARCHIVE_TTL_BLOCKS: u64 = 100_800;
+/// maximum number of matches returned by a search of the inbox
+pub const MAX_SEARCH_RESULTS: u32 = 20;
+/// maximum size of a search query in bytes
+pub const MAX_SEARCH_QUERY_SIZE: usize = 64;
+/// size of the part of the content returned around a search match, in bytes
+pub const SEARCH_SNIPPET_SIZE: usize = 64;
+/// maximum number of size tiers of the send fee
+pub const MAX_FEE_TIERS: usize = 8;
+/// maximum number of dead letters kept per recipient, older ones are deleted
+pub const MAX_DEAD_LETTERS: usize = 4;
+/// number of entries on a full page of the audit log
+pub const AUDIT_PAGE_SIZE: u32 = 20;
+/// blocks a config change waits before it can be executed if init sets no delay, about a day
+pub const DEFAULT_CONFIG_CHANGE_DELAY: u64 = 14_400;
+
 pub fn init<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    _env: Env,
+    env: Env,
     msg: InitMsg,
 ) -> StdResult<InitResponse> {
     let max_messages = match valid_max_messages(msg.max_messages) {
@@ -28,17 +144,110 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
         Some(v) => v,
         None => return Err(StdError::generic_err("Invalid max_message_size."))
     };
+    let max_sent_items = match valid_max_sent_items(msg.max_sent_items) {
+        Some(v) => v,
+        None => return Err(StdError::generic_err("Invalid max_sent_items."))
+    };
+    let padding_buckets = match valid_padding_buckets(msg.padding_buckets) {
+        Some(v) => v,
+        None => return Err(StdError::generic_err("Invalid padding_buckets."))
+    };
+    if let Some(CountFuzz::Bucket { size: 0 }) = msg.unread_count_fuzz {
+        return Err(StdError::generic_err("Invalid unread_count_fuzz."));
+    }
+
+    let admin = match msg.admin {
+        Some(admin) => deps.api.canonical_address(&admin)?,
+        None => deps.api.canonical_address(&env.message.sender)?,
+    };
+
+    // register with the postage token so it calls receive on incoming transfers
+    let mut messages = vec![];
+    let postage = match msg.postage {
+        Some(postage) => {
+            messages.push(register_receive_msg(
+                env.contract_code_hash.clone(),
+                None,
+                BLOCK_SIZE,
+                postage.token_code_hash.clone(),
+                postage.token.clone(),
+            )?);
+            Some(Postage {
+                token: deps.api.canonical_address(&postage.token)?,
+                token_code_hash: postage.token_code_hash,
+                amount: postage.amount.u128(),
+                per_byte: postage.per_byte.u128(),
+            })
+        }
+        None => None,
+    };
+    let send_fee = match msg.send_fee {
+        Some(send_fee) => Some(valid_send_fee(send_fee).ok_or_else(|| StdError::generic_err("Invalid send_fee."))?),
+        None => None,
+    };
+    let name_fee = match msg.name_fee {
+        Some(name_fee) => Some(valid_name_fee(name_fee).ok_or_else(|| StdError::generic_err("Invalid name_fee."))?),
+        None => None,
+    };
+    let pow_difficulty = msg.pow_difficulty.unwrap_or(0);
+    if pow_difficulty > MAX_POW_DIFFICULTY {
+        return Err(StdError::generic_err("Invalid pow_difficulty."));
+    }
 
     let config = Config {
+        admin: Some(admin),
         max_messages,
-        discard: msg.discard,
+        eviction_policy: match msg.eviction_policy {
+            Some(eviction_policy) => eviction_policy,
+            None if msg.discard => EvictionPolicy::Reject,
+            None => EvictionPolicy::DropOldest,
+        },
         max_message_size,
+        max_sent_items,
+        seq_start,
+        padding_buckets,
+        unread_count_fuzz: msg.unread_count_fuzz,
+        postage,
+        send_fee,
+        status: ContractStatus::Normal,
+        config_change_delay: msg.config_change_delay.unwrap_or(DEFAULT_CONFIG_CHANGE_DELAY),
+        sender_filter: msg.sender_filter.unwrap_or_default(),
+        activity_logs: msg.activity_logs.unwrap_or(false),
+        name_fee,
+        default_ttl_blocks: msg.default_ttl_blocks.unwrap_or(0),
+        payload_validation: msg.payload_validation.unwrap_or_default(),
+        max_messages_per_sender: msg.max_messages_per_sender.unwrap_or(0),
+        uniform_failures: msg.uniform_failures.unwrap_or(false),
+        receipt_nft: match msg.receipt_nft {
+            Some(receipt_nft) => Some(ReceiptNft {
+                contract: deps.api.canonical_address(&receipt_nft.contract)?,
+                code_hash: receipt_nft.code_hash,
+            }),
+            None => None,
+        },
+        pow_difficulty,
     };
 
+    // secret key used to derive message ids from the sequence
+    let mut secret_preimage = deps.api.canonical_address(&env.contract.address)?.as_slice().to_vec();
+    secret_preimage.extend_from_slice(deps.api.canonical_address(&env.message.sender)?.as_slice());
+    secret_preimage.extend_from_slice(&env.block.height.to_be_bytes());
+    secret_preimage.extend_from_slice(&env.block.time.to_be_bytes());
+    secret_preimage.extend_from_slice(&seq_start.to_be_bytes());
+    secret_preimage.extend_from_slice(msg.entropy.as_bytes());
+    let contract_secret = sha_256(&secret_preimage).to_vec();
+
     save(&mut deps.storage, CONFIG_KEY, &config)?;
-    save(&mut deps.storage, SEQ_KEY, &seq_start)?;
+    save(&mut deps.storage, CONTRACT_SECRET_KEY, &contract_secret)?;
+    save(&mut deps.storage, CONTRACT_ADDRESS_KEY, &deps.api.canonical_address(&env.contract.address)?)?;
+    write_prng_seed(&mut deps.storage, &derive_key(&contract_secret, b"prng"))?;
+    write_stats(&mut deps.storage, &Stats::default())?;
+    write_state_version(&mut deps.storage, STATE_VERSION);
 
-    Ok(InitResponse::default())
+    Ok(InitResponse {
+        messages,
+        log: vec![],
+    })
 }
 
 fn valid_max_messages(val: i32) -> Option<u32> {
@@ -49,15 +258,21 @@ fn valid_max_messages(val: i32) -> Option<u32> {
     }
 }
 
-fn valid_seq_start(val: Uint128) -> Option<u128> {
+// message ids are stored as u64
+fn valid_seq_start(val: Uint128) -> Option<u64> {
     let v = val.u128();
     if v < 1 {
         None
     } else {
-        Some(v)
+        u64::try_from(v).ok()
     }
 }
 
+/// Converts a message id from a request, ids that do not fit a u64 become 0 which is never used
+pub fn to_message_id(val: Uint128) -> u64 {
+    u64::try_from(val.u128()).unwrap_or(0)
+}
+
 // we limit the max message size to 65535
 fn valid_max_message_size(val: i32) -> Option<u16> {
     if val < 1 {
@@ -67,19 +282,353 @@ fn valid_max_message_size(val: i32) -> Option<u16> {
     }
 }
 
+// missing value disables the sent items folder
+fn valid_max_sent_items(val: Option<i32>) -> Option<u32> {
+    match val {
+        None => Some(0),
+        Some(v) => u32::try_from(v).ok(),
+    }
+}
+
+// buckets are kept sorted, missing value disables padding
+fn valid_padding_buckets(val: Option<Vec<u32>>) -> Option<Vec<u32>> {
+    let mut buckets = val.unwrap_or_default();
+    if buckets.contains(&0) {
+        return None;
+    }
+    buckets.sort_unstable();
+    buckets.dedup();
+    Some(buckets)
+}
+
+// tiers are kept sorted by size, two tiers cannot share a max_size
+fn valid_send_fee(send_fee: SendFeeInfo) -> Option<SendFee> {
+    if send_fee.denom.is_empty() || send_fee.tiers.len() > MAX_FEE_TIERS {
+        return None;
+    }
+    let mut tiers: Vec<FeeTier> = send_fee
+        .tiers
+        .into_iter()
+        .map(|tier| FeeTier { max_size: tier.max_size, amount: tier.amount.u128() })
+        .collect();
+    tiers.sort_by_key(|tier| tier.max_size);
+    if tiers.windows(2).any(|pair| pair[0].max_size == pair[1].max_size) {
+        return None;
+    }
+    Some(SendFee {
+        denom: send_fee.denom,
+        base: send_fee.base.u128(),
+        per_byte: send_fee.per_byte.u128(),
+        tiers,
+    })
+}
+
 pub fn handle<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     msg: HandleMsg,
 ) -> StdResult<HandleResponse> {
-    let response = match msg {
-        HandleMsg::Send { content, target } => try_send(deps, env, content, target),
-        HandleMsg::Recv { } => try_receive(deps, env),
+    write_latest_height(&mut deps.storage, env.block.height);
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let mut response = dispatch_handle(deps, env, msg);
+    // the attribute is the same for every handle and outcome, so it only reveals that a tx happened
+    if let Ok(response) = response.as_mut() {
+        if config.activity_logs {
+            response.log.push(log("activity", "1"));
+        }
+    }
+    pad_handle_result(response, BLOCK_SIZE)
+}
+
+fn dispatch_handle<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: HandleMsg,
+) -> StdResult<HandleResponse> {
+    match msg {
+        HandleMsg::Send {
+            content,
+            target,
+            priority,
+            content_type,
+            deliver_after_height,
+            reveal_after_height,
+            expire_after_blocks,
+            release_if_inactive_blocks,
+            tags,
+            receipt_salt,
+            keep_after_read,
+            anonymous,
+            max_views,
+            time_capsule,
+            require_read_signature,
+            mint_read_receipt,
+            token_permit,
+            nft_permit,
+            pow_nonce,
+            invite_code,
+        } => {
+            let options = SendOptions {
+                priority,
+                content_type,
+                deliver_after_height,
+                reveal_after_height,
+                expire_after_blocks,
+                release_if_inactive_blocks,
+                tags: tags.unwrap_or_default(),
+                receipt_salt,
+                keep_after_read: keep_after_read.unwrap_or(false),
+                anonymous: anonymous.unwrap_or(false),
+                max_views,
+                time_capsule: time_capsule.unwrap_or(false),
+                require_read_signature: require_read_signature.unwrap_or(false),
+                mint_read_receipt: mint_read_receipt.unwrap_or(false),
+                token_permit,
+                nft_permit,
+                pow_nonce,
+                invite_code,
+                ..SendOptions::default()
+            };
+            try_send(deps, env, content, target, options)
+        }
+        HandleMsg::Recv { folder, read_signature, block_sender } => {
+            try_receive(deps, env, None, folder, read_signature, block_sender.unwrap_or(false))
+        }
+        HandleMsg::CreateFolder { name, max_messages } => try_create_folder(deps, env, name, max_messages),
+        HandleMsg::DeleteFolder { name } => try_delete_folder(deps, env, name),
+        HandleMsg::MoveMessage { message_id, from, to } => try_move_message(deps, env, message_id, from, to),
+        HandleMsg::Archive { message_id } => try_archive(deps, env, message_id),
+        HandleMsg::SendPoll { question, options, targets } => try_send_poll(deps, env, question, options, targets),
+        HandleMsg::Vote { message_id, option } => try_vote(deps, env, message_id, option),
+        HandleMsg::CreateAlias { ttl } => try_create_alias(deps, env, ttl),
+        HandleMsg::BurnAlias { alias } => try_burn_alias(deps, env, alias),
+        HandleMsg::CreateInvite {} => try_create_invite(deps, env),
+        HandleMsg::RevokeInvite { invite } => try_revoke_invite(deps, env, invite),
+        HandleMsg::SetInviteOnly { enabled } => try_set_invite_only(deps, env, enabled),
+        HandleMsg::SendToAlias { alias, content, pow_nonce } => try_send_to_alias(deps, env, alias, content, pow_nonce),
+        HandleMsg::ReplyAnonymous { token, content } => try_reply_anonymous(deps, env, token, content),
+        HandleMsg::ReadNote { note_id } => try_read_note(deps, env, note_id),
+        HandleMsg::DeleteNote { note_id } => try_delete_note(deps, env, note_id),
+        HandleMsg::RecvByTag { tag, read_signature, block_sender } => {
+            try_receive(deps, env, Some(tag), None, read_signature, block_sender.unwrap_or(false))
+        }
+        HandleMsg::RecvDeadLetter {} => try_receive_dead_letter(deps, env),
+        HandleMsg::SetDeadLetters { enabled } => try_set_dead_letters(deps, env, enabled),
         HandleMsg::Size { } => try_size(deps, env),
         HandleMsg::Block { address } => try_block(deps, env, address),
         HandleMsg::Unblock { address } => try_unblock(deps, env, address),
-    };
-    pad_handle_result(response, BLOCK_SIZE)
+        HandleMsg::BlockSenderOf { message_id } => try_block_sender_of(deps, env, message_id),
+        HandleMsg::Mute { address } => try_mute(deps, env, address),
+        HandleMsg::Unmute { address } => try_unmute(deps, env, address),
+        HandleMsg::SetInboxPrice { price } => try_set_inbox_price(deps, env, price),
+        HandleMsg::SetSpamBond { bond } => try_set_spam_bond(deps, env, bond),
+        HandleMsg::ReportSpam { message_id } => try_report_spam(deps, env, message_id),
+        HandleMsg::ClaimBond { message_id } => try_claim_bond(deps, env, message_id),
+        HandleMsg::AddContact { address } => try_add_contact(deps, env, address),
+        HandleMsg::RemoveContact { address } => try_remove_contact(deps, env, address),
+        HandleMsg::SetAutoBlock { after } => try_set_auto_block(deps, env, after),
+        HandleMsg::SetSenderFilter { filter } => try_set_sender_filter(deps, env, filter),
+        HandleMsg::SetTokenGate { gate } => try_set_token_gate(deps, env, gate),
+        HandleMsg::SetNftGate { gate } => try_set_nft_gate(deps, env, gate),
+        HandleMsg::SetMinSenderAge { blocks } => try_set_min_sender_age(deps, env, blocks),
+        HandleMsg::SetStatus { text, away, share } => try_set_status(deps, env, text, away, share),
+        HandleMsg::SetQuietHours { window, defer } => try_set_quiet_hours(deps, env, window, defer),
+        HandleMsg::SetEvictionNotices { enabled } => try_set_eviction_notices(deps, env, enabled),
+        HandleMsg::SetBounceNotices { enabled } => try_set_bounce_notices(deps, env, enabled),
+        HandleMsg::ImportBlocklist { addresses } => try_import_blocklist(deps, env, addresses),
+        HandleMsg::SetViewingKey { key, label, expire_after_height } => {
+            try_set_viewing_key(deps, env, key, label, expire_after_height)
+        }
+        HandleMsg::CreateViewingKey { entropy, label, expire_after_height } => {
+            try_create_viewing_key(deps, env, entropy, label, expire_after_height)
+        }
+        HandleMsg::RevokeViewingKey { label } => try_revoke_viewing_key(deps, env, label),
+        HandleMsg::RotateStorageKey { } => try_rotate_storage_key(deps, env),
+        HandleMsg::ReadSent { } => try_read_sent(deps, env),
+        HandleMsg::ClearSent { } => try_clear_sent(deps, env),
+        HandleMsg::React { message_id, reaction } => try_react(deps, env, message_id, reaction),
+        HandleMsg::Edit { message_id, new_content } => try_edit(deps, env, message_id, new_content),
+        HandleMsg::Snooze { message_id, until_height } => try_snooze(deps, env, message_id, until_height),
+        HandleMsg::MoveToFront { message_id } => try_move_to_front(deps, env, message_id),
+        HandleMsg::Pin { message_id } => try_pin(deps, env, message_id, true),
+        HandleMsg::Unpin { message_id } => try_pin(deps, env, message_id, false),
+        HandleMsg::Flag { message_id } => try_flag(deps, env, message_id, true),
+        HandleMsg::Unflag { message_id } => try_flag(deps, env, message_id, false),
+        HandleMsg::ClearAll { force } => try_clear_all(deps, env, force),
+        HandleMsg::Heartbeat { } => try_heartbeat(deps, env),
+        HandleMsg::CommitSend { content_hash, target } => try_commit_send(deps, env, content_hash, target),
+        HandleMsg::RevealSend { content, nonce } => try_reveal_send(deps, env, content, nonce),
+        HandleMsg::SendChunk { transfer_id, target, index, total, data, pow_nonce } => {
+            try_send_chunk(deps, env, transfer_id, target, index, total, data, pow_nonce)
+        }
+        HandleMsg::RecvChunk { message_id, offset, len } => try_receive_chunk(deps, env, message_id, offset, len),
+        HandleMsg::AckRecv { message_id } => try_ack_receive(deps, env, message_id),
+        HandleMsg::Prune { limit } => try_prune(deps, env, limit),
+        HandleMsg::RegisterReceive { code_hash } => try_register_receive(deps, env, code_hash),
+        HandleMsg::RegisterReadCallback { code_hash } => try_register_read_callback(deps, env, code_hash),
+        HandleMsg::GrantQuery { querier, key } => try_grant_query(deps, env, querier, key),
+        HandleMsg::RevokeQuery { querier } => try_revoke_query(deps, env, querier),
+        HandleMsg::ChangeAdmin { address } => try_change_admin(deps, env, address),
+        HandleMsg::RenounceAdmin { } => try_renounce_admin(deps, env),
+        HandleMsg::SetContractStatus { status } => try_set_contract_status(deps, env, status),
+        HandleMsg::Withdraw { amount, to } => try_withdraw(deps, env, amount, to),
+        HandleMsg::ProposeConfigChange { change } => try_propose_config_change(deps, env, change),
+        HandleMsg::CloseReceiptEpoch {} => try_close_receipt_epoch(deps, env),
+        HandleMsg::RegisterName { name } => try_register_name(deps, env, name),
+        HandleMsg::TransferName { to } => try_transfer_name(deps, env, to),
+        HandleMsg::ReleaseName {} => try_release_name(deps, env),
+        HandleMsg::SetProfile { display_name, avatar_hash, bio, visibility } => {
+            try_set_profile(deps, env, display_name, avatar_hash, bio, visibility)
+        }
+        HandleMsg::RegisterContracts { addresses } => try_register_contracts(deps, env, addresses, true),
+        HandleMsg::DeregisterContracts { addresses } => try_register_contracts(deps, env, addresses, false),
+        HandleMsg::RegisterRelay { address, code_hash } => try_register_relay(deps, env, address, code_hash),
+        HandleMsg::DeregisterRelay { address } => try_deregister_relay(deps, env, address),
+        HandleMsg::RelaySend { origin, target, content, origin_hidden, pow_nonce } => {
+            try_relay_send(deps, env, origin, target, content, origin_hidden, pow_nonce)
+        }
+        HandleMsg::ExecuteConfigChange {} => try_execute_config_change(deps, env),
+        HandleMsg::CancelConfigChange {} => try_cancel_config_change(deps, env),
+        HandleMsg::PurgeQueue { address } => try_purge_queue(deps, env, address),
+        HandleMsg::PurgeAll { limit } => try_purge_all(deps, env, limit),
+        HandleMsg::Receive { from, amount, msg, .. } => try_receive_postage(deps, env, from, amount, msg),
+        HandleMsg::Batch { ops } => try_batch(deps, env, ops),
+        HandleMsg::SetSigningKey { pubkey } => try_set_signing_key(deps, env, pubkey),
+        HandleMsg::SendSigned { payload, signature, pubkey } => try_send_signed(deps, env, payload, signature, pubkey),
+        HandleMsg::GrantSession { pubkey, expires, permissions } => {
+            try_grant_session(deps, env, pubkey, expires, permissions)
+        }
+        HandleMsg::RevokeSession { pubkey } => try_revoke_session(deps, env, pubkey),
+        HandleMsg::RevokePermit { permit_name } => try_revoke_permit(deps, env, permit_name),
+        HandleMsg::RevokeAllPermits {} => try_revoke_all_permits(deps, env),
+        HandleMsg::SessionCall { payload, signature, pubkey } => try_session_call(deps, env, payload, signature, pubkey),
+    }
+}
+
+/// Runs each operation as if it was sent on its own and collects their answers. An error in any
+/// operation fails the whole batch.
+pub fn try_batch<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    ops: Vec<BatchOp>,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut results: Vec<HandleAnswer> = vec![];
+    let mut messages: Vec<CosmosMsg> = vec![];
+
+    if ops.len() > MAX_BATCH_OPS {
+        status = Failure;
+        code = CODE_LIMIT_REACHED;
+        response_message.push_str(&format!("A batch can hold at most {} operations.", MAX_BATCH_OPS));
+    } else {
+        for op in ops {
+            let msg = match op {
+                BatchOp::Send { content, target } => HandleMsg::Send {
+                    content,
+                    target,
+                    priority: None,
+                    content_type: None,
+                    deliver_after_height: None,
+                    reveal_after_height: None,
+                    expire_after_blocks: None,
+                    release_if_inactive_blocks: None,
+                    tags: None,
+                    receipt_salt: None,
+                    keep_after_read: None,
+                    anonymous: None,
+                    max_views: None,
+                    time_capsule: None,
+                    require_read_signature: None,
+                    mint_read_receipt: None,
+                    token_permit: None,
+                    nft_permit: None,
+                    pow_nonce: None,
+                    invite_code: None,
+                },
+                BatchOp::Recv {} => HandleMsg::Recv { folder: None, read_signature: None, block_sender: None },
+                BatchOp::Size {} => HandleMsg::Size {},
+                BatchOp::Block { address } => HandleMsg::Block { address },
+                BatchOp::Unblock { address } => HandleMsg::Unblock { address },
+                BatchOp::React { message_id, reaction } => HandleMsg::React { message_id, reaction },
+                BatchOp::AckRecv { message_id } => HandleMsg::AckRecv { message_id },
+                BatchOp::Heartbeat {} => HandleMsg::Heartbeat {},
+            };
+            // attached funds cannot be split between operations, so none of them can spend them
+            let mut op_env = env.clone();
+            op_env.message.sent_funds = vec![];
+            let mut response = dispatch_handle(deps, op_env, msg)?;
+            messages.append(&mut response.messages);
+            if let Some(data) = response.data {
+                results.push(from_binary(&data)?);
+            }
+        }
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Batch executed.");
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Batch {
+            status,
+            message: response_message,
+            code,
+            results,
+        })?),
+    })
+}
+
+/// optional settings of a send request
+#[derive(Default)]
+pub struct SendOptions {
+    pub priority: Option<u8>,
+    pub content_type: Option<String>,
+    pub deliver_after_height: Option<u64>,
+    pub reveal_after_height: Option<u64>,
+    pub expire_after_blocks: Option<u64>,
+    pub release_if_inactive_blocks: Option<u64>,
+    pub tags: Vec<String>,
+    pub receipt_salt: Option<Binary>,
+    /// amount of the postage token paid on top of the contract's postage
+    pub token_paid: u128,
+    /// true if the contract's postage was already paid in the postage token
+    pub postage_paid: bool,
+    /// for notes to self, true if reading the note does not delete it
+    pub keep_after_read: bool,
+    /// the recipient gets a reply token instead of the sender's address
+    pub anonymous: bool,
+    /// keeps the message out of the sender's sent items and leaves out the recipient's status,
+    /// for replies to anonymous messages
+    pub hide_target: bool,
+    /// number of reads before the message explodes
+    pub max_views: Option<u32>,
+    /// seals the content until reveal_after_height and rules out edits by the sender
+    pub time_capsule: bool,
+    /// the recipient has to sign the message id to read it
+    pub require_read_signature: bool,
+    /// the sender gets a read receipt NFT when the message explodes
+    pub mint_read_receipt: bool,
+    /// permit for the recipient's gate token
+    pub token_permit: Option<TokenPermit>,
+    /// permit for the recipient's gate collection
+    pub nft_permit: Option<TokenPermit>,
+    /// nonce of the proof of work
+    pub pow_nonce: Option<u64>,
+    /// what the proof of work names as target, the target address if None
+    pub pow_target: Option<String>,
+    /// the send needs no proof of work
+    pub pow_exempt: bool,
+    /// invite code of the recipient, for a first message to an invite-only inbox
+    pub invite_code: Option<String>,
+    /// the send needs no invite code
+    pub invite_exempt: bool,
+    /// who attached the funds and gets back what the send does not use, the sender if None
+    pub payer: Option<HumanAddr>,
+    /// block height of the commitment a revealed send was committed at
+    pub committed_height: Option<u64>,
 }
 
 pub fn try_send<S: Storage, A: Api, Q: Querier>(
@@ -87,232 +636,5190 @@ pub fn try_send<S: Storage, A: Api, Q: Querier>(
     env: Env,
     content: String,
     target: HumanAddr,
+    options: SendOptions,
 ) -> StdResult<HandleResponse> {
     let status: ResponseStatus;
+    let code: u16;
     let mut response_message = String::new();
+    let mut message_id: Option<Uint128> = None;
+    let mut receipt: Option<Binary> = None;
+    let mut recipient_status: Option<PresenceInfo> = None;
+    let mut messages: Vec<CosmosMsg> = vec![];
+    // invite code used up once the send succeeds, with the sender it makes a contact
+    let mut pending_invite: Option<(String, Option<CanonicalAddr>)> = None;
+    // the recipient's price or bond, once the send got far enough to require it
+    let mut charged: Option<Fee> = None;
+    let payer = options.payer.clone().unwrap_or_else(|| env.message.sender.clone());
+    let token_paid = options.token_paid;
+
+    // the age gate counts from the first message, which for postage sends, relayed, signed and
+    // session sends is recorded for the sender rather than who submitted the tx
+    record_first_seen(&mut deps.storage, &deps.api.canonical_address(&env.message.sender)?, env.block.height);
 
     let config: Config = load(&mut deps.storage, CONFIG_KEY)?;
-    let seq: u128 = load(&mut deps.storage, SEQ_KEY)?;
+    let priority = options.priority.unwrap_or(0);
 
-    let content_byte_slice: &[u8] = content.as_bytes();
-    if content_byte_slice.len() > config.max_message_size.into() {
+    let payload = decode_payload(&config, &content);
+    let content_byte_slice: &[u8] = payload.as_deref().unwrap_or_default();
+    let send_fee = match &config.send_fee {
+        Some(send_fee) if !options.postage_paid => Some(Fee::Native {
+            denom: send_fee.denom.clone(),
+            amount: send_fee.amount_for(content_byte_slice.len()),
+        }),
+        _ => None,
+    };
+    if config.status == ContractStatus::ReadOnly {
+        status = Failure;
+        code = CODE_READ_ONLY;
+        response_message.push_str("Sending is disabled during maintenance.");
+    } else if payload.is_none() {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str(&format!("Content must be {}.", payload_description(&config)));
+    } else if content_byte_slice.len() > config.max_message_size.into() {
+        status = Failure;
+        code = CODE_TOO_LONG;
+        response_message.push_str("Message is too long.");
+    } else if matches!(options.content_type.as_ref(), Some(t) if t.len() > MAX_CONTENT_TYPE_SIZE) {
+        status = Failure;
+        code = CODE_TOO_LONG;
+        response_message.push_str("Content type is too long.");
+    } else if options.tags.len() > MAX_TAGS || options.tags.iter().any(|tag| tag.is_empty()) {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str(&format!("A message can carry at most {} non-empty tags.", MAX_TAGS));
+    } else if options.tags.iter().any(|tag| tag.len() > MAX_TAG_SIZE) {
+        status = Failure;
+        code = CODE_TOO_LONG;
+        response_message.push_str("Tag is too long.");
+    } else if matches!(options.receipt_salt.as_ref(), Some(salt) if salt.len() < MIN_RECEIPT_SALT_SIZE) {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str(&format!("Receipt salt must be at least {} bytes.", MIN_RECEIPT_SALT_SIZE));
+    } else if matches!(options.max_views, Some(views) if views == 0 || views > MAX_VIEWS) {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str(&format!("A message can be read 1-{} times.", MAX_VIEWS));
+    } else if options.time_capsule && !matches!(options.reveal_after_height, Some(reveal) if reveal > env.block.height) {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("A time capsule needs a reveal_after_height in the future.");
+    } else if options.time_capsule && options.release_if_inactive_blocks.is_some() {
+        // heartbeats would let the sender hold the capsule back forever
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("A time capsule cannot be held back by a dead man's switch.");
+    } else if options.time_capsule && matches!(options.expire_after_blocks,
+        Some(blocks) if env.block.height.saturating_add(blocks) <= options.reveal_after_height.unwrap_or_default()) {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("A time capsule cannot expire before it is revealed.");
+    } else if options.mint_read_receipt && config.receipt_nft.is_none() {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("This deployment does not mint read receipts.");
+    } else if options.anonymous && options.receipt_salt.is_some() {
+        // a receipt would name the sender
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("Anonymous messages cannot have a receipt.");
+    } else if options.anonymous && options.mint_read_receipt {
+        // the read receipt is minted to the sender in the recipient's transaction
         status = Failure;
-        response_message.push_str(&format!("Message is too long."));
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("Anonymous messages cannot have a read receipt NFT.");
+    } else if target == env.message.sender {
+        // notes to self skip the queue, the recipient's settings and the inbox price, and only
+        // pay the contract's postage
+        let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+        if !charges_covered(send_fee.as_ref(), None, &env.message.sent_funds, options.token_paid) {
+            status = Failure;
+            code = CODE_INSUFFICIENT_FEE;
+            response_message.push_str("The attached funds do not cover the postage.");
+        } else {
+            let note = Note {
+                content: content_byte_slice.to_vec(),
+                content_size: 0,
+                content_type: options.content_type,
+                height: env.block.height,
+                timestamp: env.block.time,
+                keep: options.keep_after_read,
+            };
+            match write_note(&mut deps.storage, &sender_address_raw, note, &config.padding_buckets)? {
+                Some(note_id) => {
+                    if let Some(send_fee) = &send_fee {
+                        credit_revenue(&mut deps.storage, send_fee)?;
+                    }
+                    message_id = Some(Uint128(u128::from(note_id)));
+                    status = Success;
+                    code = CODE_OK;
+                    response_message.push_str("Note saved.");
+                }
+                None => {
+                    status = Failure;
+                    code = CODE_LIMIT_REACHED;
+                    response_message.push_str(&format!("You already have {} notes.", MAX_NOTES));
+                }
+            }
+        }
     } else {
         let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
         let target_address_raw = deps.api.canonical_address(&target)?;
+        // anonymous messages are counted together, under the anonymous sender
+        let index_sender = if options.anonymous { anonymous_sender() } else { sender_address_raw.clone() };
 
-        let mut message_queue_storage = MessageQueueStorage::from_storage(&mut deps.storage);
-        let mut message_queue = message_queue_storage.get_message_queue(&target_address_raw);
+        let message_queue_storage = ReadonlyMessageQueueStorage::from_storage(&deps.storage);
+        let message_queue = message_queue_storage.get_message_queue(&target_address_raw);
 
-        if message_queue.blocked.contains(&sender_address_raw.as_slice().to_vec()) {
+        // a recipient sets either a price or a bond, never both
+        let (fee, bond) = inbox_fee(&deps.storage, &target_address_raw, &sender_address_raw);
+        let charge = fee.as_ref().or(bond.as_ref());
+        charged = charge.cloned();
+        let quiet_hours = active_quiet_hours(&deps.storage, &target_address_raw, env.block.height, env.block.time);
+        let refused = refuses_sender(&deps.storage, &config, &target_address_raw, &sender_address_raw)?
+            || matches!(quiet_hours.as_ref(), Some(quiet_hours) if !quiet_hours.defer);
+        let pow_target = options.pow_target.as_deref().unwrap_or(target.as_str());
+        let invite_needed = !options.invite_exempt && needs_invite(&deps.storage, &target_address_raw, &sender_address_raw);
+        if invite_needed {
+            // an anonymous sender uses up the code without becoming a contact
+            let contact = if options.anonymous { None } else { Some(sender_address_raw.clone()) };
+            pending_invite = options.invite_code.clone().map(|invite| (invite, contact));
+        }
+        // with uniform failures a refusal is only reported where a full queue would be, so a
+        // sender cannot tell the two apart by the answer or by how far the send got
+        if !options.pow_exempt
+            && !pow_valid(config.pow_difficulty, &env.message.sender, pow_target, content_byte_slice, options.pow_nonce) {
             status = Failure;
-            response_message.push_str(&format!("Message could not be sent."));
-        } else if (message_queue.length == config.max_messages) && config.discard {
+            code = CODE_POW_REQUIRED;
+            response_message.push_str("The proof of work does not meet the difficulty.");
+        } else if invite_needed && !invite_valid(&deps.storage, &target_address_raw, options.invite_code.as_deref())? {
             status = Failure;
-            response_message.push_str(&format!("Message could not be sent."));
-        } else {
-            let mut message_storage = MessageStorage::from_storage(&mut deps.storage);
-
-            // will only happen if config.discard is false
-            if message_queue.length == config.max_messages {
-                // remove front message
-                let front_message: Option<Message> = message_storage.get_message(&message_queue.front);
-                if let Some(found_front_message) = front_message {
-                    // remove the front message
-                    message_storage.remove_message(&message_queue.front);
-                    message_queue.front = found_front_message.next;
-                    message_queue.length -= 1;
+            code = CODE_GATED;
+            response_message.push_str("A first message to the recipient needs one of their invite codes.");
+        } else if options.anonymous && bond.is_some() {
+            // the bond is refunded to the sender in the recipient's transaction
+            status = Failure;
+            code = CODE_INVALID_INPUT;
+            response_message.push_str("Anonymous messages cannot be sent to an inbox that requires a bond.");
+        } else if options.require_read_signature && read_signing_key(&deps.storage, &target_address_raw).is_none() {
+            status = Failure;
+            code = CODE_INVALID_INPUT;
+            response_message.push_str("The recipient has no signing key to sign reads with.");
+        } else if refused && !config.uniform_failures {
+            status = Failure;
+            code = CODE_NOT_DELIVERED;
+            response_message.push_str("Message could not be sent.");
+        } else if !token_gate_passed(
+            &deps.storage, &deps.api, &deps.querier, &target_address_raw, &sender_address_raw, options.token_permit.as_ref(),
+        )? {
+            status = Failure;
+            code = CODE_GATED;
+            response_message.push_str("A permit showing enough of the recipient's gate token is required.");
+        } else if !nft_gate_passed(
+            &deps.storage, &deps.api, &deps.querier, &target_address_raw, &sender_address_raw, options.nft_permit.as_ref(),
+        )? {
+            status = Failure;
+            code = CODE_GATED;
+            response_message.push_str("A permit showing a token of the recipient's gate collection is required.");
+        } else if !age_gate_passed(&deps.storage, &target_address_raw, &sender_address_raw, env.block.height)? {
+            status = Failure;
+            code = CODE_GATED;
+            response_message.push_str("The recipient only accepts senders who have used the contract for longer.");
+        } else if !charges_covered(send_fee.as_ref(), charge, &env.message.sent_funds, options.token_paid) {
+            status = Failure;
+            code = CODE_INSUFFICIENT_FEE;
+            response_message.push_str("The attached funds do not cover the postage.");
+        } else if options.release_if_inactive_blocks.is_some() || quiet_hours.is_some() {
+            // hold the message back until the sender stops sending heartbeats, or until the
+            // recipient's quiet hours are over
+            let deferred = options.release_if_inactive_blocks.is_none();
+            let mut release_storage = ScheduledReleaseStorage::from_storage(&mut deps.storage)?;
+            let mut releases = release_storage.get_releases(&target_address_raw)?;
+            if refused || releases.len() >= config.max_messages as usize {
+                notify_bounce(
+                    &mut deps.storage, &config, &sender_address_raw, &target_address_raw, env.block.height, env.block.time,
+                )?;
+                status = Failure;
+                code = CODE_NOT_DELIVERED;
+                response_message.push_str("Message could not be sent.");
+            } else {
+                releases.push(ScheduledRelease {
+                    from: sender_address_raw.clone(),
+                    content: content_byte_slice.to_vec(),
+                    priority,
+                    content_type: options.content_type,
+                    height: env.block.height,
+                    timestamp: env.block.time,
+                    release_if_inactive_blocks: options.release_if_inactive_blocks.unwrap_or(0),
+                    fee,
+                    bond,
+                    tags: options.tags,
+                    quiet: deferred,
+                    anonymous: options.anonymous,
+                    views_left: options.max_views,
+                    unlock_height: if options.time_capsule { options.reveal_after_height } else { None },
+                    signed_read: options.require_read_signature,
+                    mint_receipt: options.mint_read_receipt,
+                });
+                release_storage.set_releases(&target_address_raw, releases)?;
+                if !deferred {
+                    write_heartbeat(&mut deps.storage, &sender_address_raw, env.block.height)?;
+                }
+                if let Some(send_fee) = &send_fee {
+                    credit_revenue(&mut deps.storage, send_fee)?;
+                }
+                if let Some(salt) = &options.receipt_salt {
+                    receipt = Some(store_receipt(
+                        &mut deps.storage, &env, &target, &sender_address_raw, &target_address_raw, content_byte_slice, salt,
+                    )?);
+                }
+
+                status = Success;
+                code = CODE_OK;
+                if deferred {
+                    response_message.push_str("Message deferred until the recipient's quiet hours are over.");
                 } else {
-                    // this should never happen (empty queue but also length equal to max)
-                    return Err(StdError::generic_err("Corrupted message queue."));
+                    response_message.push_str("Message deposited.");
                 }
             }
+        } else if sender_at_limit(&deps.storage, &config, &target_address_raw, &index_sender) {
+            status = Failure;
+            code = CODE_LIMIT_REACHED;
+            if options.anonymous {
+                response_message.push_str(&format!(
+                    "The inbox already has {} unread anonymous messages.", config.max_messages_per_sender
+                ));
+            } else {
+                response_message.push_str(&format!(
+                    "You already have {} unread messages in this inbox.", config.max_messages_per_sender
+                ));
+            }
+        } else if refused || ((message_queue.length >= config.max_messages) && !config.eviction_policy.makes_room()) {
+            // refusals only get this far with uniform failures, where they bounce like a full queue
+            notify_bounce(
+                &mut deps.storage, &config, &sender_address_raw, &target_address_raw, env.block.height, env.block.time,
+            )?;
+            status = Failure;
+            code = CODE_NOT_DELIVERED;
+            response_message.push_str(&format!("Message could not be sent."));
+        } else {
             // prepare new message
-            let mut new_message = Message {
-                content: content_byte_slice.to_vec(),
-                from: sender_address_raw,
-                prev: 0,
-                next: 0
+            let new_message = Message {
+                priority,
+                content_type: options.content_type,
+                deliver_after_height: options.deliver_after_height,
+                reveal_after_height: options.reveal_after_height,
+                expire_after_height: options.expire_after_blocks.map(|blocks| env.block.height.saturating_add(blocks)),
+                committed_height: options.committed_height,
+                fee,
+                tags: options.tags,
+                anonymous: options.anonymous,
+                views_left: options.max_views,
+                time_capsule: options.time_capsule,
+                signed_read: options.require_read_signature,
+                mint_receipt: options.mint_read_receipt,
+                ..Message::new(content_byte_slice.to_vec(), sender_address_raw.clone(), env.block.height, env.block.time)
             };
-
-            // get current rear message
-            let rear_message: Option<Message> = message_storage.get_message(&message_queue.rear);
-
-            if let Some(mut found_rear_message) = rear_message {
-                found_rear_message.next = seq.clone();
-                // update rear message in the message storage
-                message_storage.set_message(&message_queue.rear, found_rear_message);
-                new_message.prev = message_queue.rear.clone();
-            } else {
-                // message is first entry in queue
-                message_queue.front = seq.clone();
+            let id = enqueue_message(&mut deps.storage, &config, &target_address_raw, message_queue, new_message)?;
+            let muted = read_muted_message(&deps.storage, id)?.is_some();
+            if let Some(bond) = bond {
+                hold_bond(&mut deps.storage, &target_address_raw, id, Bond { sender: sender_address_raw.clone(), amount: bond })?;
+            }
+            if let Some(send_fee) = &send_fee {
+                credit_revenue(&mut deps.storage, send_fee)?;
+            }
+            if let Some(salt) = &options.receipt_salt {
+                receipt = Some(store_receipt(
+                    &mut deps.storage, &env, &target, &sender_address_raw, &target_address_raw, content_byte_slice, salt,
+                )?);
             }
-            message_storage.set_message(&seq.clone(), new_message);
-            message_queue.rear = seq.clone();
-            message_queue.length += 1;
 
-            // update the message queue in storage
-            let mut message_queue_storage = MessageQueueStorage::from_storage(&mut deps.storage);
-            message_queue_storage.set_message_queue(&target_address_raw, message_queue);
+            // keep a record in the sender's sent items folder
+            if config.max_sent_items > 0 && !options.hide_target {
+                let sent_item = SentItem {
+                    id,
+                    to: target_address_raw.clone(),
+                    size: content_byte_slice.len() as u32,
+                    content_hash: sha_256(content_byte_slice).to_vec(),
+                    timestamp: env.block.time,
+                };
+                let mut sent_items_storage = SentItemsStorage::from_storage(&mut deps.storage);
+                sent_items_storage.push_sent_item(&sender_address_raw, sent_item, config.max_sent_items);
+            }
 
-            // increment message id sequence
-            save(&mut deps.storage, SEQ_KEY, &(seq + 1))?;
+            // the callback would tell the receiving contract who sent the message, and there is no
+            // message to announce if the target muted the sender
+            if !options.anonymous && !muted {
+                if let Some(callback) = receiver_callback(&deps.storage, &target_address_raw, &target, &env.message.sender, id)? {
+                    messages.push(callback);
+                }
+            }
 
+            message_id = Some(Uint128(u128::from(id)));
+            if !options.hide_target {
+                recipient_status = shared_presence(&deps.storage, &target_address_raw);
+            }
             status = Success;
+            code = CODE_OK;
             response_message.push_str(&format!("Message sent."));
         }
     }
+    if code != CODE_OK {
+        // a failed send is reverted, so the attached funds go back to the payer
+        if !env.message.sent_funds.is_empty() {
+            return Err(StdError::generic_err(response_message));
+        }
+    } else {
+        if let Some((invite, contact)) = pending_invite {
+            redeem_invite(&mut deps.storage, &invite, contact.as_ref())?;
+        }
+        messages.extend(refund_excess(
+            &deps.api, &config, &env.contract.address, &payer, send_fee.as_ref(), charged.as_ref(),
+            &env.message.sent_funds, token_paid,
+        )?);
+    }
     Ok(HandleResponse {
-        messages: vec![],
+        messages,
         log: vec![],
         data: Some(to_binary(&HandleAnswer::Send {
             status,
             message: response_message,
+            code,
+            message_id,
+            receipt,
+            recipient_status,
         })?),
     })
 }
 
-pub fn try_receive<S: Storage, A: Api, Q: Querier>(
+/// Answers the sender of an anonymous message with a regular message from the caller. The token
+/// only works for the recipient of the anonymous message and is used up by a successful reply.
+pub fn try_reply_anonymous<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
+    token: Binary,
+    content: String,
 ) -> StdResult<HandleResponse> {
-    let status: ResponseStatus;
-    let mut response_message = String::new();
-    let mut number_of_unread_messages: u32 = 0;
-    let mut content: Option<String> = None;
-    let mut sender: Option<HumanAddr> = None;
-
     let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
-    let mut message_queue_storage = MessageQueueStorage::from_storage(&mut deps.storage);
-    let mut message_queue = message_queue_storage.get_message_queue(&sender_address_raw);
-
-    if message_queue.length == 0 {
-        status = Failure;
-        response_message.push_str(&format!("No messages."));
-    } else {
-        // get message at front of the queue
-        let mut message_storage = MessageStorage::from_storage(&mut deps.storage);
-        // remove front message
-        let mes: Option<Message> = message_storage.get_message(&message_queue.front);
-        if let Some(found_mes) = mes {
-            content = String::from_utf8(found_mes.content).ok();
-            sender = deps.api.human_address(&found_mes.from).ok();
-            // explode the message
-            message_storage.remove_message(&message_queue.front);
-            message_queue.front = found_mes.next;
-            message_queue.length -= 1;
-            number_of_unread_messages = message_queue.length.clone();
-
-            // store new version of message queue
-            let mut message_queue_storage = MessageQueueStorage::from_storage(&mut deps.storage);
-            message_queue_storage.set_message_queue(&sender_address_raw, message_queue);
-            status = Success;
-        } else {
-            // this should never happen (queue length > 0 but front message is not in message store)
-            return Err(StdError::generic_err("Corrupted message queue."));
+    let token_hash = sha_256(token.as_slice());
+    let record = read_reply_token(&deps.storage, &token_hash)?;
+    let record = match record {
+        Some(record) if record.recipient == sender_address_raw => record,
+        _ => {
+            return Ok(HandleResponse {
+                messages: vec![],
+                log: vec![],
+                data: Some(to_binary(&HandleAnswer::ReplyAnonymous {
+                    status: Failure,
+                    message: String::from("Invalid reply token."),
+                    code: CODE_NOT_FOUND,
+                    message_id: None,
+                })?),
+            });
         }
+    };
+
+    // the reply is sent like any other message, without revealing the target to the replier
+    let target = deps.api.human_address(&record.sender)?;
+    // a token allows a single reply and the original sender started the conversation, so replies
+    // need neither a proof of work nor an invite code
+    let options = SendOptions { hide_target: true, pow_exempt: true, invite_exempt: true, ..SendOptions::default() };
+    let response = try_send(deps, env, content, target, options)?;
+    let (status, message, code, message_id) = match response.data.as_ref().map(from_binary::<HandleAnswer>).transpose()? {
+        Some(HandleAnswer::Send { status, message, code, message_id, .. }) => (status, message, code, message_id),
+        _ => return Err(StdError::generic_err("Unexpected send response.")),
+    };
+    if code == CODE_OK {
+        remove_reply_token(&mut deps.storage, &token_hash);
     }
 
     Ok(HandleResponse {
-        messages: vec![],
+        messages: response.messages,
         log: vec![],
-        data: Some(to_binary(&HandleAnswer::Recv {
+        data: Some(to_binary(&HandleAnswer::ReplyAnonymous {
             status,
-            message: response_message,
-            number_of_unread_messages,
-            content,
-            sender,
+            message,
+            code,
+            message_id,
         })?),
     })
 }
 
-pub fn try_size<S: Storage, A: Api, Q: Querier>(
+/// Returns the status of a recipient if they share it with senders
+fn shared_presence<S: ReadonlyStorage>(storage: &S, recipient: &CanonicalAddr) -> Option<PresenceInfo> {
+    match ReadonlyUserSettingsStorage::from_storage(storage).get_settings(recipient).presence {
+        Some(presence) if presence.shared => Some(PresenceInfo { text: presence.text, away: presence.away }),
+        _ => None,
+    }
+}
+
+/// Stores the public receipt of a sent message and returns its hash, which is
+/// sha256(sender ++ 0x00 ++ target ++ 0x00 ++ height as 8 big-endian bytes ++ sha256(salt) ++ content)
+/// over the human-readable addresses, so parties who know the message can prove it was sent
+fn store_receipt<S: Storage>(
+    storage: &mut S,
+    env: &Env,
+    target: &HumanAddr,
+    sender_raw: &CanonicalAddr,
+    target_raw: &CanonicalAddr,
+    content: &[u8],
+    salt: &Binary,
+) -> StdResult<Binary> {
+    let mut preimage = env.message.sender.as_str().as_bytes().to_vec();
+    preimage.push(0);
+    preimage.extend_from_slice(target.as_str().as_bytes());
+    preimage.push(0);
+    preimage.extend_from_slice(&env.block.height.to_be_bytes());
+    preimage.extend_from_slice(&sha_256(salt.as_slice()));
+    preimage.extend_from_slice(content);
+    let hash = sha_256(&preimage);
+
+    let mut epoch = open_receipt_epoch(storage, env.block.height)?;
+    if receipt_epoch_due(&epoch, env.block.height) {
+        epoch = close_receipt_epoch(storage, epoch, env.block.height)?;
+    }
+    let index = epoch.leaves.len() as u32;
+    epoch.leaves.push(leaf_hash(&hash));
+    save(storage, RECEIPT_EPOCH_KEY, &epoch)?;
+
+    let receipt = Receipt {
+        height: env.block.height,
+        timestamp: env.block.time,
+        sender: sender_raw.clone(),
+        recipient: target_raw.clone(),
+        epoch: epoch.id,
+        index,
+    };
+    write_receipt(storage, &hash, &receipt)?;
+    Ok(Binary(hash.to_vec()))
+}
+
+/// Returns the open receipt epoch, starting the first one at `height`
+fn open_receipt_epoch<S: ReadonlyStorage>(storage: &S, height: u64) -> StdResult<ReceiptEpoch> {
+    Ok(may_load(storage, RECEIPT_EPOCH_KEY)?.unwrap_or(ReceiptEpoch {
+        id: 0,
+        start_height: height,
+        end_height: 0,
+        leaves: vec![],
+        root: None,
+    }))
+}
+
+fn receipt_epoch_due(epoch: &ReceiptEpoch, height: u64) -> bool {
+    !epoch.leaves.is_empty()
+        && (epoch.leaves.len() >= MAX_RECEIPTS_PER_EPOCH
+            || height >= epoch.start_height.saturating_add(RECEIPT_EPOCH_BLOCKS))
+}
+
+/// Folds the receipts of an epoch into its Merkle root and returns the next, empty epoch
+fn close_receipt_epoch<S: Storage>(storage: &mut S, mut epoch: ReceiptEpoch, height: u64) -> StdResult<ReceiptEpoch> {
+    epoch.root = Some(merkle_root(&epoch.leaves));
+    epoch.end_height = height;
+    write_closed_epoch(storage, &epoch)?;
+    Ok(ReceiptEpoch {
+        id: epoch.id + 1,
+        start_height: height,
+        end_height: 0,
+        leaves: vec![],
+        root: None,
+    })
+}
+
+pub fn try_close_receipt_epoch<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
 ) -> StdResult<HandleResponse> {
     let status: ResponseStatus;
-    let config: Config = load(&mut deps.storage, CONFIG_KEY)?;
-    let response_message = String::from(&format!("Maximum number of messages allowed: {}", config.max_messages));
+    let code: u16;
+    let mut response_message = String::new();
+    let mut closed: Option<u64> = None;
 
-    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
-    let mut message_queue_storage = MessageQueueStorage::from_storage(&mut deps.storage);
-    let message_queue = message_queue_storage.get_message_queue(&sender_address_raw);
-    status = Success;
+    let epoch = open_receipt_epoch(&deps.storage, env.block.height)?;
+    if receipt_epoch_due(&epoch, env.block.height) {
+        closed = Some(epoch.id);
+        let next = close_receipt_epoch(&mut deps.storage, epoch, env.block.height)?;
+        save(&mut deps.storage, RECEIPT_EPOCH_KEY, &next)?;
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Receipt epoch closed.");
+    } else {
+        status = Failure;
+        code = CODE_SEALED;
+        response_message.push_str(&format!(
+            "The receipt epoch is empty or can be closed at height {}.",
+            epoch.start_height.saturating_add(RECEIPT_EPOCH_BLOCKS)
+        ));
+    }
 
     Ok(HandleResponse {
         messages: vec![],
         log: vec![],
-        data: Some(to_binary(&HandleAnswer::Size {
+        data: Some(to_binary(&HandleAnswer::CloseReceiptEpoch {
             status,
             message: response_message,
-            number_of_unread_messages: message_queue.length,
+            code,
+            epoch: closed,
         })?),
     })
 }
 
-pub fn try_block<S: Storage, A: Api, Q: Querier>(
+/// Handles a SNIP-20 send of the postage token carrying a message to deliver. Failing to
+/// deliver the message is an error, so the token transfer is reverted with it.
+pub fn try_receive_postage<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    address: HumanAddr,
+    from: HumanAddr,
+    amount: Uint128,
+    msg: Option<Binary>,
 ) -> StdResult<HandleResponse> {
-    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
-    let blocked_address_raw = deps.api.canonical_address(&address)?;
-    let mut message_queue_storage = MessageQueueStorage::from_storage(&mut deps.storage);
-    let mut message_queue = message_queue_storage.get_message_queue(&sender_address_raw);
-    if !message_queue.blocked.contains(&blocked_address_raw.as_slice().to_vec()) {
-        // only write to the storage if needed
-        message_queue.blocked.insert(blocked_address_raw.as_slice().to_vec());
-        message_queue_storage.set_message_queue(&sender_address_raw, message_queue);
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let postage = match &config.postage {
+        Some(postage) if postage.token == deps.api.canonical_address(&env.message.sender)? => postage.clone(),
+        _ => return Err(StdError::unauthorized()),
+    };
+    let (target, content) = match msg {
+        Some(msg) => match from_binary(&msg)? {
+            ReceiveMsg::Deliver { target, content } => (target, content),
+        },
+        None => return Err(StdError::generic_err("Missing message to deliver.")),
+    };
+    // postage counts stored bytes like a regular send, invalid content is refused by try_send
+    let payload_size = decode_payload(&config, &content).map_or(0, |payload| payload.len());
+    let postage_amount = postage.amount_for(payload_size);
+    if amount.u128() < postage_amount {
+        return Err(StdError::generic_err("Insufficient postage."));
     }
 
-    let status: ResponseStatus = Success;
-    let response_message = String::from(&format!("Address {} blocked.", address));
-
-    Ok(HandleResponse {
-        messages: vec![],
-        log: vec![],
-        data: Some(to_binary(&HandleAnswer::Block {
-            status,
-            message: response_message,
-        })?),
-    })
+    // send on behalf of the owner of the tokens
+    let mut send_env = env;
+    send_env.message.sender = from;
+    send_env.message.sent_funds = vec![];
+    let options = SendOptions {
+        token_paid: amount.u128() - postage_amount,
+        postage_paid: true,
+        ..SendOptions::default()
+    };
+    let response = try_send(deps, send_env, content, target, options)?;
+    if let Some(data) = &response.data {
+        if let HandleAnswer::Send { status: Failure, message, .. } = from_binary(data)? {
+            return Err(StdError::generic_err(message));
+        }
+    }
+    credit_revenue(&mut deps.storage, &Fee::Token { amount: postage_amount })?;
+    Ok(response)
 }
 
-pub fn try_unblock<S: Storage, A: Api, Q: Querier>(
+pub fn try_set_signing_key<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    address: HumanAddr,
+    pubkey: Binary,
 ) -> StdResult<HandleResponse> {
-    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
-    let blocked_address_raw = deps.api.canonical_address(&address)?;
-    let mut message_queue_storage = MessageQueueStorage::from_storage(&mut deps.storage);
-    let mut message_queue = message_queue_storage.get_message_queue(&sender_address_raw);
-    if message_queue.blocked.contains(&blocked_address_raw.as_slice().to_vec()) {
-        // only write to the storage if needed
-        message_queue.blocked.remove(&blocked_address_raw.as_slice().to_vec());
-        message_queue_storage.set_message_queue(&sender_address_raw, message_queue);
-    }
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
 
-    let status: ResponseStatus = Success;
-    let response_message = String::from(&format!("Address {} unblocked.", address));
+    if PublicKey::parse(pubkey.as_slice()).is_err() {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("Invalid public key.");
+    } else {
+        let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+        write_signing_key(&mut deps.storage, &sender_address_raw, pubkey.as_slice());
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Signing key set.");
+    }
 
     Ok(HandleResponse {
         messages: vec![],
         log: vec![],
-        data: Some(to_binary(&HandleAnswer::Unblock {
+        data: Some(to_binary(&HandleAnswer::SetSigningKey {
             status,
             message: response_message,
+            code,
         })?),
     })
 }
 
-pub fn query<S: Storage, A: Api, Q: Querier>(
-    _deps: &Extern<S, A, Q>,
-    msg: QueryMsg,
-) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::Ping {} => to_binary(&query_ping()?),
+/// Sends a message signed offline by its sender and submitted by a relayer, who pays the gas.
+/// Invalid, expired or replayed signatures are errors.
+pub fn try_send_signed<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    payload: Binary,
+    signature: Binary,
+    pubkey: Binary,
+) -> StdResult<HandleResponse> {
+    let signed: SignedSend = from_binary(&payload)?;
+    let sender_address_raw = deps.api.canonical_address(&signed.sender)?;
+
+    if read_signing_key(&deps.storage, &sender_address_raw).as_deref() != Some(pubkey.as_slice()) {
+        return Err(StdError::unauthorized());
+    }
+    let public_key = PublicKey::parse(pubkey.as_slice())?;
+    let signature = Signature::parse_slice(signature.as_slice())?;
+    if !public_key.verify(&sha_256(payload.as_slice()), signature) {
+        return Err(StdError::generic_err("Invalid signature."));
+    }
+    if signed.contract != env.contract.address {
+        return Err(StdError::generic_err("Signed for a different contract."));
+    }
+    if env.block.height > signed.expires {
+        return Err(StdError::generic_err("Signature expired."));
+    }
+    if signed.nonce <= read_nonce(&deps.storage, &sender_address_raw)? {
+        return Err(StdError::generic_err("Nonce already used."));
+    }
+    write_nonce(&mut deps.storage, &sender_address_raw, signed.nonce)?;
+
+    // send on behalf of the signer
+    let mut send_env = env;
+    send_env.message.sender = signed.sender;
+    send_env.message.sent_funds = vec![];
+    try_send(deps, send_env, signed.content, signed.target, SendOptions::default())
+}
+
+/// Sends a message on behalf of `origin`, submitted by a relay contract the admin sanctioned, so
+/// the origin does not appear as the sender of the transaction. Calls from other addresses or
+/// with a code hash other than the registered one are errors.
+pub fn try_relay_send<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    origin: HumanAddr,
+    target: HumanAddr,
+    content: String,
+    origin_hidden: bool,
+    pow_nonce: Option<u64>,
+) -> StdResult<HandleResponse> {
+    // a contract cannot learn the code hash of its caller, so the address the admin registered is
+    // the only check, the registered code hash is published for users to verify the relay's code
+    let relay_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let sanctioned = read_relays(&deps.storage)?.iter().any(|relay| relay.address == relay_address_raw);
+    if !sanctioned {
+        return Err(StdError::unauthorized());
+    }
+
+    // send on behalf of the origin, the relay's funds pay the postage
+    let relay_address = env.message.sender.clone();
+    let mut send_env = env;
+    send_env.message.sender = origin;
+    let options = SendOptions {
+        anonymous: origin_hidden,
+        pow_nonce,
+        payer: Some(relay_address),
+        ..SendOptions::default()
+    };
+    try_send(deps, send_env, content, target, options)
+}
+
+pub fn try_grant_session<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pubkey: Binary,
+    expires: u64,
+    permissions: Vec<SessionPermission>,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut session_storage = SessionStorage::from_storage(&mut deps.storage);
+    // drop expired sessions and any earlier grant of the same key
+    let mut sessions: Vec<Session> = session_storage
+        .get_sessions(&sender_address_raw)
+        .into_iter()
+        .filter(|s| s.expires >= env.block.height && s.pubkey.as_slice() != pubkey.as_slice())
+        .collect();
+
+    if PublicKey::parse(pubkey.as_slice()).is_err() {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("Invalid public key.");
+    } else if sessions.len() >= MAX_SESSIONS as usize {
+        status = Failure;
+        code = CODE_LIMIT_REACHED;
+        response_message.push_str("Too many active sessions.");
+    } else {
+        sessions.push(Session { pubkey: pubkey.as_slice().to_vec(), expires, permissions });
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str(&format!("Session granted until block {}.", expires));
+    }
+    session_storage.set_sessions(&sender_address_raw, sessions);
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::GrantSession {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_revoke_session<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pubkey: Binary,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut session_storage = SessionStorage::from_storage(&mut deps.storage);
+    let mut sessions = session_storage.get_sessions(&sender_address_raw);
+    let count = sessions.len();
+    sessions.retain(|s| s.pubkey.as_slice() != pubkey.as_slice());
+
+    if sessions.len() == count {
+        status = Failure;
+        code = CODE_NOT_FOUND;
+        response_message.push_str("Session not found.");
+    } else {
+        session_storage.set_sessions(&sender_address_raw, sessions);
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Session revoked.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RevokeSession {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_revoke_permit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    permit_name: String,
+) -> StdResult<HandleResponse> {
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    revoke_permit(&mut deps.storage, &sender_address_raw, &permit_name);
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RevokePermit {
+            status: Success,
+            message: "Permit revoked.".to_string(),
+            code: CODE_OK,
+        })?),
+    })
+}
+
+/// Moves the sender to a new permit epoch, so every permit it signed so far stops working
+pub fn try_revoke_all_permits<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut epoch = read_permit_epoch(&deps.storage, &sender_address_raw)?;
+    match epoch.checked_add(1) {
+        Some(next_epoch) => {
+            write_permit_epoch(&mut deps.storage, &sender_address_raw, next_epoch)?;
+            epoch = next_epoch;
+            status = Success;
+            code = CODE_OK;
+            response_message.push_str("All permits revoked.");
+        }
+        None => {
+            status = Failure;
+            code = CODE_LIMIT_REACHED;
+            response_message.push_str("No permit epochs left to move to.");
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RevokeAllPermits {
+            status,
+            message: response_message,
+            code,
+            epoch,
+        })?),
+    })
+}
+
+/// Runs a send or recv signed with a session key as if the grantor had sent it. Signatures of
+/// unknown or expired sessions, missing permissions and replayed nonces are errors.
+pub fn try_session_call<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    payload: Binary,
+    signature: Binary,
+    pubkey: Binary,
+) -> StdResult<HandleResponse> {
+    let call: SessionPayload = from_binary(&payload)?;
+    let grantor_address_raw = deps.api.canonical_address(&call.grantor)?;
+
+    let mut session_storage = SessionStorage::from_storage(&mut deps.storage);
+    let session = session_storage
+        .get_sessions(&grantor_address_raw)
+        .into_iter()
+        .find(|s| s.pubkey.as_slice() == pubkey.as_slice() && s.expires >= env.block.height);
+    let permission = match call.op {
+        SessionOp::Send { .. } => SessionPermission::Send,
+        SessionOp::Recv { } => SessionPermission::Recv,
+    };
+    match session {
+        Some(session) if session.permissions.contains(&permission) => {}
+        _ => return Err(StdError::unauthorized()),
+    }
+
+    let public_key = PublicKey::parse(pubkey.as_slice())?;
+    let signature = Signature::parse_slice(signature.as_slice())?;
+    if !public_key.verify(&sha_256(payload.as_slice()), signature) {
+        return Err(StdError::generic_err("Invalid signature."));
+    }
+    if call.contract != env.contract.address {
+        return Err(StdError::generic_err("Signed for a different contract."));
+    }
+    if call.nonce <= read_nonce(&deps.storage, &grantor_address_raw)? {
+        return Err(StdError::generic_err("Nonce already used."));
+    }
+    write_nonce(&mut deps.storage, &grantor_address_raw, call.nonce)?;
+
+    // act on behalf of the grantor
+    let mut call_env = env;
+    call_env.message.sender = call.grantor;
+    call_env.message.sent_funds = vec![];
+    match call.op {
+        SessionOp::Send { target, content } => try_send(deps, call_env, content, target, SendOptions::default()),
+        SessionOp::Recv { } => try_receive(deps, call_env, None, None, None, false),
+    }
+}
+
+pub fn try_commit_send<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    content_hash: Binary,
+    target: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let target_address_raw = deps.api.canonical_address(&target)?;
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let mut commitment_storage = CommitmentStorage::from_storage(&mut deps.storage);
+    let mut commitments = commitment_storage.get_commitments(&sender_address_raw);
+    if config.status == ContractStatus::ReadOnly {
+        status = Failure;
+        code = CODE_READ_ONLY;
+        response_message.push_str("Sending is disabled during maintenance.");
+    } else if content_hash.as_slice().len() != 32 {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("Invalid content hash.");
+    } else if commitments.len() >= MAX_PENDING_COMMITMENTS as usize {
+        status = Failure;
+        code = CODE_LIMIT_REACHED;
+        response_message.push_str("Too many pending commitments.");
+    } else {
+        commitments.push(Commitment {
+            content_hash: content_hash.0,
+            to: target_address_raw,
+            height: env.block.height,
+        });
+        commitment_storage.set_commitments(&sender_address_raw, commitments);
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str(&format!("Commitment recorded at block {}.", env.block.height));
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CommitSend {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_reveal_send<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    content: String,
+    nonce: String,
+) -> StdResult<HandleResponse> {
+    // leave the commitment in place so it can still be revealed after maintenance
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    if config.status == ContractStatus::ReadOnly {
+        return Ok(HandleResponse {
+            messages: vec![],
+            log: vec![],
+            data: Some(to_binary(&HandleAnswer::Send {
+                status: Failure,
+                message: String::from("Sending is disabled during maintenance."),
+                code: CODE_READ_ONLY,
+                message_id: None,
+                receipt: None,
+                recipient_status: None,
+            })?),
+        });
+    }
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+
+    // the length prefix keeps content and nonce from being split differently under the same hash
+    let mut preimage = (content.len() as u64).to_be_bytes().to_vec();
+    preimage.extend_from_slice(content.as_bytes());
+    preimage.extend_from_slice(nonce.as_bytes());
+    let content_hash = sha_256(&preimage);
+
+    let mut commitment_storage = CommitmentStorage::from_storage(&mut deps.storage);
+    match commitment_storage.take_commitment(&sender_address_raw, &content_hash) {
+        Some(commitment) => {
+            let target = deps.api.human_address(&commitment.to)?;
+            let options = SendOptions {
+                committed_height: Some(commitment.height),
+                ..SendOptions::default()
+            };
+            let response = try_send(deps, env, content, target, options)?;
+            // a failed send is an error, so the commitment it used up is restored
+            if let Some(data) = &response.data {
+                if let HandleAnswer::Send { status: Failure, message, .. } = from_binary(data)? {
+                    return Err(StdError::generic_err(message));
+                }
+            }
+            Ok(response)
+        }
+        None => Ok(HandleResponse {
+            messages: vec![],
+            log: vec![],
+            data: Some(to_binary(&HandleAnswer::Send {
+                status: Failure,
+                message: String::from("No matching commitment."),
+                code: CODE_NOT_FOUND,
+                message_id: None,
+                receipt: None,
+                recipient_status: None,
+            })?),
+        }),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn try_send_chunk<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    transfer_id: u64,
+    target: HumanAddr,
+    index: u32,
+    total: u32,
+    data: String,
+    pow_nonce: Option<u64>,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut message_id: Option<Uint128> = None;
+    let mut messages: Vec<CosmosMsg> = vec![];
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let target_address_raw = deps.api.canonical_address(&target)?;
+    record_first_seen(&mut deps.storage, &sender_address_raw, env.block.height);
+
+    // garbage collect abandoned transfers of the sender
+    let mut transfer_storage = TransferStorage::from_storage(&mut deps.storage);
+    let (mut transfers, abandoned): (Vec<PendingTransfer>, Vec<PendingTransfer>) = transfer_storage
+        .get_transfers(&sender_address_raw)
+        .into_iter()
+        .partition(|t| env.block.height.saturating_sub(t.last_update_height) < TRANSFER_TIMEOUT_BLOCKS);
+    for transfer in abandoned {
+        remove_transfer_chunks(&mut deps.storage, &sender_address_raw, &transfer);
+    }
+
+    // raw chunks are decoded one by one, JSON can only be checked once the content is assembled
+    let chunk = match config.payload_validation {
+        PayloadValidation::Json => Some(data.as_bytes().to_vec()),
+        _ => decode_payload(&config, &data),
+    };
+    let chunk_size = chunk.as_ref().map_or(0, |chunk| chunk.len());
+    let position = transfers.iter().position(|t| t.transfer_id == transfer_id);
+    let valid = total > 0 && total <= MAX_CHUNKS && index < total
+        && chunk.is_some()
+        && chunk_size <= config.max_message_size.into()
+        && match position {
+            Some(p) => transfers[p].to == target_address_raw && transfers[p].received.len() == total as usize,
+            None => transfers.len() < MAX_PENDING_TRANSFERS as usize,
+        };
+
+    // every chunk pays postage for its own bytes, the chunk completing the transfer also pays the
+    // base postage and the recipient's charges
+    let completes = valid && match position {
+        Some(p) => transfers[p].received.iter().enumerate().all(|(i, r)| *r || i == index as usize),
+        None => total == 1,
+    };
+    let (fee, bond) = if completes {
+        inbox_fee(&deps.storage, &target_address_raw, &sender_address_raw)
+    } else {
+        (None, None)
+    };
+    let send_fee = config.send_fee.as_ref().map(|send_fee| Fee::Native {
+        denom: send_fee.denom.clone(),
+        amount: send_fee.per_byte.saturating_mul(chunk_size as u128)
+            .saturating_add(if completes { send_fee.base } else { 0 }),
+    });
+    let paid = charges_covered(send_fee.as_ref(), fee.as_ref().or(bond.as_ref()), &env.message.sent_funds, 0);
+
+    if config.status == ContractStatus::ReadOnly {
+        status = Failure;
+        code = CODE_READ_ONLY;
+        response_message.push_str("Sending is disabled during maintenance.");
+    } else if !valid {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("Invalid chunk.");
+    } else if !paid {
+        status = Failure;
+        code = CODE_INSUFFICIENT_FEE;
+        response_message.push_str("The attached funds do not cover the postage.");
+    } else {
+        if let Some(send_fee) = &send_fee {
+            credit_revenue(&mut deps.storage, send_fee)?;
+        }
+        let position = position.unwrap_or_else(|| {
+            transfers.push(PendingTransfer {
+                transfer_id,
+                to: target_address_raw.clone(),
+                received: vec![false; total as usize],
+                last_update_height: env.block.height,
+            });
+            transfers.len() - 1
+        });
+        write_chunk(&mut deps.storage, &sender_address_raw, transfer_id, index, &chunk.unwrap_or_default())?;
+        transfers[position].received[index as usize] = true;
+        transfers[position].last_update_height = env.block.height;
+
+        if transfers[position].is_complete() {
+            // assemble the chunks into a single message
+            let transfer = transfers.remove(position);
+            let mut content: Vec<u8> = vec![];
+            for i in 0..total {
+                if let Some(chunk) = take_chunk(&mut deps.storage, &sender_address_raw, transfer_id, i)? {
+                    content.extend_from_slice(&chunk);
+                }
+            }
+            let content_size = content.len() as u32;
+            let content_hash = sha_256(&content).to_vec();
+            let accepted = config.payload_validation != PayloadValidation::Json
+                || from_slice::<IgnoredAny>(&content).is_ok();
+            let recipient = deps.api.human_address(&transfer.to)?;
+            let stamped = pow_valid(config.pow_difficulty, &env.message.sender, recipient.as_str(), &content, pow_nonce);
+            let new_message = Message {
+                fee,
+                ..Message::new(content, sender_address_raw.clone(), env.block.height, env.block.time)
+            };
+            let delivered = if accepted && stamped {
+                deliver_message(&mut deps.storage, &config, &transfer.to, new_message)?
+            } else {
+                None
+            };
+            match delivered {
+                _ if !accepted => {
+                    status = Failure;
+                    code = CODE_INVALID_INPUT;
+                    response_message.push_str("The assembled content is not valid JSON.");
+                }
+                _ if !stamped => {
+                    status = Failure;
+                    code = CODE_POW_REQUIRED;
+                    response_message.push_str("The proof of work does not meet the difficulty.");
+                }
+                Some(id) => {
+                    let muted = read_muted_message(&deps.storage, id)?.is_some();
+                    if let Some(bond) = bond {
+                        hold_bond(&mut deps.storage, &transfer.to, id, Bond { sender: sender_address_raw.clone(), amount: bond })?;
+                    }
+                    // the sent item is kept for muted messages too, as for a regular send
+                    if config.max_sent_items > 0 {
+                        let sent_item = SentItem {
+                            id,
+                            to: transfer.to.clone(),
+                            size: content_size,
+                            content_hash,
+                            timestamp: env.block.time,
+                        };
+                        let mut sent_items_storage = SentItemsStorage::from_storage(&mut deps.storage);
+                        sent_items_storage.push_sent_item(&sender_address_raw, sent_item, config.max_sent_items);
+                    }
+                    // there is no message to announce if the target muted the sender
+                    if !muted {
+                        if let Some(callback) =
+                            receiver_callback(&deps.storage, &transfer.to, &recipient, &env.message.sender, id)? {
+                            messages.push(callback);
+                        }
+                    }
+                    message_id = Some(Uint128(u128::from(id)));
+                    status = Success;
+                    code = CODE_OK;
+                    response_message.push_str("Message sent.");
+                }
+                None => {
+                    status = Failure;
+                    code = CODE_NOT_DELIVERED;
+                    response_message.push_str("Message could not be sent.");
+                }
+            }
+        } else {
+            status = Success;
+            code = CODE_OK;
+            response_message.push_str(&format!("Chunk {} of {} received.", index + 1, total));
+        }
+    }
+    // a failed chunk is reverted, so attached funds go back to the sender, and a transfer whose
+    // delivery failed keeps its chunks and postage for another attempt at the last chunk
+    if code != CODE_OK && (completes || !env.message.sent_funds.is_empty()) {
+        return Err(StdError::generic_err(response_message));
+    }
+
+    let mut transfer_storage = TransferStorage::from_storage(&mut deps.storage);
+    transfer_storage.set_transfers(&sender_address_raw, transfers);
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SendChunk {
+            status,
+            message: response_message,
+            code,
+            message_id,
+        })?),
+    })
+}
+
+/// Deletes the stored chunk data of an unfinished transfer
+fn remove_transfer_chunks<S: Storage>(storage: &mut S, sender_address_raw: &CanonicalAddr, transfer: &PendingTransfer) {
+    for (index, received) in transfer.received.iter().enumerate() {
+        if *received {
+            remove_chunk(storage, sender_address_raw, transfer.transfer_id, index as u32);
+        }
+    }
+}
+
+/// Returns the notification for a recipient contract that registered a receiver, if any
+fn receiver_callback<S: ReadonlyStorage>(
+    storage: &S,
+    recipient_raw: &CanonicalAddr,
+    recipient: &HumanAddr,
+    sender: &HumanAddr,
+    message_id: u64,
+) -> StdResult<Option<CosmosMsg>> {
+    match read_receiver(storage, recipient_raw)? {
+        Some(code_hash) => {
+            let msg = ReceiverHandleMsg::MessageReceived {
+                sender: sender.clone(),
+                message_id: Uint128(u128::from(message_id)),
+            };
+            msg.to_cosmos_msg(code_hash, recipient.clone(), None).map(Some)
+        }
+        None => Ok(None),
+    }
+}
+
+/// Returns the notification for a sender contract that registered a read callback, if any
+fn sender_callback<S: ReadonlyStorage, A: Api>(
+    storage: &S,
+    api: &A,
+    mes: &Message,
+    recipient: &HumanAddr,
+    message_id: u64,
+) -> StdResult<Option<CosmosMsg>> {
+    // the callback would be sent to the address of an anonymous sender
+    if mes.system.is_some() || mes.anonymous {
+        return Ok(None);
+    }
+    match read_sender_callback(storage, &mes.from)? {
+        Some(code_hash) => {
+            let msg = ReceiverHandleMsg::MessageRead {
+                recipient: recipient.clone(),
+                message_id: Uint128(u128::from(message_id)),
+            };
+            msg.to_cosmos_msg(code_hash, api.human_address(&mes.from)?, None).map(Some)
+        }
+        None => Ok(None),
+    }
+}
+
+/// Returns the postage and the spam bond the target requires from the sender, None if the sender
+/// is exempt or the target does not require them
+pub fn inbox_fee<S: ReadonlyStorage>(storage: &S, target: &CanonicalAddr, sender: &CanonicalAddr) -> (Option<Fee>, Option<Fee>) {
+    if target == sender || is_contact(storage, target, sender) {
+        return (None, None);
+    }
+    let settings = ReadonlyUserSettingsStorage::from_storage(storage).get_settings(target);
+    (settings.price, settings.bond)
+}
+
+/// Returns true if sha256(sender ++ 0x00 ++ target ++ 0x00 ++ sha256(content) ++ nonce as 8
+/// big-endian bytes) starts with at least `difficulty` zero bits, or if no proof of work is required
+pub fn pow_valid(difficulty: u8, sender: &HumanAddr, target: &str, content: &[u8], nonce: Option<u64>) -> bool {
+    if difficulty == 0 {
+        return true;
+    }
+    let nonce = match nonce {
+        Some(nonce) => nonce,
+        None => return false,
+    };
+    let mut preimage = sender.as_str().as_bytes().to_vec();
+    preimage.push(0);
+    preimage.extend_from_slice(target.as_bytes());
+    preimage.push(0);
+    preimage.extend_from_slice(&sha_256(content));
+    preimage.extend_from_slice(&nonce.to_be_bytes());
+    let mut zero_bits: u32 = 0;
+    for byte in sha_256(&preimage).iter() {
+        zero_bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    zero_bits >= u32::from(difficulty)
+}
+
+/// Returns true if the attached native funds or postage tokens cover `fee`
+pub fn fee_covered(fee: &Fee, sent_funds: &[Coin], token_paid: u128) -> bool {
+    match fee {
+        Fee::Native { denom, amount } => {
+            let paid: u128 = sent_funds.iter().filter(|c| c.denom == *denom).map(|c| c.amount.u128()).sum();
+            paid >= *amount
+        }
+        Fee::Token { amount } => token_paid >= *amount,
+    }
+}
+
+/// Returns true if the attached funds cover the contract's postage and the recipient's charge
+/// together, adding them up if they are in the same coin
+fn charges_covered(postage: Option<&Fee>, charge: Option<&Fee>, sent_funds: &[Coin], token_paid: u128) -> bool {
+    match (postage, charge) {
+        (Some(Fee::Native { denom, amount }), Some(Fee::Native { denom: charge_denom, amount: charge_amount }))
+            if denom == charge_denom =>
+        {
+            let total = Fee::Native { denom: denom.clone(), amount: amount.saturating_add(*charge_amount) };
+            fee_covered(&total, sent_funds, token_paid)
+        }
+        _ => {
+            !matches!(postage, Some(f) if !fee_covered(f, sent_funds, token_paid))
+                && !matches!(charge, Some(f) if !fee_covered(f, sent_funds, token_paid))
+        }
+    }
+}
+
+/// Returns the messages returning to the payer what they attached beyond the contract's postage
+/// and the recipient's charge
+#[allow(clippy::too_many_arguments)]
+pub fn refund_excess<A: Api>(
+    api: &A,
+    config: &Config,
+    contract: &HumanAddr,
+    payer: &HumanAddr,
+    postage: Option<&Fee>,
+    charge: Option<&Fee>,
+    sent_funds: &[Coin],
+    token_paid: u128,
+) -> StdResult<Vec<CosmosMsg>> {
+    let mut messages: Vec<CosmosMsg> = vec![];
+    let used = |denom: &str| -> u128 {
+        [postage, charge]
+            .iter()
+            .filter_map(|fee| match fee {
+                Some(Fee::Native { denom: fee_denom, amount }) if fee_denom == denom => Some(*amount),
+                _ => None,
+            })
+            .sum()
+    };
+    let mut excess: Vec<Coin> = vec![];
+    for coin in sent_funds {
+        if excess.iter().any(|c| c.denom == coin.denom) {
+            continue;
+        }
+        let paid: u128 = sent_funds.iter().filter(|c| c.denom == coin.denom).map(|c| c.amount.u128()).sum();
+        let left = paid.saturating_sub(used(&coin.denom));
+        if left > 0 {
+            excess.push(Coin { denom: coin.denom.clone(), amount: Uint128(left) });
+        }
+    }
+    if !excess.is_empty() {
+        messages.push(CosmosMsg::Bank(BankMsg::Send {
+            from_address: contract.clone(),
+            to_address: payer.clone(),
+            amount: excess,
+        }));
+    }
+    // the contract's postage in the token was taken before the send
+    let token_used = match charge {
+        Some(Fee::Token { amount }) => *amount,
+        _ => 0,
+    };
+    if token_paid > token_used {
+        if let Some(refund) = fee_payout(api, config, contract, payer, &Fee::Token { amount: token_paid - token_used })? {
+            messages.push(refund);
+        }
+    }
+    Ok(messages)
+}
+
+/// Returns the message paying the postage a message carried to its recipient
+fn fee_payout<A: Api>(
+    api: &A,
+    config: &Config,
+    contract: &HumanAddr,
+    recipient: &HumanAddr,
+    fee: &Fee,
+) -> StdResult<Option<CosmosMsg>> {
+    match fee {
+        Fee::Native { denom, amount } => Ok(Some(CosmosMsg::Bank(BankMsg::Send {
+            from_address: contract.clone(),
+            to_address: recipient.clone(),
+            amount: vec![Coin { denom: denom.clone(), amount: Uint128(*amount) }],
+        }))),
+        Fee::Token { amount } => match &config.postage {
+            Some(postage) => transfer_msg(
+                recipient.clone(),
+                Uint128(*amount),
+                None,
+                BLOCK_SIZE,
+                postage.token_code_hash.clone(),
+                api.human_address(&postage.token)?,
+            ).map(Some),
+            None => Ok(None),
+        },
+    }
+}
+
+/// Returns true if the sender counts as a contract: the admin registered it or sanctioned it as a
+/// relay, or it registered a receiver or read callback, which only contracts can make use of
+fn is_contract_sender<S: ReadonlyStorage>(storage: &S, sender: &CanonicalAddr) -> StdResult<bool> {
+    Ok(is_registered_contract(storage, sender)
+        || read_receiver(storage, sender)?.is_some()
+        || read_sender_callback(storage, sender)?.is_some()
+        || read_relays(storage)?.iter().any(|relay| &relay.address == sender))
+}
+
+/// Returns true if the target blocked the sender, or the deployment or the target refuses its
+/// kind of sender
+fn refuses_sender<S: ReadonlyStorage>(
+    storage: &S,
+    config: &Config,
+    target: &CanonicalAddr,
+    sender: &CanonicalAddr,
+) -> StdResult<bool> {
+    let blocked = is_blocked(storage, target, sender);
+    if blocked && !config.uniform_failures {
+        return Ok(true);
+    }
+    // with uniform failures the settings are read for blocked senders too, so the gas used does
+    // not reveal the block
+    let settings = ReadonlyUserSettingsStorage::from_storage(storage).get_settings(target);
+    if config.sender_filter == SenderFilter::Any && settings.sender_filter == SenderFilter::Any {
+        return Ok(blocked);
+    }
+    let is_contract = is_contract_sender(storage, sender)?;
+    Ok(blocked || !config.sender_filter.allows(is_contract) || !settings.sender_filter.allows(is_contract))
+}
+
+/// Returns true if the sender already has as many unread messages in the target's queue as
+/// max_messages_per_sender allows
+fn sender_at_limit<S: ReadonlyStorage>(
+    storage: &S,
+    config: &Config,
+    target: &CanonicalAddr,
+    sender: &CanonicalAddr,
+) -> bool {
+    if config.max_messages_per_sender == 0 {
+        return false;
+    }
+    ReadonlyConversationStorage::from_storage(storage)
+        .get_conversations(target)
+        .iter()
+        .any(|conversation| &conversation.sender == sender && conversation.unread >= config.max_messages_per_sender)
+}
+
+/// Enqueues a message unless the target refuses its sender, the sender is at its limit in the
+/// target's queue, or the queue is full and its policy refuses new messages. Returns the id of the
+/// message if it was queued.
+pub fn deliver_message<S: Storage>(
+    storage: &mut S,
+    config: &Config,
+    target_address_raw: &CanonicalAddr,
+    new_message: Message,
+) -> StdResult<Option<u64>> {
+    let message_queue = MessageQueueStorage::from_storage(storage).get_message_queue(target_address_raw);
+    // deliveries like these carry no permit, so token and NFT gates refuse them
+    let refused = refuses_sender(storage, config, target_address_raw, &new_message.from)?
+        || gated(storage, target_address_raw, &new_message.from)?
+        || needs_invite(storage, target_address_raw, &new_message.from)
+        || !age_gate_passed(storage, target_address_raw, &new_message.from, new_message.height)?
+        || active_quiet_hours(storage, target_address_raw, new_message.height, new_message.timestamp).is_some();
+    if refused && !config.uniform_failures {
+        return Ok(None);
+    }
+    // with uniform failures the remaining checks run for refused senders too, so a refusal uses
+    // as much gas as a full queue
+    if sender_at_limit(storage, config, target_address_raw, &new_message.index_sender())
+        || refused
+        || ((message_queue.length >= config.max_messages) && !config.eviction_policy.makes_room()) {
+        return Ok(None);
+    }
+    enqueue_message(storage, config, target_address_raw, message_queue, new_message).map(Some)
+}
+
+/// Tells the sender of an evicted message that it was dropped unread, if they asked for it
+fn notify_eviction<S: Storage>(
+    storage: &mut S,
+    config: &Config,
+    target_address_raw: &CanonicalAddr,
+    evicted_message: &Message,
+    height: u64,
+    timestamp: u64,
+) -> StdResult<()> {
+    let sender = &evicted_message.from;
+    if !ReadonlyUserSettingsStorage::from_storage(storage).get_settings(sender).eviction_notices {
+        return Ok(());
+    }
+    let notice = SystemMessage::Evicted {
+        sent_height: evicted_message.height,
+        sent_timestamp: evicted_message.timestamp,
+    };
+    deliver_notice(storage, config, sender, target_address_raw, notice, height, timestamp)
+}
+
+/// Tells a sender that their send was dropped because the target's queue is full, if they asked
+/// for it
+fn notify_bounce<S: Storage>(
+    storage: &mut S,
+    config: &Config,
+    sender: &CanonicalAddr,
+    target_address_raw: &CanonicalAddr,
+    height: u64,
+    timestamp: u64,
+) -> StdResult<()> {
+    if !ReadonlyUserSettingsStorage::from_storage(storage).get_settings(sender).bounce_notices {
+        return Ok(());
+    }
+    let notice = SystemMessage::Bounced { sent_height: height, sent_timestamp: timestamp };
+    deliver_notice(storage, config, sender, target_address_raw, notice, height, timestamp)
+}
+
+/// Delivers a system notice from `target_address_raw` to `sender`. The notice is only delivered if
+/// it fits without evicting anything, so notices never cascade, and not if the sender blocked the
+/// target.
+fn deliver_notice<S: Storage>(
+    storage: &mut S,
+    config: &Config,
+    sender: &CanonicalAddr,
+    target_address_raw: &CanonicalAddr,
+    notice: SystemMessage,
+    height: u64,
+    timestamp: u64,
+) -> StdResult<()> {
+    if is_blocked(storage, sender, target_address_raw) {
+        return Ok(());
+    }
+    let message_queue = MessageQueueStorage::from_storage(storage).get_message_queue(sender);
+    if message_queue.length >= config.max_messages {
+        return Ok(());
+    }
+    let notice = Message {
+        system: Some(notice),
+        ..Message::new(vec![], target_address_raw.clone(), height, timestamp)
+    };
+    enqueue_message(storage, config, sender, message_queue, notice)?;
+    Ok(())
+}
+
+/// Drops a message from a sender the target muted. The message gets an id and counts as sent like
+/// a delivered one, so the sender cannot tell it never reached the queue.
+fn discard_muted_message<S: Storage>(
+    storage: &mut S,
+    target_address_raw: &CanonicalAddr,
+    mut seq: u64,
+    contract_secret: &[u8],
+    muted_message: &Message,
+) -> StdResult<u64> {
+    let message_storage = ReadonlyMessageStorage::from_storage(storage)?;
+    let mut message_id = derive_message_id(contract_secret, target_address_raw, seq);
+    while message_id == 0 || message_storage.get_message(&message_id)?.is_some() {
+        seq += 1;
+        message_id = derive_message_id(contract_secret, target_address_raw, seq);
+    }
+    write_sequence(storage, target_address_raw, seq + 1)?;
+
+    let mut stats = read_stats(storage)?;
+    stats.messages_sent += 1;
+    write_stats(storage, &stats)?;
+    let mut sender_stats = read_user_stats(storage, &muted_message.from)?;
+    sender_stats.messages_sent += 1;
+    write_user_stats(storage, &muted_message.from, &sender_stats)?;
+
+    let tombstone = MutedMessage { from: muted_message.from.clone(), time_capsule: muted_message.time_capsule };
+    write_muted_message(storage, message_id, &tombstone)?;
+    if let Some(fee) = &muted_message.fee {
+        forfeit_to(storage, target_address_raw, fee)?;
+    }
+    Ok(message_id)
+}
+
+/// Stores the bond of a queued message, or forfeits it to the target if the message was dropped
+/// because the target muted the sender
+fn hold_bond<S: Storage>(storage: &mut S, target_address_raw: &CanonicalAddr, message_id: u64, bond: Bond) -> StdResult<()> {
+    if read_muted_message(storage, message_id)?.is_some() {
+        forfeit_to(storage, target_address_raw, &bond.amount)
+    } else {
+        write_bond(storage, message_id, &bond)
+    }
+}
+
+/// Returns the bytes to store for `content` under the deployment's payload validation, None if the
+/// content is not accepted
+fn decode_payload(config: &Config, content: &str) -> Option<Vec<u8>> {
+    match config.payload_validation {
+        PayloadValidation::Utf8 => Some(content.as_bytes().to_vec()),
+        PayloadValidation::Json => from_slice::<IgnoredAny>(content.as_bytes()).ok().map(|_| content.as_bytes().to_vec()),
+        PayloadValidation::Raw => Binary::from_base64(content).ok().map(|bytes| bytes.0),
+    }
+}
+
+/// Returns stored content as text, or as raw bytes if the deployment accepts raw bytes or the
+/// content is not valid UTF-8, so it is never dropped
+pub fn encode_payload(config: &Config, payload: &[u8]) -> (Option<String>, Option<Binary>) {
+    match config.payload_validation {
+        PayloadValidation::Raw => (None, Some(Binary(payload.to_vec()))),
+        _ => match String::from_utf8(payload.to_vec()) {
+            Ok(text) => (Some(text), None),
+            Err(_) => (None, Some(Binary(payload.to_vec()))),
+        },
+    }
+}
+
+fn payload_description(config: &Config) -> &'static str {
+    match config.payload_validation {
+        PayloadValidation::Utf8 => "text",
+        PayloadValidation::Json => "valid JSON",
+        PayloadValidation::Raw => "base64 encoded bytes",
+    }
+}
+
+/// Returns the expiry height of a message delivered at `delivered` that has no expiry of its own
+fn default_expiry(config: &Config, delivered: u64) -> Option<u64> {
+    match config.default_ttl_blocks {
+        0 => None,
+        ttl => Some(delivered.saturating_add(ttl)),
+    }
+}
+
+/// Pushes a message to the rear of the target's queue, evicting the message chosen by the eviction
+/// policy if the queue is full, and returns the id assigned to the new message. `message_queue` is the target's queue
+/// as already loaded by the caller. Callers are responsible for checking the blocklist and the
+/// eviction policy first.
+fn enqueue_message<S: Storage>(
+    storage: &mut S,
+    config: &Config,
+    target_address_raw: &CanonicalAddr,
+    mut message_queue: MessageQueue,
+    mut new_message: Message,
+) -> StdResult<u64> {
+    let mut seq = read_sequence(storage, target_address_raw)?.unwrap_or(config.seq_start);
+    let contract_secret: Vec<u8> = load(storage, CONTRACT_SECRET_KEY)?;
+    if is_muted(storage, target_address_raw, &new_message.from) {
+        return discard_muted_message(storage, target_address_raw, seq, &contract_secret, &new_message);
+    }
+    let mut stats = read_stats(storage)?;
+    let sender_address_raw = new_message.from.clone();
+    let index_sender = new_message.index_sender();
+    let anonymous = new_message.anonymous;
+    let height = new_message.height;
+    let timestamp = new_message.timestamp;
+    let tags = new_message.tags.clone();
+    let content = std::mem::take(&mut new_message.content);
+    new_message.set_content(content, &config.padding_buckets);
+    if new_message.expire_after_height.is_none() {
+        let mut delivered = new_message.height.max(new_message.deliver_after_height.unwrap_or(0));
+        // a time capsule cannot expire before it is revealed
+        if new_message.time_capsule {
+            delivered = delivered.max(new_message.reveal_after_height.unwrap_or(0));
+        }
+        new_message.expire_after_height = default_expiry(config, delivered);
+        new_message.expires_by_default = new_message.expire_after_height.is_some();
+    }
+    if message_queue.length == 0 {
+        stats.active_queues += 1;
+        link_active_queue(storage, target_address_raw)?;
+    }
+
+    let mut message_storage = MessageStorage::from_storage(storage)?;
+
+    // derive the id of the new message, skipping the unused id 0 and any id still taken
+    let mut message_id = derive_message_id(&contract_secret, target_address_raw, seq);
+    while message_id == 0 || message_storage.get_message(&message_id)?.is_some() {
+        seq += 1;
+        message_id = derive_message_id(&contract_secret, target_address_raw, seq);
+    }
+
+    // a full queue only gets here if the eviction policy makes room
+    let evicted = message_storage.push_message(
+        &mut message_queue,
+        target_address_raw,
+        message_id,
+        new_message,
+        config.max_messages,
+        &config.eviction_policy,
+    )?;
+    let evicted_sender = evicted.as_ref().map(|(_, evicted_message)| evicted_message.index_sender());
+
+    let mut message_queue_storage = MessageQueueStorage::from_storage(storage);
+    message_queue_storage.set_message_queue(target_address_raw, message_queue);
+
+    // update the per-sender index
+    let mut conversation_storage = ConversationStorage::from_storage(storage);
+    if let Some(evicted_sender) = &evicted_sender {
+        conversation_storage.remove_message(target_address_raw, evicted_sender);
+    }
+    conversation_storage.add_message(target_address_raw, &index_sender, timestamp);
+
+    // update the per-tag indexes
+    for tag in &tags {
+        index_tag(storage, config, target_address_raw, tag, message_id)?;
+    }
+
+    // anonymous messages can be answered once with a token derived from the message, of which
+    // only the hash is stored
+    if anonymous {
+        let token = reply_token(&contract_secret, message_id, height);
+        let record = ReplyToken { sender: sender_address_raw.clone(), recipient: target_address_raw.clone() };
+        write_reply_token(storage, &sha_256(&token), &record)?;
+    }
+
+    // increment the target's sequence the message ids are derived from
+    write_sequence(storage, target_address_raw, seq + 1)?;
+
+    stats.messages_sent += 1;
+    if evicted_sender.is_some() {
+        stats.messages_evicted += 1;
+    }
+    write_stats(storage, &stats)?;
+
+    // update the activity counters of the sender and the target
+    let mut sender_stats = read_user_stats(storage, &sender_address_raw)?;
+    sender_stats.messages_sent += 1;
+    write_user_stats(storage, &sender_address_raw, &sender_stats)?;
+    if evicted_sender.is_some() {
+        let mut target_stats = read_user_stats(storage, target_address_raw)?;
+        target_stats.messages_evicted += 1;
+        write_user_stats(storage, target_address_raw, &target_stats)?;
+    }
+    if let Some((evicted_id, evicted_message)) = evicted {
+        remove_ballot(storage, target_address_raw, evicted_id, &evicted_message);
+        if evicted_message.system.is_none() {
+            record_offense(storage, target_address_raw, &evicted_message.from)?;
+            notify_eviction(storage, config, target_address_raw, &evicted_message, height, timestamp)?;
+            if ReadonlyUserSettingsStorage::from_storage(storage).get_settings(target_address_raw).dead_letters {
+                let letter = DeadLetter { message: evicted_message.clone(), reason: DeadLetterReason::Evicted };
+                push_dead_letter(storage, target_address_raw, &letter, MAX_DEAD_LETTERS)?;
+            }
+        }
+        // postage of messages that are never read stays with the contract
+        if let Some(fee) = &evicted_message.fee {
+            credit_revenue(storage, fee)?;
+        }
+    }
+
+    Ok(message_id)
+}
+
+/// Counts a message from `sender` that the recipient lost unread or reported as spam and blocks
+/// the sender once the recipient's auto block threshold is reached
+fn record_offense<S: Storage>(storage: &mut S, recipient: &CanonicalAddr, sender: &CanonicalAddr) -> StdResult<()> {
+    let mut settings = ReadonlyUserSettingsStorage::from_storage(storage).get_settings(recipient);
+    if settings.auto_block_after == 0 || is_blocked(storage, recipient, sender) {
+        return Ok(());
+    }
+    let count = read_offense_count(storage, recipient, sender)? + 1;
+    if count < settings.auto_block_after || settings.blocked_count >= MAX_BLOCKED {
+        return write_offense_count(storage, recipient, sender, count);
+    }
+    block_sender(storage, recipient, sender, settings.blocked_count)?;
+    settings.blocked_count += 1;
+    let mut settings_storage = UserSettingsStorage::from_storage(storage);
+    settings_storage.set_settings(recipient, settings);
+    write_offense_count(storage, recipient, sender, 0)
+}
+
+/// Appends a message to the owner's index of a tag. Entries of messages that left the queue are
+/// dropped when the index reaches the queue limit, so it stays bounded without touching the index
+/// on every removal.
+fn index_tag<S: Storage>(storage: &mut S, config: &Config, owner: &CanonicalAddr, tag: &str, id: u64) -> StdResult<()> {
+    let mut ids = read_tag_index(storage, owner, tag)?;
+    if ids.len() >= config.max_messages as usize {
+        let message_storage = ReadonlyMessageStorage::from_storage(storage)?;
+        let mut queued = vec![];
+        for indexed_id in ids {
+            if message_storage.get_message(&indexed_id)?.is_some() {
+                queued.push(indexed_id);
+            }
+        }
+        ids = queued;
+    }
+    ids.push(id);
+    write_tag_index(storage, owner, tag, &ids)
+}
+
+/// Returns the owner's queued messages carrying a tag, oldest first
+fn tagged_messages<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr, tag: &str) -> StdResult<Vec<(u64, Message)>> {
+    let message_storage = ReadonlyMessageStorage::from_storage(storage)?;
+    let mut tagged = vec![];
+    for id in read_tag_index(storage, owner, tag)? {
+        if let Some(mes) = message_storage.get_message(&id)? {
+            if mes.tags.iter().any(|t| t == tag) {
+                tagged.push((id, mes));
+            }
+        }
+    }
+    Ok(tagged)
+}
+
+/// Returns the quiet hours of a recipient if they are active at the given height and time
+fn active_quiet_hours<S: ReadonlyStorage>(
+    storage: &S,
+    target: &CanonicalAddr,
+    height: u64,
+    time: u64,
+) -> Option<QuietHours> {
+    ReadonlyUserSettingsStorage::from_storage(storage)
+        .get_settings(target)
+        .quiet_hours
+        .filter(|quiet_hours| quiet_hours.window.contains(height, time))
+}
+
+/// Moves dead man's switch messages whose senders have been inactive long enough, and messages
+/// deferred by quiet hours that are over, into the target's queue. Messages stay pending while
+/// the queue is full and its policy refuses new messages.
+fn release_scheduled_messages<S: Storage>(
+    storage: &mut S,
+    config: &Config,
+    target_address_raw: &CanonicalAddr,
+    height: u64,
+    time: u64,
+) -> StdResult<()> {
+    let mut release_storage = ScheduledReleaseStorage::from_storage(storage)?;
+    let releases = release_storage.get_releases(target_address_raw)?;
+    if releases.is_empty() {
+        return Ok(());
+    }
+
+    let quiet = active_quiet_hours(storage, target_address_raw, height, time).is_some();
+    let mut pending: Vec<ScheduledRelease> = vec![];
+    for release in releases {
+        let held = if release.quiet {
+            quiet
+        } else {
+            height.saturating_sub(read_heartbeat(storage, &release.from)?) < release.release_if_inactive_blocks
+        };
+        let message_queue = MessageQueueStorage::from_storage(storage).get_message_queue(target_address_raw);
+        if held
+            || sender_at_limit(storage, config, target_address_raw, &if release.anonymous { anonymous_sender() } else { release.from.clone() })
+            || ((message_queue.length >= config.max_messages) && !config.eviction_policy.makes_room()) {
+            pending.push(release);
+            continue;
+        }
+        let released_message = Message {
+            priority: release.priority,
+            content_type: release.content_type,
+            fee: release.fee,
+            tags: release.tags,
+            // time capsules expire counting from their unlock
+            expire_after_height: default_expiry(config, height.max(release.unlock_height.unwrap_or_default())),
+            expires_by_default: config.default_ttl_blocks > 0,
+            anonymous: release.anonymous,
+            views_left: release.views_left,
+            reveal_after_height: release.unlock_height,
+            time_capsule: release.unlock_height.is_some(),
+            signed_read: release.signed_read,
+            mint_receipt: release.mint_receipt,
+            ..Message::new(release.content, release.from.clone(), release.height, release.timestamp)
+        };
+        let id = enqueue_message(storage, config, target_address_raw, message_queue, released_message)?;
+        if let Some(bond) = release.bond {
+            hold_bond(storage, target_address_raw, id, Bond { sender: release.from, amount: bond })?;
+        }
+    }
+
+    let mut release_storage = ScheduledReleaseStorage::from_storage(storage)?;
+    release_storage.set_releases(target_address_raw, pending)?;
+    Ok(())
+}
+
+/// Explodes the first message that is ready to be read, or the first one carrying `tag` if set
+pub fn try_receive<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    tag: Option<String>,
+    folder: Option<String>,
+    read_signature: Option<Binary>,
+    block_sender: bool,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut number_of_unread_messages: u32 = 0;
+    let mut message_id: Option<Uint128> = None;
+    let mut content: Option<String> = None;
+    let mut raw_content: Option<Binary> = None;
+    let mut sender: Option<HumanAddr> = None;
+    let mut system: Option<SystemNotice> = None;
+    let mut sealed_until: Option<u64> = None;
+    let mut sent_height: Option<u64> = None;
+    let mut sent_timestamp: Option<u64> = None;
+    let mut committed_height: Option<u64> = None;
+    let mut priority: Option<u8> = None;
+    let mut content_type: Option<String> = None;
+    let mut tags: Vec<String> = vec![];
+    let mut more_from_sender = false;
+    let mut reply: Option<Binary> = None;
+    let mut views_left: Option<u32> = None;
+    let mut sender_blocked = false;
+    let mut messages: Vec<CosmosMsg> = vec![];
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    release_scheduled_messages(&mut deps.storage, &config, &sender_address_raw, env.block.height, env.block.time)?;
+    for fee in take_forfeited(&mut deps.storage, &sender_address_raw)? {
+        messages.extend(fee_payout(&deps.api, &config, &env.contract.address, &env.message.sender, &fee)?);
+    }
+    let folder_missing = match &folder {
+        Some(name) if name == ARCHIVE_FOLDER => false,
+        Some(name) => !read_folders(&deps.storage, &sender_address_raw)?.iter().any(|f| &f.name == name),
+        None => false,
+    };
+    let mut message_queue = match &folder {
+        Some(name) => read_folder_queue(&deps.storage, &sender_address_raw, name)?,
+        None => MessageQueueStorage::from_storage(&mut deps.storage).get_message_queue(&sender_address_raw),
+    };
+    let height = env.block.height;
+    prune_messages(
+        &mut deps.storage,
+        &sender_address_raw,
+        folder.as_deref(),
+        &mut message_queue,
+        MAX_PRUNED_PER_CALL,
+        Some(height),
+        |m| m.is_expired(height),
+    )?;
+
+    // get first message in the queue that is ready to be delivered
+    let (mes, sealed_mes) = match &tag {
+        Some(tag) => {
+            let tagged = tagged_messages(&deps.storage, &sender_address_raw, tag)?;
+            let mes = tagged.iter().find(|(_, m)| m.is_visible(height) && !m.is_sealed(height)).cloned();
+            let sealed_mes = match mes {
+                Some(_) => None,
+                None => tagged.iter().find(|(_, m)| m.is_visible(height)).cloned(),
+            };
+            // rewrite the index without the messages that left the queue or are about to
+            let remaining: Vec<u64> = tagged
+                .iter()
+                .map(|(id, _)| *id)
+                .filter(|id| {
+                    !matches!(mes.as_ref(), Some((read_id, m)) if id == read_id && m.is_last_view() && !m.signed_read)
+                })
+                .collect();
+            write_tag_index(&mut deps.storage, &sender_address_raw, tag, &remaining)?;
+            (mes, sealed_mes)
+        }
+        None => {
+            let mes = find_message(&deps.storage, &message_queue, |m| m.is_visible(height) && !m.is_sealed(height))?;
+            let sealed_mes = match mes {
+                Some(_) => None,
+                None => find_message(&deps.storage, &message_queue, |m| m.is_visible(height))?,
+            };
+            (mes, sealed_mes)
+        }
+    };
+    // a message sent with require_read_signature is only read with a valid signature over its id
+    let unsigned_id = match &mes {
+        Some((id, m)) if m.signed_read => {
+            let signed = matches!(read_signature.as_ref(), Some(signature)
+                if read_signature_valid(&deps.storage, &env.contract.address, &sender_address_raw, *id, signature));
+            if signed { None } else { Some(*id) }
+        }
+        _ => None,
+    };
+    if let Some(id) = unsigned_id {
+        number_of_unread_messages = count_visible_messages(&deps.storage, &message_queue, height)?;
+        message_id = Some(Uint128(u128::from(id)));
+        status = Failure;
+        code = CODE_SIGNATURE_REQUIRED;
+        response_message.push_str("Sign the message id with your signing key to read this message.");
+    } else if let Some((id, found_mes)) = mes {
+        if found_mes.signed_read {
+            if let (Some(pubkey), Some(signature)) = (read_signing_key(&deps.storage, &sender_address_raw), &read_signature) {
+                let proof = ReadProof {
+                    recipient: sender_address_raw.clone(),
+                    pubkey: Binary(pubkey),
+                    signature: signature.clone(),
+                    height,
+                };
+                write_read_proof(&mut deps.storage, &found_mes.from, id, &proof)?;
+            }
+        }
+        if found_mes.is_last_view() {
+            // explode the message
+            explode_message(&mut deps.storage, &sender_address_raw, folder.as_deref(), &mut message_queue, id, &found_mes)?;
+            if let Some(callback) = sender_callback(&deps.storage, &deps.api, &found_mes, &env.message.sender, id)? {
+                messages.push(callback);
+            }
+            if let Some(fee) = &found_mes.fee {
+                messages.extend(fee_payout(&deps.api, &config, &env.contract.address, &env.message.sender, fee)?);
+            }
+            if let Some(bond) = take_bond(&mut deps.storage, id)? {
+                let bond_sender = deps.api.human_address(&bond.sender)?;
+                messages.extend(fee_payout(&deps.api, &config, &env.contract.address, &bond_sender, &bond.amount)?);
+            }
+            if let Some(mint) = mint_read_receipt(&mut deps.storage, &deps.api, &config, id, &found_mes, height)? {
+                messages.push(mint);
+            }
+            views_left = found_mes.views_left.map(|_| 0);
+        } else {
+            // count the read and leave the message where it is, postage and bonds wait for the
+            // last read
+            let mut viewed_mes = found_mes.clone();
+            viewed_mes.views_left = found_mes.views_left.map(|views| views - 1);
+            viewed_mes.viewed = true;
+            views_left = viewed_mes.views_left;
+            MessageStorage::from_storage(&mut deps.storage)?.set_message(&id, viewed_mes)?;
+        }
+        number_of_unread_messages = count_visible_messages(&deps.storage, &message_queue, env.block.height)?;
+        let conversation_storage = ReadonlyConversationStorage::from_storage(&deps.storage);
+        more_from_sender = folder.is_none() && !found_mes.anonymous && conversation_storage
+            .get_conversations(&sender_address_raw)
+            .iter()
+            .any(|c| c.sender == found_mes.from);
+
+        message_id = Some(Uint128(u128::from(id)));
+        if found_mes.anonymous {
+            let contract_secret: Vec<u8> = load(&deps.storage, CONTRACT_SECRET_KEY)?;
+            reply = Some(Binary(reply_token(&contract_secret, id, found_mes.height).to_vec()));
+        } else {
+            sender = deps.api.human_address(&found_mes.from).ok();
+        }
+        sent_height = Some(found_mes.height);
+        sent_timestamp = Some(found_mes.timestamp);
+        committed_height = found_mes.committed_height;
+        priority = Some(found_mes.priority);
+        if found_mes.system.is_none() {
+            let (text, raw) = encode_payload(&config, found_mes.payload());
+            content = text;
+            raw_content = raw;
+        }
+        content_type = found_mes.content_type;
+        tags = found_mes.tags;
+        system = system_notice(&deps.storage, found_mes.system)?;
+        if block_sender {
+            if found_mes.anonymous {
+                response_message.push_str("The sender of an anonymous message cannot be blocked.");
+            } else if found_mes.from == sender_address_raw {
+                response_message.push_str("You cannot block yourself.");
+            } else if add_to_blocklist(&mut deps.storage, &sender_address_raw, &found_mes.from)? {
+                sender_blocked = true;
+                response_message.push_str("Sender blocked.");
+            } else {
+                response_message.push_str(&format!("A blocklist can hold at most {} addresses, sender not blocked.", MAX_BLOCKED));
+            }
+        }
+        status = Success;
+        code = CODE_OK;
+    } else if let Some((id, found_mes)) = sealed_mes {
+        // only sealed messages are waiting, announce the first one without exploding it
+        let reveal_after_height = found_mes.reveal_after_height.unwrap_or_default();
+        number_of_unread_messages = count_visible_messages(&deps.storage, &message_queue, height)?;
+        message_id = Some(Uint128(u128::from(id)));
+        if !found_mes.anonymous {
+            sender = deps.api.human_address(&found_mes.from).ok();
+        }
+        sent_height = Some(found_mes.height);
+        sent_timestamp = Some(found_mes.timestamp);
+        sealed_until = Some(reveal_after_height);
+        status = Failure;
+        code = CODE_SEALED;
+        response_message.push_str(&format!("Message sealed until block {}.", reveal_after_height));
+    } else if folder_missing {
+        status = Failure;
+        code = CODE_NOT_FOUND;
+        response_message.push_str("Folder not found.");
+    } else if tag.is_some() {
+        status = Failure;
+        code = CODE_NO_MESSAGES;
+        response_message.push_str("No messages with this tag.");
+    } else if folder.is_some() {
+        status = Failure;
+        code = CODE_NO_MESSAGES;
+        response_message.push_str("No messages in this folder.");
+    } else {
+        status = Failure;
+        code = CODE_NO_MESSAGES;
+        response_message.push_str("No messages.");
+    }
+    let number_of_unread_messages =
+        obfuscate_unread_count(&deps.storage, &config, &sender_address_raw, height, number_of_unread_messages)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Recv {
+            status,
+            message: response_message,
+            code,
+            number_of_unread_messages,
+            message_id,
+            content,
+            raw_content,
+            sender,
+            system,
+            sealed_until,
+            sent_height,
+            sent_timestamp,
+            committed_height,
+            priority,
+            content_type,
+            tags,
+            more_from_sender,
+            reply_token: reply,
+            views_left,
+            sender_blocked,
+        })?),
+    })
+}
+
+/// Returns true if `signature` is a signature by the recipient's signing key over the read proof
+/// hash of a message
+fn read_signature_valid<S: ReadonlyStorage>(
+    storage: &S,
+    contract: &HumanAddr,
+    recipient: &CanonicalAddr,
+    message_id: u64,
+    signature: &Binary,
+) -> bool {
+    let pubkey = match read_signing_key(storage, recipient) {
+        Some(pubkey) => pubkey,
+        None => return false,
+    };
+    match (PublicKey::parse(&pubkey), Signature::parse_slice(signature.as_slice())) {
+        (Ok(public_key), Ok(signature)) => public_key.verify(&read_proof_hash(contract, message_id), signature),
+        _ => false,
+    }
+}
+
+/// Stores one of the owner's queues, the inbox if `folder` is None
+fn store_queue<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    folder: Option<&str>,
+    message_queue: &MessageQueue,
+) -> StdResult<()> {
+    match folder {
+        Some(name) => write_folder_queue(storage, owner, name, message_queue),
+        None => {
+            MessageQueueStorage::from_storage(storage).set_message_queue(owner, message_queue.clone());
+            Ok(())
+        }
+    }
+}
+
+/// Deletes a message from one of the owner's queues, the inbox if `folder` is None, and updates
+/// the indexes that refer to it. The per-sender and active queue indexes only cover inboxes.
+fn explode_message<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    folder: Option<&str>,
+    message_queue: &mut MessageQueue,
+    id: u64,
+    mes: &Message,
+) -> StdResult<()> {
+    let mut message_storage = MessageStorage::from_storage(storage)?;
+    message_storage.unlink_message(message_queue, &id, mes)?;
+    remove_ballot(storage, owner, id, mes);
+
+    let mut stats = read_stats(storage)?;
+    stats.messages_exploded += 1;
+    if message_queue.length == 0 && folder.is_none() {
+        stats.active_queues = stats.active_queues.saturating_sub(1);
+        unlink_active_queue(storage, owner)?;
+    }
+    write_stats(storage, &stats)?;
+
+    let mut owner_stats = read_user_stats(storage, owner)?;
+    owner_stats.messages_received += 1;
+    write_user_stats(storage, owner, &owner_stats)?;
+
+    // store new version of message queue
+    store_queue(storage, owner, folder, message_queue)?;
+
+    // update the per-sender index
+    if folder.is_none() {
+        let mut conversation_storage = ConversationStorage::from_storage(storage);
+        conversation_storage.remove_message(owner, &mes.index_sender());
+    }
+
+    // remember the sender so the recipient can react to the message, anonymous messages are only
+    // answered with their reply token
+    if mes.system.is_none() && !mes.anonymous {
+        let mut received_storage = ReceivedStorage::from_storage(storage);
+        received_storage.push_received(owner, ReceivedMessage { id, from: mes.from.clone() });
+    }
+    Ok(())
+}
+
+/// Deletes a message from the owner's queue without reading it
+fn discard_message<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    message_queue: &mut MessageQueue,
+    id: u64,
+    mes: &Message,
+) -> StdResult<()> {
+    let mut message_storage = MessageStorage::from_storage(storage)?;
+    message_storage.unlink_message(message_queue, &id, mes)?;
+    remove_ballot(storage, owner, id, mes);
+
+    if let Some(fee) = &mes.fee {
+        credit_revenue(storage, fee)?;
+    }
+
+    let mut stats = read_stats(storage)?;
+    stats.messages_expired += 1;
+    if message_queue.length == 0 {
+        stats.active_queues = stats.active_queues.saturating_sub(1);
+        unlink_active_queue(storage, owner)?;
+    }
+    write_stats(storage, &stats)?;
+
+    let mut message_queue_storage = MessageQueueStorage::from_storage(storage);
+    message_queue_storage.set_message_queue(owner, message_queue.clone());
+
+    let mut conversation_storage = ConversationStorage::from_storage(storage);
+    conversation_storage.remove_message(owner, &mes.index_sender());
+    Ok(())
+}
+
+/// Deletes up to `limit` messages matching `predicate` from one of the owner's queues, the inbox if
+/// `folder` is None. Returns how many were deleted and whether matching messages remain. If
+/// `archive_expired_at` is set, deleted messages that expired at that height go to the owner's
+/// dead letters if the owner keeps them. Postage of the deleted messages stays with the contract.
+fn prune_messages<S: Storage, F: Fn(&Message) -> bool>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    folder: Option<&str>,
+    message_queue: &mut MessageQueue,
+    limit: u32,
+    archive_expired_at: Option<u64>,
+    predicate: F,
+) -> StdResult<(u32, bool)> {
+    let (pruned, more_remaining) =
+        remove_messages(storage, owner, folder, message_queue, limit, archive_expired_at, predicate)?;
+    for fee in pruned.iter().filter_map(|(_, mes)| mes.fee.as_ref()) {
+        credit_revenue(storage, fee)?;
+    }
+    Ok((pruned.len() as u32, more_remaining))
+}
+
+/// Deletes messages like prune_messages, but returns the deleted messages and leaves their postage
+/// to the caller
+fn remove_messages<S: Storage, F: Fn(&Message) -> bool>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    folder: Option<&str>,
+    message_queue: &mut MessageQueue,
+    limit: u32,
+    archive_expired_at: Option<u64>,
+    predicate: F,
+) -> StdResult<(Vec<(u64, Message)>, bool)> {
+    let mut pruned: Vec<(u64, Message)> = vec![];
+    let mut more_remaining = false;
+    let message_storage = ReadonlyMessageStorage::from_storage(storage)?;
+    let mut id = message_queue.front;
+    for _ in 0..message_queue.length {
+        match message_storage.get_message(&id)? {
+            Some(mes) => {
+                let next = mes.next;
+                if predicate(&mes) {
+                    if pruned.len() >= limit as usize {
+                        more_remaining = true;
+                        break;
+                    }
+                    pruned.push((id, mes));
+                }
+                id = next;
+            }
+            // this should never happen (queue length is longer than the linked messages)
+            None => return Err(StdError::generic_err("Corrupted message queue.")),
+        }
+    }
+    if pruned.is_empty() {
+        return Ok((pruned, more_remaining));
+    }
+
+    let mut message_storage = MessageStorage::from_storage(storage)?;
+    for (id, mes) in pruned.iter() {
+        message_storage.unlink_message(message_queue, id, mes)?;
+    }
+    for (id, mes) in pruned.iter() {
+        remove_ballot(storage, owner, *id, mes);
+    }
+
+    if let Some(height) = archive_expired_at {
+        if ReadonlyUserSettingsStorage::from_storage(storage).get_settings(owner).dead_letters {
+            for (_, mes) in pruned.iter().filter(|(_, mes)| mes.system.is_none() && mes.is_expired(height)) {
+                let letter = DeadLetter { message: mes.clone(), reason: DeadLetterReason::Expired };
+                push_dead_letter(storage, owner, &letter, MAX_DEAD_LETTERS)?;
+            }
+        }
+    }
+
+    let mut stats = read_stats(storage)?;
+    stats.messages_expired += pruned.len() as u64;
+    if message_queue.length == 0 && folder.is_none() {
+        stats.active_queues = stats.active_queues.saturating_sub(1);
+        unlink_active_queue(storage, owner)?;
+    }
+    write_stats(storage, &stats)?;
+
+    store_queue(storage, owner, folder, message_queue)?;
+
+    if folder.is_none() {
+        let mut conversation_storage = ConversationStorage::from_storage(storage);
+        for (_, mes) in pruned.iter() {
+            conversation_storage.remove_message(owner, &mes.index_sender());
+        }
+    }
+    Ok((pruned, more_remaining))
+}
+
+/// Cuts the owner's queue off at the first broken link, so messages that can no longer be reached
+/// stop counting against the queue length. Returns the number of entries dropped.
+fn truncate_broken_queue<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    message_queue: &mut MessageQueue,
+) -> StdResult<u32> {
+    let message_storage = ReadonlyMessageStorage::from_storage(storage)?;
+    let mut last_id: u64 = 0;
+    let mut id = message_queue.front;
+    let mut linked: u32 = 0;
+    while linked < message_queue.length {
+        match message_storage.get_message(&id)? {
+            Some(mes) => {
+                last_id = id;
+                id = mes.next;
+                linked += 1;
+            }
+            None => break,
+        }
+    }
+    let dropped = message_queue.length - linked;
+    if dropped == 0 {
+        return Ok(0);
+    }
+
+    let mut message_storage = MessageStorage::from_storage(storage)?;
+    if let Some(mut last_message) = message_storage.get_message(&last_id)? {
+        last_message.next = 0;
+        message_storage.set_message(&last_id, last_message)?;
+    } else {
+        message_queue.front = 0;
+    }
+    message_queue.rear = last_id;
+    message_queue.length = linked;
+
+    if message_queue.length == 0 {
+        let mut stats = read_stats(storage)?;
+        stats.active_queues = stats.active_queues.saturating_sub(1);
+        write_stats(storage, &stats)?;
+        unlink_active_queue(storage, owner)?;
+    }
+    let mut message_queue_storage = MessageQueueStorage::from_storage(storage);
+    message_queue_storage.set_message_queue(owner, message_queue.clone());
+    Ok(dropped)
+}
+
+/// Returns the message with the given id if it is in the queue
+fn find_message_by_id<S: ReadonlyStorage>(
+    storage: &S,
+    message_queue: &MessageQueue,
+    message_id: u64,
+) -> StdResult<Option<Message>> {
+    let message_storage = ReadonlyMessageStorage::from_storage(storage)?;
+    let mut id = message_queue.front;
+    for _ in 0..message_queue.length {
+        match message_storage.get_message(&id)? {
+            Some(mes) if id == message_id => return Ok(Some(mes)),
+            Some(mes) => id = mes.next,
+            // this should never happen (queue length is longer than the linked messages)
+            None => return Err(StdError::generic_err("Corrupted message queue.")),
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the id and contents of the first message in the queue matching `predicate`
+fn find_message<S: ReadonlyStorage, F: Fn(&Message) -> bool>(
+    storage: &S,
+    message_queue: &MessageQueue,
+    predicate: F,
+) -> StdResult<Option<(u64, Message)>> {
+    let message_storage = ReadonlyMessageStorage::from_storage(storage)?;
+    let mut id = message_queue.front;
+    for _ in 0..message_queue.length {
+        match message_storage.get_message(&id)? {
+            Some(mes) if predicate(&mes) => return Ok(Some((id, mes))),
+            Some(mes) => id = mes.next,
+            // this should never happen (queue length is longer than the linked messages)
+            None => return Err(StdError::generic_err("Corrupted message queue.")),
+        }
+    }
+    Ok(None)
+}
+
+/// Applies the configured obfuscation to an unread count reported to its owner. Noise is derived
+/// from the contract secret, the owner and the height, so repeating a request within a block does
+/// not average it out.
+fn obfuscate_unread_count<S: ReadonlyStorage>(
+    storage: &S,
+    config: &Config,
+    owner: &CanonicalAddr,
+    height: u64,
+    count: u32,
+) -> StdResult<u32> {
+    match config.unread_count_fuzz {
+        None => Ok(count),
+        Some(CountFuzz::Bucket { size }) => {
+            let (count, size) = (u64::from(count), u64::from(size));
+            let buckets = count / size + u64::from(count % size != 0);
+            Ok(u32::try_from(buckets * size).unwrap_or(u32::MAX))
+        }
+        Some(CountFuzz::Noise { max }) => {
+            let contract_secret: Vec<u8> = load(storage, CONTRACT_SECRET_KEY)?;
+            let mut preimage = contract_secret;
+            preimage.extend_from_slice(owner.as_slice());
+            preimage.extend_from_slice(&height.to_be_bytes());
+            let hash = sha_256(&preimage);
+            let mut noise_bytes = [0u8; 8];
+            noise_bytes.copy_from_slice(&hash[..8]);
+            let noise = u64::from_be_bytes(noise_bytes) % (u64::from(max) + 1);
+            Ok(count.saturating_add(noise as u32))
+        }
+    }
+}
+
+/// Returns the number of messages in the queue that can be received at `height`
+fn count_visible_messages<S: ReadonlyStorage>(
+    storage: &S,
+    message_queue: &MessageQueue,
+    height: u64,
+) -> StdResult<u32> {
+    let message_storage = ReadonlyMessageStorage::from_storage(storage)?;
+    let mut count: u32 = 0;
+    let mut id = message_queue.front;
+    for _ in 0..message_queue.length {
+        match message_storage.get_message(&id)? {
+            Some(mes) => {
+                if mes.is_visible(height) {
+                    count += 1;
+                }
+                id = mes.next;
+            }
+            // this should never happen (queue length is longer than the linked messages)
+            None => return Err(StdError::generic_err("Corrupted message queue.")),
+        }
+    }
+    Ok(count)
+}
+
+pub fn try_receive_chunk<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    message_id: Uint128,
+    offset: u32,
+    len: u32,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut content: Option<Binary> = None;
+    let mut total_size: u32 = 0;
+    let mut final_chunk = false;
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut message_queue_storage = MessageQueueStorage::from_storage(&mut deps.storage);
+    let message_queue = message_queue_storage.get_message_queue(&sender_address_raw);
+
+    let id = to_message_id(message_id);
+    let height = env.block.height;
+    match find_message_by_id(&deps.storage, &message_queue, id)? {
+        // chunks would bypass the read signature
+        Some(found_mes) if found_mes.signed_read => {
+            status = Failure;
+            code = CODE_SIGNATURE_REQUIRED;
+            response_message.push_str("This message can only be read with recv and a read signature.");
+        }
+        Some(mut found_mes) if found_mes.is_visible(height) && !found_mes.is_sealed(height)
+            && found_mes.system.is_none() => {
+            total_size = found_mes.content_size;
+            // each call returns at most max_message_size bytes to keep gas bounded
+            let start = offset.min(total_size) as usize;
+            let end = offset.saturating_add(len.min(config.max_message_size.into())).min(total_size) as usize;
+            content = Some(Binary(found_mes.payload()[start..end].to_vec()));
+            final_chunk = end == total_size as usize;
+            if final_chunk && !found_mes.fetched {
+                found_mes.fetched = true;
+                let mut message_storage = MessageStorage::from_storage(&mut deps.storage)?;
+                message_storage.set_message(&id, found_mes)?;
+            }
+            status = Success;
+            code = CODE_OK;
+        }
+        _ => {
+            status = Failure;
+            code = CODE_NOT_FOUND;
+            response_message.push_str("Message not found.");
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RecvChunk {
+            status,
+            message: response_message,
+            code,
+            content,
+            total_size,
+            final_chunk,
+        })?),
+    })
+}
+
+pub fn try_ack_receive<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    message_id: Uint128,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut messages: Vec<CosmosMsg> = vec![];
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut message_queue_storage = MessageQueueStorage::from_storage(&mut deps.storage);
+    let mut message_queue = message_queue_storage.get_message_queue(&sender_address_raw);
+
+    let id = to_message_id(message_id);
+    match find_message_by_id(&deps.storage, &message_queue, id)? {
+        // only messages whose final chunk was fetched can be acknowledged
+        Some(found_mes) if found_mes.fetched => {
+            explode_message(&mut deps.storage, &sender_address_raw, None, &mut message_queue, id, &found_mes)?;
+            if let Some(callback) = sender_callback(&deps.storage, &deps.api, &found_mes, &env.message.sender, id)? {
+                messages.push(callback);
+            }
+            let config: Config = load(&deps.storage, CONFIG_KEY)?;
+            if let Some(fee) = &found_mes.fee {
+                messages.extend(fee_payout(&deps.api, &config, &env.contract.address, &env.message.sender, fee)?);
+            }
+            if let Some(bond) = take_bond(&mut deps.storage, id)? {
+                let bond_sender = deps.api.human_address(&bond.sender)?;
+                messages.extend(fee_payout(&deps.api, &config, &env.contract.address, &bond_sender, &bond.amount)?);
+            }
+            if let Some(mint) = mint_read_receipt(&mut deps.storage, &deps.api, &config, id, &found_mes, env.block.height)? {
+                messages.push(mint);
+            }
+            status = Success;
+            code = CODE_OK;
+            response_message.push_str("Message exploded.");
+        }
+        _ => {
+            status = Failure;
+            code = CODE_NOT_FOUND;
+            response_message.push_str("Message cannot be acknowledged.");
+        }
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::AckRecv {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_react<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    message_id: Uint128,
+    reaction: String,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+
+    if reaction.is_empty() || reaction.len() > MAX_REACTION_SIZE {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("Invalid reaction.");
+    } else {
+        let mut received_storage = ReceivedStorage::from_storage(&mut deps.storage);
+        match received_storage.take_received(&sender_address_raw, to_message_id(message_id)) {
+            None => {
+                status = Failure;
+                code = CODE_NOT_FOUND;
+                response_message.push_str("Cannot react to this message.");
+            }
+            Some(received_message) => {
+                let mut message_queue_storage = MessageQueueStorage::from_storage(&mut deps.storage);
+                let message_queue = message_queue_storage.get_message_queue(&received_message.from);
+
+                if is_blocked(&deps.storage, &received_message.from, &sender_address_raw) {
+                    status = Failure;
+                    code = CODE_NOT_DELIVERED;
+                    response_message.push_str("Reaction could not be sent.");
+                } else if (message_queue.length >= config.max_messages) && !config.eviction_policy.makes_room() {
+                    status = Failure;
+                    code = CODE_NOT_DELIVERED;
+                    response_message.push_str("Reaction could not be sent.");
+                } else {
+                    let reaction_message = Message {
+                        system: Some(SystemMessage::Reaction {
+                            message_id: to_message_id(message_id),
+                            reaction,
+                        }),
+                        ..Message::new(vec![], sender_address_raw.clone(), env.block.height, env.block.time)
+                    };
+                    enqueue_message(&mut deps.storage, &config, &received_message.from, message_queue, reaction_message)?;
+                    status = Success;
+                    code = CODE_OK;
+                    response_message.push_str("Reaction sent.");
+                }
+            }
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::React {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_edit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    message_id: Uint128,
+    new_content: String,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+
+    let payload = decode_payload(&config, &new_content);
+    let content_byte_slice: &[u8] = payload.as_deref().unwrap_or_default();
+    if payload.is_none() {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str(&format!("Content must be {}.", payload_description(&config)));
+    } else if content_byte_slice.len() > config.max_message_size.into() {
+        status = Failure;
+        code = CODE_TOO_LONG;
+        response_message.push_str("Message is too long.");
+    } else {
+        let id = to_message_id(message_id);
+        // a message dropped because the target muted the sender is edited like an unread one, with
+        // nothing to rewrite but the sent item
+        let muted_mes = read_muted_message(&deps.storage, id)?.filter(|m| m.from == sender_address_raw);
+        let mut message_storage = MessageStorage::from_storage(&mut deps.storage)?;
+        let found_mes = message_storage.get_message(&id)?.filter(|m| m.from == sender_address_raw);
+        match (found_mes, muted_mes) {
+            // the content of a time capsule is fixed once it is sent
+            (Some(Message { time_capsule: true, .. }), _) | (None, Some(MutedMessage { time_capsule: true, .. })) => {
+                status = Failure;
+                code = CODE_SEALED;
+                response_message.push_str("Time capsules cannot be edited.");
+            }
+            // only unread messages written by the sender can be edited, a message that was read
+            // but kept for more views or fetched in chunks no longer counts as unread
+            (Some(mut found_mes), _) if found_mes.system.is_none() && !found_mes.viewed && !found_mes.fetched => {
+                found_mes.set_content(content_byte_slice.to_vec(), &config.padding_buckets);
+                message_storage.set_message(&id, found_mes)?;
+                update_sent_item(&mut deps.storage, &config, &sender_address_raw, id, content_byte_slice);
+                status = Success;
+                code = CODE_OK;
+                response_message.push_str("Message edited.");
+            }
+            (None, Some(_)) => {
+                update_sent_item(&mut deps.storage, &config, &sender_address_raw, id, content_byte_slice);
+                status = Success;
+                code = CODE_OK;
+                response_message.push_str("Message edited.");
+            }
+            _ => {
+                status = Failure;
+                code = CODE_NOT_FOUND;
+                response_message.push_str("Message could not be edited.");
+            }
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Edit {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+/// Updates the size and hash the sender's sent items folder keeps for an edited message
+fn update_sent_item<S: Storage>(storage: &mut S, config: &Config, sender: &CanonicalAddr, id: u64, content: &[u8]) {
+    if config.max_sent_items > 0 {
+        let mut sent_items_storage = SentItemsStorage::from_storage(storage);
+        sent_items_storage.update_sent_item(sender, id, content.len() as u32, sha_256(content).to_vec());
+    }
+}
+
+pub fn try_snooze<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    message_id: Uint128,
+    until_height: u64,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut message_queue = MessageQueueStorage::from_storage(&mut deps.storage).get_message_queue(&sender_address_raw);
+    let id = to_message_id(message_id);
+    if until_height <= env.block.height {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("A message can only be snoozed until a future block.");
+    } else if let Some(mut found_mes) = find_message_by_id(&deps.storage, &message_queue, id)? {
+        found_mes.snoozed_until = Some(until_height);
+        let mut message_storage = MessageStorage::from_storage(&mut deps.storage)?;
+        message_storage.move_to_front(&mut message_queue, &id, found_mes)?;
+        let mut message_queue_storage = MessageQueueStorage::from_storage(&mut deps.storage);
+        message_queue_storage.set_message_queue(&sender_address_raw, message_queue);
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str(&format!("Message snoozed until block {}.", until_height));
+    } else {
+        status = Failure;
+        code = CODE_NOT_FOUND;
+        response_message.push_str("Message not found.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Snooze {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_move_to_front<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    message_id: Uint128,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    if move_to_front(&mut deps.storage, &sender_address_raw, to_message_id(message_id))? {
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Message moved to the front of the queue.");
+    } else {
+        status = Failure;
+        code = CODE_NOT_FOUND;
+        response_message.push_str("Message not found.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::MoveToFront {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_create_folder<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: String,
+    max_messages: Option<u32>,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut folders = read_folders(&deps.storage, &sender_address_raw)?;
+    let max_messages = max_messages.unwrap_or(config.max_messages);
+    if name.is_empty() || name.len() > MAX_FOLDER_NAME_SIZE {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str(&format!("Folder names are 1-{} bytes.", MAX_FOLDER_NAME_SIZE));
+    } else if max_messages == 0 || max_messages > config.max_messages {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str(&format!("A folder holds 1-{} messages.", config.max_messages));
+    } else if name == ARCHIVE_FOLDER || folders.iter().any(|f| f.name == name) {
+        status = Failure;
+        code = CODE_TAKEN;
+        response_message.push_str("A folder with this name already exists.");
+    } else if folders.len() >= MAX_FOLDERS as usize {
+        status = Failure;
+        code = CODE_LIMIT_REACHED;
+        response_message.push_str(&format!("You already have {} folders.", MAX_FOLDERS));
+    } else {
+        folders.push(Folder { name, max_messages });
+        write_folders(&mut deps.storage, &sender_address_raw, &folders)?;
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Folder created.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateFolder {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_delete_folder<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: String,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut folders = read_folders(&deps.storage, &sender_address_raw)?;
+    if !folders.iter().any(|f| f.name == name) {
+        status = Failure;
+        code = CODE_NOT_FOUND;
+        response_message.push_str("Folder not found.");
+    } else if read_folder_queue(&deps.storage, &sender_address_raw, &name)?.length > 0 {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("Only empty folders can be deleted.");
+    } else {
+        folders.retain(|f| f.name != name);
+        write_folders(&mut deps.storage, &sender_address_raw, &folders)?;
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Folder deleted.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::DeleteFolder {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+/// Moves an unread message between the inbox and the sender's folders. The archive is not a folder
+/// here: messages only enter it through archive and leave it when read or expired.
+pub fn try_move_message<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    message_id: Uint128,
+    from: Option<String>,
+    to: Option<String>,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let folders = read_folders(&deps.storage, &sender_address_raw)?;
+    let find_folder = |name: &Option<String>| match name {
+        Some(name) => folders.iter().find(|f| &f.name == name).map(|f| f.max_messages),
+        None => Some(config.max_messages),
+    };
+    let load_queue = |storage: &S, name: &Option<String>| match name {
+        Some(name) => read_folder_queue(storage, &sender_address_raw, name),
+        None => Ok(ReadonlyMessageQueueStorage::from_storage(storage).get_message_queue(&sender_address_raw)),
+    };
+    let id = to_message_id(message_id);
+
+    let mut from_queue = load_queue(&deps.storage, &from)?;
+    let mut to_queue = load_queue(&deps.storage, &to)?;
+    let found_mes = match (find_folder(&from), find_folder(&to)) {
+        (Some(_), Some(_)) => find_message_by_id(&deps.storage, &from_queue, id)?,
+        _ => None,
+    };
+    match find_folder(&to) {
+        _ if from == to => {
+            status = Failure;
+            code = CODE_INVALID_INPUT;
+            response_message.push_str("The message is already there.");
+        }
+        Some(_) if find_folder(&from).is_none() => {
+            status = Failure;
+            code = CODE_NOT_FOUND;
+            response_message.push_str("Folder not found.");
+        }
+        None => {
+            status = Failure;
+            code = CODE_NOT_FOUND;
+            response_message.push_str("Folder not found.");
+        }
+        Some(max_messages) => match found_mes {
+            None => {
+                status = Failure;
+                code = CODE_NOT_FOUND;
+                response_message.push_str("Message not found.");
+            }
+            Some(_) if to_queue.length >= max_messages => {
+                status = Failure;
+                code = CODE_LIMIT_REACHED;
+                response_message.push_str("The destination is full.");
+            }
+            Some(mes) => {
+                relocate_message(
+                    &mut deps.storage,
+                    &config,
+                    &sender_address_raw,
+                    from.as_deref(),
+                    &mut from_queue,
+                    to.as_deref(),
+                    &mut to_queue,
+                    id,
+                    mes,
+                    max_messages,
+                )?;
+                status = Success;
+                code = CODE_OK;
+                response_message.push_str("Message moved.");
+            }
+        },
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::MoveMessage {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+/// Moves an unread message from the inbox to the archive. The archive never evicts, and the
+/// message expires after ARCHIVE_TTL_BLOCKS unless the sender set an earlier expiry.
+pub fn try_archive<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    message_id: Uint128,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut expire_after_height: Option<u64> = None;
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let id = to_message_id(message_id);
+    let mut message_queue = MessageQueueStorage::from_storage(&mut deps.storage).get_message_queue(&sender_address_raw);
+    let mut archive_queue = read_folder_queue(&deps.storage, &sender_address_raw, ARCHIVE_FOLDER)?;
+    match find_message_by_id(&deps.storage, &message_queue, id)? {
+        None => {
+            status = Failure;
+            code = CODE_NOT_FOUND;
+            response_message.push_str("Message not found.");
+        }
+        Some(_) if archive_queue.length >= MAX_ARCHIVED_MESSAGES => {
+            status = Failure;
+            code = CODE_LIMIT_REACHED;
+            response_message.push_str(&format!("The archive already holds {} messages.", MAX_ARCHIVED_MESSAGES));
+        }
+        Some(mut mes) => {
+            let archive_expiry = env.block.height.saturating_add(ARCHIVE_TTL_BLOCKS);
+            let own_expiry = mes.expire_after_height.filter(|_| !(mes.flagged && mes.expires_by_default));
+            mes.expire_after_height = Some(own_expiry.map_or(archive_expiry, |h| h.min(archive_expiry)));
+            mes.expires_by_default = false;
+            expire_after_height = mes.expire_after_height;
+            relocate_message(
+                &mut deps.storage,
+                &config,
+                &sender_address_raw,
+                None,
+                &mut message_queue,
+                Some(ARCHIVE_FOLDER),
+                &mut archive_queue,
+                id,
+                mes,
+                MAX_ARCHIVED_MESSAGES,
+            )?;
+            status = Success;
+            code = CODE_OK;
+            response_message.push_str("Message archived.");
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Archive {
+            status,
+            message: response_message,
+            code,
+            expire_after_height,
+        })?),
+    })
+}
+
+/// Takes a message out of one of the owner's queues and appends it to the rear of another, the
+/// inbox where the folder is None, and stores both queues. The message loses its pin and snooze.
+/// The per-sender, tag and active queue indexes only cover inboxes, so they are updated when the
+/// message leaves or enters the inbox.
+#[allow(clippy::too_many_arguments)]
+fn relocate_message<S: Storage>(
+    storage: &mut S,
+    config: &Config,
+    owner: &CanonicalAddr,
+    from: Option<&str>,
+    from_queue: &mut MessageQueue,
+    to: Option<&str>,
+    to_queue: &mut MessageQueue,
+    id: u64,
+    mut mes: Message,
+    max_messages: u32,
+) -> StdResult<()> {
+    let mut message_storage = MessageStorage::from_storage(storage)?;
+    message_storage.detach_message(from_queue, &id, &mes)?;
+    mes.prev = 0;
+    mes.next = 0;
+    mes.pinned = false;
+    mes.snoozed_until = None;
+    let sent_from = mes.index_sender();
+    let timestamp = mes.timestamp;
+    let tags = mes.tags.clone();
+    message_storage.push_message(to_queue, owner, id, mes, max_messages, &EvictionPolicy::Reject)?;
+    store_queue(storage, owner, from, from_queue)?;
+    store_queue(storage, owner, to, to_queue)?;
+
+    let mut stats = read_stats(storage)?;
+    if from.is_none() {
+        ConversationStorage::from_storage(storage).remove_message(owner, &sent_from);
+        for tag in &tags {
+            let mut ids = read_tag_index(storage, owner, tag)?;
+            ids.retain(|indexed_id| *indexed_id != id);
+            write_tag_index(storage, owner, tag, &ids)?;
+        }
+        if from_queue.length == 0 {
+            stats.active_queues = stats.active_queues.saturating_sub(1);
+            unlink_active_queue(storage, owner)?;
+        }
+    }
+    if to.is_none() {
+        ConversationStorage::from_storage(storage).add_message(owner, &sent_from, timestamp);
+        for tag in &tags {
+            index_tag(storage, config, owner, tag, id)?;
+        }
+        if to_queue.length == 1 {
+            stats.active_queues += 1;
+            link_active_queue(storage, owner)?;
+        }
+    }
+    write_stats(storage, &stats)
+}
+
+/// Returns the number of pinned messages in the queue
+fn count_pinned_messages<S: ReadonlyStorage>(storage: &S, message_queue: &MessageQueue) -> StdResult<u32> {
+    let message_storage = ReadonlyMessageStorage::from_storage(storage)?;
+    let mut count: u32 = 0;
+    let mut id = message_queue.front;
+    for _ in 0..message_queue.length {
+        match message_storage.get_message(&id)? {
+            Some(mes) => {
+                if mes.pinned {
+                    count += 1;
+                }
+                id = mes.next;
+            }
+            // this should never happen (queue length is longer than the linked messages)
+            None => return Err(StdError::generic_err("Corrupted message queue.")),
+        }
+    }
+    Ok(count)
+}
+
+pub fn try_pin<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    message_id: Uint128,
+    pinned: bool,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let message_queue = ReadonlyMessageQueueStorage::from_storage(&deps.storage).get_message_queue(&sender_address_raw);
+    let id = to_message_id(message_id);
+    match find_message_by_id(&deps.storage, &message_queue, id)? {
+        None => {
+            status = Failure;
+            code = CODE_NOT_FOUND;
+            response_message.push_str("Message not found.");
+        }
+        Some(found_mes) if pinned && !found_mes.pinned
+            && count_pinned_messages(&deps.storage, &message_queue)? >= MAX_PINNED_MESSAGES =>
+        {
+            status = Failure;
+            code = CODE_LIMIT_REACHED;
+            response_message.push_str(&format!("At most {} messages can be pinned.", MAX_PINNED_MESSAGES));
+        }
+        Some(mut found_mes) => {
+            found_mes.pinned = pinned;
+            MessageStorage::from_storage(&mut deps.storage)?.set_message(&id, found_mes)?;
+            status = Success;
+            code = CODE_OK;
+            if pinned {
+                response_message.push_str("Message pinned.");
+            } else {
+                response_message.push_str("Message unpinned.");
+            }
+        }
+    }
+
+    let answer = if pinned {
+        HandleAnswer::Pin { status, message: response_message, code }
+    } else {
+        HandleAnswer::Unpin { status, message: response_message, code }
+    };
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&answer)?),
+    })
+}
+
+/// Flags or unflags a message in any of the sender's queues. A flagged message keeps its place in
+/// the flagged index and ignores an expiry that came from the default TTL.
+pub fn try_flag<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    message_id: Uint128,
+    flagged: bool,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let id = to_message_id(message_id);
+    let message_storage = ReadonlyMessageStorage::from_storage(&deps.storage)?;
+    let found_mes = match message_storage.owner_of(&id)? {
+        Some(owner) if owner == sender_address_raw => message_storage.get_message(&id)?,
+        _ => None,
+    };
+    match found_mes {
+        None => {
+            status = Failure;
+            code = CODE_NOT_FOUND;
+            response_message.push_str("Message not found.");
+        }
+        Some(mut found_mes) => {
+            let was_flagged = found_mes.flagged;
+            found_mes.flagged = flagged;
+            MessageStorage::from_storage(&mut deps.storage)?.set_message(&id, found_mes)?;
+            let mut ids = read_flagged_index(&deps.storage, &sender_address_raw)?;
+            if flagged && !was_flagged {
+                // drop entries of messages that were read or unflagged before the index grows
+                if ids.len() >= config.max_messages as usize {
+                    let message_storage = ReadonlyMessageStorage::from_storage(&deps.storage)?;
+                    let mut still_flagged = vec![];
+                    for indexed_id in ids {
+                        if matches!(message_storage.get_message(&indexed_id)?, Some(m) if m.flagged) {
+                            still_flagged.push(indexed_id);
+                        }
+                    }
+                    ids = still_flagged;
+                }
+                ids.push(id);
+            } else if !flagged {
+                ids.retain(|indexed_id| *indexed_id != id);
+            }
+            write_flagged_index(&mut deps.storage, &sender_address_raw, &ids)?;
+            status = Success;
+            code = CODE_OK;
+            if flagged {
+                response_message.push_str("Message flagged.");
+            } else {
+                response_message.push_str("Message unflagged.");
+            }
+        }
+    }
+
+    let answer = if flagged {
+        HandleAnswer::Flag { status, message: response_message, code }
+    } else {
+        HandleAnswer::Unflag { status, message: response_message, code }
+    };
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&answer)?),
+    })
+}
+
+pub fn try_clear_all<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    force: bool,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut message_queue_storage = MessageQueueStorage::from_storage(&mut deps.storage);
+    let mut message_queue = message_queue_storage.get_message_queue(&sender_address_raw);
+    let (removed, more_remaining) = remove_messages(
+        &mut deps.storage,
+        &sender_address_raw,
+        None,
+        &mut message_queue,
+        MAX_PURGED_PER_CALL,
+        None,
+        |m| force || !m.pinned,
+    )?;
+    // cleared messages were never read, so their price is handled like the bond of an evicted
+    // message: the sender can claim it back, unless claiming would reveal an anonymous sender
+    for (id, mes) in removed.iter() {
+        if let Some(fee) = &mes.fee {
+            if mes.anonymous {
+                forfeit_to(&mut deps.storage, &sender_address_raw, fee)?;
+            } else {
+                write_bond(&mut deps.storage, *id, &Bond { sender: mes.from.clone(), amount: fee.clone() })?;
+            }
+        }
+    }
+    let removed = removed.len() as u32;
+    let response_message = String::from(&format!("Removed {} messages.", removed));
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ClearAll {
+            status,
+            message: response_message,
+            code,
+            removed,
+            more_remaining,
+        })?),
+    })
+}
+
+pub fn try_heartbeat<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    write_heartbeat(&mut deps.storage, &sender_address_raw, env.block.height)?;
+
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+    let response_message = String::from(&format!("Heartbeat recorded at block {}.", env.block.height));
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Heartbeat {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_prune<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    limit: u32,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut message_queue_storage = MessageQueueStorage::from_storage(&mut deps.storage);
+    let mut message_queue = message_queue_storage.get_message_queue(&sender_address_raw);
+
+    let orphaned = truncate_broken_queue(&mut deps.storage, &sender_address_raw, &mut message_queue)?;
+    let height = env.block.height;
+    let max_message_size = config.max_message_size as usize;
+    let (pruned, more_remaining) = prune_messages(
+        &mut deps.storage,
+        &sender_address_raw,
+        None,
+        &mut message_queue,
+        limit,
+        Some(height),
+        |m| m.is_expired(height) || m.content_size as usize > max_message_size,
+    )?;
+    let removed = orphaned + pruned;
+    let response_message = String::from(&format!("Removed {} messages.", removed));
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Prune {
+            status,
+            message: response_message,
+            code,
+            removed,
+            more_remaining,
+        })?),
+    })
+}
+
+pub fn try_size<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let response_message = String::from(&format!("Maximum number of messages allowed: {}", config.max_messages));
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    release_scheduled_messages(&mut deps.storage, &config, &sender_address_raw, env.block.height, env.block.time)?;
+    let mut message_queue_storage = MessageQueueStorage::from_storage(&mut deps.storage);
+    let mut message_queue = message_queue_storage.get_message_queue(&sender_address_raw);
+    let height = env.block.height;
+    prune_messages(&mut deps.storage, &sender_address_raw, None, &mut message_queue, MAX_PRUNED_PER_CALL, Some(height), |m| {
+        m.is_expired(height)
+    })?;
+    let number_of_unread_messages = count_visible_messages(&deps.storage, &message_queue, height)?;
+    let number_of_unread_messages =
+        obfuscate_unread_count(&deps.storage, &config, &sender_address_raw, height, number_of_unread_messages)?;
+    status = Success;
+    code = CODE_OK;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Size {
+            status,
+            message: response_message,
+            code,
+            number_of_unread_messages,
+        })?),
+    })
+}
+
+pub fn try_block<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let blocked_address_raw = deps.api.canonical_address(&address)?;
+    if add_to_blocklist(&mut deps.storage, &sender_address_raw, &blocked_address_raw)? {
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str(&format!("Address {} blocked.", address));
+    } else {
+        status = Failure;
+        code = CODE_LIMIT_REACHED;
+        response_message.push_str(&format!("A blocklist can hold at most {} addresses.", MAX_BLOCKED));
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Block {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+/// Adds an address to the owner's blocklist. Returns false if the blocklist is full
+fn add_to_blocklist<S: Storage>(storage: &mut S, owner: &CanonicalAddr, blocked: &CanonicalAddr) -> StdResult<bool> {
+    if is_blocked(storage, owner, blocked) {
+        // already blocked, only write to the storage if needed
+        return Ok(true);
+    }
+    let mut settings = ReadonlyUserSettingsStorage::from_storage(storage).get_settings(owner);
+    if settings.blocked_count >= MAX_BLOCKED {
+        return Ok(false);
+    }
+    block_sender(storage, owner, blocked, settings.blocked_count)?;
+    settings.blocked_count += 1;
+    let mut settings_storage = UserSettingsStorage::from_storage(storage);
+    settings_storage.set_settings(owner, settings);
+    Ok(true)
+}
+
+/// Blocks the sender of a message still in the caller's inbox or one of the recently read ones
+pub fn try_block_sender_of<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    message_id: Uint128,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut blocked: Option<HumanAddr> = None;
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let id = to_message_id(message_id);
+    let received = ReceivedStorage::from_storage(&mut deps.storage)
+        .get_received(&sender_address_raw)
+        .into_iter()
+        .find(|r| r.id == id);
+    // Err for anonymous messages, which have no sender to block
+    let from = match received {
+        Some(received_message) => Some(Ok(received_message.from)),
+        None => {
+            let message_queue = ReadonlyMessageQueueStorage::from_storage(&deps.storage).get_message_queue(&sender_address_raw);
+            match find_message_by_id(&deps.storage, &message_queue, id)? {
+                Some(found_mes) if found_mes.system.is_none() => {
+                    if found_mes.anonymous { Some(Err(())) } else { Some(Ok(found_mes.from)) }
+                }
+                _ => None,
+            }
+        }
+    };
+    match from {
+        None => {
+            status = Failure;
+            code = CODE_NOT_FOUND;
+            response_message.push_str("Message not found.");
+        }
+        Some(Err(())) => {
+            status = Failure;
+            code = CODE_INVALID_INPUT;
+            response_message.push_str("The sender of an anonymous message cannot be blocked.");
+        }
+        Some(Ok(from)) if from == sender_address_raw => {
+            status = Failure;
+            code = CODE_INVALID_INPUT;
+            response_message.push_str("You cannot block yourself.");
+        }
+        Some(Ok(from)) => {
+            if add_to_blocklist(&mut deps.storage, &sender_address_raw, &from)? {
+                let address = deps.api.human_address(&from)?;
+                status = Success;
+                code = CODE_OK;
+                response_message.push_str(&format!("Address {} blocked.", address));
+                blocked = Some(address);
+            } else {
+                status = Failure;
+                code = CODE_LIMIT_REACHED;
+                response_message.push_str(&format!("A blocklist can hold at most {} addresses.", MAX_BLOCKED));
+            }
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::BlockSenderOf {
+            status,
+            message: response_message,
+            code,
+            blocked,
+        })?),
+    })
+}
+
+pub fn try_unblock<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let blocked_address_raw = deps.api.canonical_address(&address)?;
+    if is_blocked(&deps.storage, &sender_address_raw, &blocked_address_raw) {
+        // only write to the storage if needed
+        let mut settings = ReadonlyUserSettingsStorage::from_storage(&deps.storage).get_settings(&sender_address_raw);
+        unblock_sender(&mut deps.storage, &sender_address_raw, &blocked_address_raw, settings.blocked_count)?;
+        // give the unblocked sender a clean slate
+        write_offense_count(&mut deps.storage, &sender_address_raw, &blocked_address_raw, 0)?;
+        settings.blocked_count = settings.blocked_count.saturating_sub(1);
+        let mut settings_storage = UserSettingsStorage::from_storage(&mut deps.storage);
+        settings_storage.set_settings(&sender_address_raw, settings);
+    }
+
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+    let response_message = String::from(&format!("Address {} unblocked.", address));
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Unblock {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_set_inbox_price<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    price: Option<FeeInfo>,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let price: Option<Fee> = price.map(Fee::from);
+    if !valid_fee(&config, &price) {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("Invalid price.");
+    } else {
+        let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+        let mut settings_storage = UserSettingsStorage::from_storage(&mut deps.storage);
+        let mut settings = settings_storage.get_settings(&sender_address_raw);
+        settings.price = price;
+        settings.bond = None;
+        settings_storage.set_settings(&sender_address_raw, settings);
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Inbox price set.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetInboxPrice {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+/// Returns true if `fee` is a positive amount of a named native coin or of the postage token
+fn valid_fee(config: &Config, fee: &Option<Fee>) -> bool {
+    match fee {
+        Some(Fee::Native { denom, amount }) => !denom.is_empty() && *amount > 0,
+        Some(Fee::Token { amount }) => config.postage.is_some() && *amount > 0,
+        None => true,
+    }
+}
+
+pub fn try_set_spam_bond<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    bond: Option<FeeInfo>,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let bond: Option<Fee> = bond.map(Fee::from);
+    if !valid_fee(&config, &bond) {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("Invalid bond.");
+    } else {
+        let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+        let mut settings_storage = UserSettingsStorage::from_storage(&mut deps.storage);
+        let mut settings = settings_storage.get_settings(&sender_address_raw);
+        settings.bond = bond;
+        settings.price = None;
+        settings_storage.set_settings(&sender_address_raw, settings);
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Spam bond set.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetSpamBond {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_report_spam<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    message_id: Uint128,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut messages: Vec<CosmosMsg> = vec![];
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut message_queue = ReadonlyMessageQueueStorage::from_storage(&deps.storage).get_message_queue(&sender_address_raw);
+
+    let id = to_message_id(message_id);
+    match find_message_by_id(&deps.storage, &message_queue, id)? {
+        Some(found_mes) if found_mes.system.is_none() => {
+            discard_message(&mut deps.storage, &sender_address_raw, &mut message_queue, id, &found_mes)?;
+            // the bond is forfeited to the recipient
+            if let Some(bond) = take_bond(&mut deps.storage, id)? {
+                let config: Config = load(&deps.storage, CONFIG_KEY)?;
+                messages.extend(fee_payout(&deps.api, &config, &env.contract.address, &env.message.sender, &bond.amount)?);
+            }
+            record_offense(&mut deps.storage, &sender_address_raw, &found_mes.from)?;
+            status = Success;
+            code = CODE_OK;
+            response_message.push_str("Message reported as spam.");
+        }
+        _ => {
+            status = Failure;
+            code = CODE_NOT_FOUND;
+            response_message.push_str("Message not found.");
+        }
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ReportSpam {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_claim_bond<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    message_id: Uint128,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut messages: Vec<CosmosMsg> = vec![];
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let id = to_message_id(message_id);
+    // a message dropped because the target muted the sender counts as unread
+    let message_gone = ReadonlyMessageStorage::from_storage(&deps.storage)?.get_message(&id)?.is_none()
+        && read_muted_message(&deps.storage, id)?.is_none();
+    match read_bond(&deps.storage, id)? {
+        // a message that still exists may yet be read or reported
+        Some(bond) if bond.sender == sender_address_raw && message_gone => {
+            take_bond(&mut deps.storage, id)?;
+            let config: Config = load(&deps.storage, CONFIG_KEY)?;
+            messages.extend(fee_payout(&deps.api, &config, &env.contract.address, &env.message.sender, &bond.amount)?);
+            status = Success;
+            code = CODE_OK;
+            response_message.push_str("Bond refunded.");
+        }
+        _ => {
+            status = Failure;
+            code = CODE_NOT_FOUND;
+            response_message.push_str("No refundable bond.");
+        }
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ClaimBond {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_add_contact<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let contact_address_raw = deps.api.canonical_address(&address)?;
+    add_contact(&mut deps.storage, &sender_address_raw, &contact_address_raw);
+
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+    let response_message = String::from(&format!("Address {} added to contacts.", address));
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::AddContact {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_remove_contact<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let contact_address_raw = deps.api.canonical_address(&address)?;
+    remove_contact(&mut deps.storage, &sender_address_raw, &contact_address_raw);
+
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+    let response_message = String::from(&format!("Address {} removed from contacts.", address));
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RemoveContact {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_set_auto_block<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    after: u32,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut settings_storage = UserSettingsStorage::from_storage(&mut deps.storage);
+    let mut settings = settings_storage.get_settings(&sender_address_raw);
+    settings.auto_block_after = after;
+    settings_storage.set_settings(&sender_address_raw, settings);
+    let response_message = if after == 0 {
+        String::from("Automatic blocking disabled.")
+    } else {
+        format!("Senders will be blocked after {} evicted messages.", after)
+    };
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetAutoBlock {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_set_sender_filter<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    filter: SenderFilter,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut settings_storage = UserSettingsStorage::from_storage(&mut deps.storage);
+    let mut settings = settings_storage.get_settings(&sender_address_raw);
+    settings.sender_filter = filter;
+    settings_storage.set_settings(&sender_address_raw, settings);
+    let response_message = match filter {
+        SenderFilter::Any => String::from("Messages are accepted from any sender."),
+        SenderFilter::HumansOnly => String::from("Messages from contracts will be refused."),
+        SenderFilter::ContractsOnly => String::from("Messages from senders other than contracts will be refused."),
+    };
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetSenderFilter {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_set_status<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    text: Option<String>,
+    away: bool,
+    share: bool,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    if matches!(text.as_ref(), Some(text) if text.len() > MAX_STATUS_SIZE) {
+        status = Failure;
+        code = CODE_TOO_LONG;
+        response_message.push_str(&format!("Status is longer than {} bytes.", MAX_STATUS_SIZE));
+    } else {
+        let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+        let mut settings_storage = UserSettingsStorage::from_storage(&mut deps.storage);
+        let mut settings = settings_storage.get_settings(&sender_address_raw);
+        // an empty status is not stored
+        settings.presence = if text.is_none() && !away && !share {
+            None
+        } else {
+            Some(Presence { text, away, shared: share })
+        };
+        settings_storage.set_settings(&sender_address_raw, settings);
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Status set.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetStatus {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_set_quiet_hours<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    window: Option<QuietWindow>,
+    defer: bool,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    if matches!(window.as_ref(), Some(window) if !window.is_valid()) {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("Quiet hours must end after they start, within a day for daily windows.");
+    } else {
+        let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+        let mut settings_storage = UserSettingsStorage::from_storage(&mut deps.storage);
+        let mut settings = settings_storage.get_settings(&sender_address_raw);
+        settings.quiet_hours = window.map(|window| QuietHours { window, defer });
+        let ended = settings.quiet_hours.is_none();
+        settings_storage.set_settings(&sender_address_raw, settings);
+        status = Success;
+        code = CODE_OK;
+        if ended {
+            response_message.push_str("Quiet hours removed.");
+        } else {
+            response_message.push_str("Quiet hours set.");
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetQuietHours {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_set_eviction_notices<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    enabled: bool,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut settings_storage = UserSettingsStorage::from_storage(&mut deps.storage);
+    let mut settings = settings_storage.get_settings(&sender_address_raw);
+    settings.eviction_notices = enabled;
+    settings_storage.set_settings(&sender_address_raw, settings);
+    let response_message = if enabled {
+        String::from("You will be notified when your messages are evicted unread.")
+    } else {
+        String::from("Eviction notices disabled.")
+    };
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetEvictionNotices {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_set_bounce_notices<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    enabled: bool,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut settings_storage = UserSettingsStorage::from_storage(&mut deps.storage);
+    let mut settings = settings_storage.get_settings(&sender_address_raw);
+    settings.bounce_notices = enabled;
+    settings_storage.set_settings(&sender_address_raw, settings);
+    let response_message = if enabled {
+        String::from("You will be notified when your messages bounce off a full queue.")
+    } else {
+        String::from("Bounce notices disabled.")
+    };
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetBounceNotices {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_set_dead_letters<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    enabled: bool,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut settings_storage = UserSettingsStorage::from_storage(&mut deps.storage);
+    let mut settings = settings_storage.get_settings(&sender_address_raw);
+    settings.dead_letters = enabled;
+    settings_storage.set_settings(&sender_address_raw, settings);
+    let response_message = if enabled {
+        format!("Up to {} evicted or expired messages will be kept as dead letters.", MAX_DEAD_LETTERS)
+    } else {
+        String::from("Evicted and expired messages will be deleted.")
+    };
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetDeadLetters {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+/// Explodes the oldest dead letter of the sender
+pub fn try_receive_dead_letter<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut content: Option<String> = None;
+    let mut raw_content: Option<Binary> = None;
+    let mut sender: Option<HumanAddr> = None;
+    let mut sent_height: Option<u64> = None;
+    let mut sent_timestamp: Option<u64> = None;
+    let mut content_type: Option<String> = None;
+    let mut reason: Option<DeadLetterReason> = None;
+    let mut sealed_until: Option<u64> = None;
+    let mut remaining: u32 = 0;
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    match read_oldest_dead_letter(&deps.storage, &sender_address_raw)? {
+        Some(letter) if letter.message.is_sealed(env.block.height) => {
+            // a letter that is still sealed keeps its place until it can be revealed
+            sealed_until = letter.message.reveal_after_height;
+            status = Failure;
+            code = CODE_SEALED;
+            response_message.push_str("The oldest dead letter is still sealed.");
+        }
+        Some(letter) => {
+            remaining = remove_oldest_dead_letter(&mut deps.storage, &sender_address_raw)?;
+            let config: Config = load(&deps.storage, CONFIG_KEY)?;
+            let (text, raw) = encode_payload(&config, letter.message.payload());
+            content = text;
+            raw_content = raw;
+            if !letter.message.anonymous {
+                sender = deps.api.human_address(&letter.message.from).ok();
+            }
+            sent_height = Some(letter.message.height);
+            sent_timestamp = Some(letter.message.timestamp);
+            content_type = letter.message.content_type;
+            reason = Some(letter.reason);
+            status = Success;
+            code = CODE_OK;
+        }
+        None => {
+            status = Failure;
+            code = CODE_NO_MESSAGES;
+            response_message.push_str("No dead letters.");
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RecvDeadLetter {
+            status,
+            message: response_message,
+            code,
+            remaining,
+            content,
+            raw_content,
+            sender,
+            sent_height,
+            sent_timestamp,
+            content_type,
+            reason,
+            sealed_until,
+        })?),
+    })
+}
+
+pub fn try_import_blocklist<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    addresses: Vec<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut imported: u32 = 0;
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut settings = ReadonlyUserSettingsStorage::from_storage(&deps.storage).get_settings(&sender_address_raw);
+
+    // only the addresses that are not blocked yet take up room
+    let mut new_addresses: Vec<CanonicalAddr> = vec![];
+    if addresses.len() <= MAX_BLOCKED as usize {
+        for address in addresses.iter() {
+            let address_raw = deps.api.canonical_address(address)?;
+            if !is_blocked(&deps.storage, &sender_address_raw, &address_raw) && !new_addresses.contains(&address_raw) {
+                new_addresses.push(address_raw);
+            }
+        }
+    }
+
+    if addresses.len() > MAX_BLOCKED as usize
+        || settings.blocked_count as usize + new_addresses.len() > MAX_BLOCKED as usize
+    {
+        status = Failure;
+        code = CODE_LIMIT_REACHED;
+        response_message.push_str(&format!("A blocklist can hold at most {} addresses.", MAX_BLOCKED));
+    } else {
+        for address_raw in new_addresses.iter() {
+            block_sender(&mut deps.storage, &sender_address_raw, address_raw, settings.blocked_count)?;
+            settings.blocked_count += 1;
+            imported += 1;
+        }
+        let mut settings_storage = UserSettingsStorage::from_storage(&mut deps.storage);
+        settings_storage.set_settings(&sender_address_raw, settings);
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str(&format!("Blocked {} new addresses.", imported));
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ImportBlocklist {
+            status,
+            message: response_message,
+            code,
+            imported,
+        })?),
+    })
+}
+
+pub fn try_read_sent<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut sent_items_storage = SentItemsStorage::from_storage(&mut deps.storage);
+    let items = sent_items_storage
+        .get_sent_items(&sender_address_raw)
+        .into_iter()
+        .map(|item| Ok(SentItemSummary {
+            id: Uint128(u128::from(item.id)),
+            target: deps.api.human_address(&item.to)?,
+            size: item.size,
+            content_hash: Binary(item.content_hash),
+            timestamp: item.timestamp,
+        }))
+        .collect::<StdResult<Vec<SentItemSummary>>>()?;
+
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+    let response_message = String::from(&format!("{} sent items.", items.len()));
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ReadSent {
+            status,
+            message: response_message,
+            code,
+            items,
+        })?),
+    })
+}
+
+pub fn try_clear_sent<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut sent_items_storage = SentItemsStorage::from_storage(&mut deps.storage);
+    sent_items_storage.set_sent_items(&sender_address_raw, vec![]);
+
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+    let response_message = String::from("Sent items cleared.");
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ClearSent {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_register_receive<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    code_hash: String,
+) -> StdResult<HandleResponse> {
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    write_receiver(&mut deps.storage, &sender_address_raw, &code_hash)?;
+
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+    let response_message = String::from("Receiver registered.");
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RegisterReceive {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_register_read_callback<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    code_hash: String,
+) -> StdResult<HandleResponse> {
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    write_sender_callback(&mut deps.storage, &sender_address_raw, &code_hash)?;
+
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+    let response_message = String::from("Read callback registered.");
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RegisterReadCallback {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_grant_query<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    querier: HumanAddr,
+    key: String,
+) -> StdResult<HandleResponse> {
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let querier_address_raw = deps.api.canonical_address(&querier)?;
+    write_delegated_key(&mut deps.storage, &sender_address_raw, &querier_address_raw, &ViewingKey(key));
+
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+    let response_message = String::from("Query access granted.");
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::GrantQuery {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_revoke_query<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    querier: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let querier_address_raw = deps.api.canonical_address(&querier)?;
+    remove_delegated_key(&mut deps.storage, &sender_address_raw, &querier_address_raw);
+
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+    let response_message = String::from("Query access revoked.");
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RevokeQuery {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+/// Appends an action of the message sender to the audit log
+fn record_audit<S: Storage, A: Api>(storage: &mut S, api: &A, env: &Env, action: AuditAction) -> StdResult<()> {
+    append_audit_entry(storage, &AuditEntry {
+        height: env.block.height,
+        timestamp: env.block.time,
+        actor: api.canonical_address(&env.message.sender)?,
+        action,
+    })
+}
+
+/// Returns true if `address` is the current admin, always false once the admin renounced
+fn is_admin<A: Api>(api: &A, config: &Config, address: &HumanAddr) -> StdResult<bool> {
+    match &config.admin {
+        Some(admin) => Ok(*admin == api.canonical_address(address)?),
+        None => Ok(false),
+    }
+}
+
+pub fn try_change_admin<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let mut config: Config = load(&deps.storage, CONFIG_KEY)?;
+    if !is_admin(&deps.api, &config, &env.message.sender)? {
+        status = Failure;
+        code = CODE_UNAUTHORIZED;
+        response_message.push_str("Only the admin can change the admin.");
+    } else {
+        let new_admin = deps.api.canonical_address(&address)?;
+        config.admin = Some(new_admin.clone());
+        save(&mut deps.storage, CONFIG_KEY, &config)?;
+        record_audit(&mut deps.storage, &deps.api, &env, AuditAction::ChangeAdmin { new_admin })?;
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Admin changed.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ChangeAdmin {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_renounce_admin<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let mut config: Config = load(&deps.storage, CONFIG_KEY)?;
+    if !is_admin(&deps.api, &config, &env.message.sender)? {
+        status = Failure;
+        code = CODE_UNAUTHORIZED;
+        response_message.push_str("Only the admin can renounce.");
+    } else {
+        config.admin = None;
+        save(&mut deps.storage, CONFIG_KEY, &config)?;
+        record_audit(&mut deps.storage, &deps.api, &env, AuditAction::RenounceAdmin)?;
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Admin renounced, the configuration is now locked.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RenounceAdmin {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_set_contract_status<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    contract_status: ContractStatus,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let mut config: Config = load(&deps.storage, CONFIG_KEY)?;
+    if !is_admin(&deps.api, &config, &env.message.sender)? {
+        status = Failure;
+        code = CODE_UNAUTHORIZED;
+        response_message.push_str("Only the admin can change the contract status.");
+    } else {
+        config.status = contract_status;
+        save(&mut deps.storage, CONFIG_KEY, &config)?;
+        record_audit(&mut deps.storage, &deps.api, &env, AuditAction::SetContractStatus { status: contract_status })?;
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Contract status changed.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetContractStatus {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_withdraw<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    amount: FeeInfo,
+    to: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut messages: Vec<CosmosMsg> = vec![];
+    let mut logs = vec![];
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let amount: Fee = amount.into();
+    let mut revenue: Revenue = may_load(&deps.storage, REVENUE_KEY)?.unwrap_or_default();
+    // token postage cannot be paid out once the deployment has no postage token
+    let payout = fee_payout(&deps.api, &config, &env.contract.address, &to, &amount)?;
+    if !is_admin(&deps.api, &config, &env.message.sender)? {
+        status = Failure;
+        code = CODE_UNAUTHORIZED;
+        response_message.push_str("Only the admin can withdraw.");
+    } else if payout.is_none() {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("This deployment has no postage token to pay out.");
+    } else if !revenue.debit(&amount) {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("Not enough postage was collected.");
+    } else {
+        save(&mut deps.storage, REVENUE_KEY, &revenue)?;
+        messages.extend(payout);
+        let to_raw = deps.api.canonical_address(&to)?;
+        record_audit(&mut deps.storage, &deps.api, &env, AuditAction::Withdraw { amount, to: to_raw })?;
+        logs = vec![
+            log("action", "withdraw"),
+            log("to", to.as_str()),
+        ];
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Postage withdrawn.");
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: logs,
+        data: Some(to_binary(&HandleAnswer::Withdraw {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_register_relay<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+    code_hash: String,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let address_raw = deps.api.canonical_address(&address)?;
+    let mut relays = read_relays(&deps.storage)?;
+    relays.retain(|relay| relay.address != address_raw);
+    if !is_admin(&deps.api, &config, &env.message.sender)? {
+        status = Failure;
+        code = CODE_UNAUTHORIZED;
+        response_message.push_str("Only the admin can register relays.");
+    } else if code_hash.is_empty() {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("A relay needs a code hash.");
+    } else if relays.len() >= MAX_RELAYS as usize {
+        status = Failure;
+        code = CODE_LIMIT_REACHED;
+        response_message.push_str(&format!("There are already {} relays.", MAX_RELAYS));
+    } else {
+        relays.push(Relay { address: address_raw.clone(), code_hash });
+        write_relays(&mut deps.storage, &relays)?;
+        record_audit(&mut deps.storage, &deps.api, &env, AuditAction::RegisterRelay { relay: address_raw })?;
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Relay registered.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RegisterRelay {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_deregister_relay<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let address_raw = deps.api.canonical_address(&address)?;
+    let mut relays = read_relays(&deps.storage)?;
+    if !is_admin(&deps.api, &config, &env.message.sender)? {
+        status = Failure;
+        code = CODE_UNAUTHORIZED;
+        response_message.push_str("Only the admin can deregister relays.");
+    } else if !relays.iter().any(|relay| relay.address == address_raw) {
+        status = Failure;
+        code = CODE_NOT_FOUND;
+        response_message.push_str("Relay not found.");
+    } else {
+        relays.retain(|relay| relay.address != address_raw);
+        write_relays(&mut deps.storage, &relays)?;
+        record_audit(&mut deps.storage, &deps.api, &env, AuditAction::DeregisterRelay { relay: address_raw })?;
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Relay deregistered.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::DeregisterRelay {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_register_contracts<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    addresses: Vec<HumanAddr>,
+    registered: bool,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    if !is_admin(&deps.api, &config, &env.message.sender)? {
+        status = Failure;
+        code = CODE_UNAUTHORIZED;
+        response_message.push_str("Only the admin can register contracts.");
+    } else {
+        for address in &addresses {
+            let address_raw = deps.api.canonical_address(address)?;
+            write_registered_contract(&mut deps.storage, &address_raw, registered);
+        }
+        let count = addresses.len() as u32;
+        let action = if registered {
+            AuditAction::RegisterContracts { count }
+        } else {
+            AuditAction::DeregisterContracts { count }
+        };
+        record_audit(&mut deps.storage, &deps.api, &env, action)?;
+        status = Success;
+        code = CODE_OK;
+        if registered {
+            response_message.push_str(&format!("{} contracts registered.", addresses.len()));
+        } else {
+            response_message.push_str(&format!("{} contracts deregistered.", addresses.len()));
+        }
+    }
+
+    let answer = if registered {
+        HandleAnswer::RegisterContracts { status, message: response_message, code }
+    } else {
+        HandleAnswer::DeregisterContracts { status, message: response_message, code }
+    };
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&answer)?),
+    })
+}
+
+// validates the values of a proposed change the same way init does, None if any is invalid
+fn valid_config_change(change: ConfigChangeInfo) -> Option<ConfigChange> {
+    if change.max_messages == Some(0) || change.max_message_size == Some(0)
+        || matches!(change.pow_difficulty, Some(difficulty) if difficulty > MAX_POW_DIFFICULTY) {
+        return None;
+    }
+    let padding_buckets = match change.padding_buckets {
+        Some(buckets) => Some(valid_padding_buckets(Some(buckets))?),
+        None => None,
+    };
+    let send_fee = match change.send_fee {
+        Some(send_fee) => Some(valid_send_fee(send_fee)?),
+        None => None,
+    };
+    let name_fee = match change.name_fee {
+        Some(name_fee) => Some(valid_name_fee(name_fee)?),
+        None => None,
+    };
+    Some(ConfigChange {
+        max_messages: change.max_messages,
+        max_message_size: change.max_message_size,
+        eviction_policy: change.eviction_policy,
+        max_sent_items: change.max_sent_items,
+        padding_buckets,
+        send_fee,
+        config_change_delay: change.config_change_delay,
+        sender_filter: change.sender_filter,
+        activity_logs: change.activity_logs,
+        name_fee,
+        default_ttl_blocks: change.default_ttl_blocks,
+        payload_validation: change.payload_validation,
+        max_messages_per_sender: change.max_messages_per_sender,
+        uniform_failures: change.uniform_failures,
+        pow_difficulty: change.pow_difficulty,
+    })
+}
+
+pub fn try_propose_config_change<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    change: ConfigChangeInfo,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut executable_height: Option<u64> = None;
+    let mut logs = vec![];
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    if !is_admin(&deps.api, &config, &env.message.sender)? {
+        status = Failure;
+        code = CODE_UNAUTHORIZED;
+        response_message.push_str("Only the admin can propose a config change.");
+    } else {
+        match valid_config_change(change) {
+            Some(change) if !change.is_empty() => {
+                let height = env.block.height.saturating_add(config.config_change_delay);
+                save(&mut deps.storage, PENDING_CONFIG_KEY, &PendingConfigChange {
+                    change,
+                    proposed_height: env.block.height,
+                    executable_height: height,
+                })?;
+                executable_height = Some(height);
+                record_audit(&mut deps.storage, &deps.api, &env, AuditAction::ProposeConfigChange {
+                    executable_height: height,
+                })?;
+                logs = vec![
+                    log("action", "propose_config_change"),
+                    log("executable_height", height.to_string()),
+                ];
+                status = Success;
+                code = CODE_OK;
+                response_message.push_str(&format!("Config change proposed, it can be executed at height {}.", height));
+            }
+            Some(_) => {
+                status = Failure;
+                code = CODE_INVALID_INPUT;
+                response_message.push_str("The proposal does not change anything.");
+            }
+            None => {
+                status = Failure;
+                code = CODE_INVALID_INPUT;
+                response_message.push_str("Invalid config change.");
+            }
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: logs,
+        data: Some(to_binary(&HandleAnswer::ProposeConfigChange {
+            status,
+            message: response_message,
+            code,
+            executable_height,
+        })?),
+    })
+}
+
+pub fn try_execute_config_change<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut logs = vec![];
+
+    let mut config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let pending: Option<PendingConfigChange> = may_load(&deps.storage, PENDING_CONFIG_KEY)?;
+    if !is_admin(&deps.api, &config, &env.message.sender)? {
+        status = Failure;
+        code = CODE_UNAUTHORIZED;
+        response_message.push_str("Only the admin can execute a config change.");
+    } else {
+        match pending {
+            None => {
+                status = Failure;
+                code = CODE_NOT_FOUND;
+                response_message.push_str("No config change is pending.");
+            }
+            Some(pending) if env.block.height < pending.executable_height => {
+                status = Failure;
+                code = CODE_SEALED;
+                response_message.push_str(&format!(
+                    "The config change can be executed at height {}.", pending.executable_height
+                ));
+            }
+            Some(pending) => {
+                pending.change.apply(&mut config);
+                save(&mut deps.storage, CONFIG_KEY, &config)?;
+                remove(&mut deps.storage, PENDING_CONFIG_KEY);
+                record_audit(&mut deps.storage, &deps.api, &env, AuditAction::ExecuteConfigChange)?;
+                logs = vec![log("action", "execute_config_change")];
+                status = Success;
+                code = CODE_OK;
+                response_message.push_str("Config change executed.");
+            }
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: logs,
+        data: Some(to_binary(&HandleAnswer::ExecuteConfigChange {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_cancel_config_change<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut logs = vec![];
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let pending: Option<PendingConfigChange> = may_load(&deps.storage, PENDING_CONFIG_KEY)?;
+    if !is_admin(&deps.api, &config, &env.message.sender)? {
+        status = Failure;
+        code = CODE_UNAUTHORIZED;
+        response_message.push_str("Only the admin can cancel a config change.");
+    } else if pending.is_none() {
+        status = Failure;
+        code = CODE_NOT_FOUND;
+        response_message.push_str("No config change is pending.");
+    } else {
+        remove(&mut deps.storage, PENDING_CONFIG_KEY);
+        record_audit(&mut deps.storage, &deps.api, &env, AuditAction::CancelConfigChange)?;
+        logs = vec![log("action", "cancel_config_change")];
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Config change cancelled.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: logs,
+        data: Some(to_binary(&HandleAnswer::CancelConfigChange {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+/// Deletes up to `limit` messages from the owner's queue, including entries lost to broken links.
+/// Returns how many were deleted and whether the queue still holds messages.
+fn purge_queue<S: Storage>(storage: &mut S, owner: &CanonicalAddr, limit: u32) -> StdResult<(u32, bool)> {
+    let mut message_queue = ReadonlyMessageQueueStorage::from_storage(storage).get_message_queue(owner);
+    let orphaned = truncate_broken_queue(storage, owner, &mut message_queue)?;
+    let (pruned, more_remaining) =
+        prune_messages(storage, owner, None, &mut message_queue, limit.saturating_sub(orphaned), None, |_| true)?;
+    if message_queue.length == 0 {
+        // drop queues that were indexed while already empty, so a purge always makes progress
+        unlink_active_queue(storage, owner)?;
+    }
+    Ok((orphaned + pruned, more_remaining))
+}
+
+pub fn try_purge_queue<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut removed: u32 = 0;
+    let mut more_remaining = false;
+    let mut logs = vec![];
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    if !is_admin(&deps.api, &config, &env.message.sender)? {
+        status = Failure;
+        code = CODE_UNAUTHORIZED;
+        response_message.push_str("Only the admin can purge queues.");
+    } else {
+        let owner = deps.api.canonical_address(&address)?;
+        let (purged, more) = purge_queue(&mut deps.storage, &owner, MAX_PURGED_PER_CALL)?;
+        removed = purged;
+        more_remaining = more;
+        record_audit(&mut deps.storage, &deps.api, &env, AuditAction::PurgeQueue { address: owner, removed })?;
+        logs = vec![
+            log("action", "purge_queue"),
+            log("address", address.as_str()),
+            log("removed", removed),
+        ];
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str(&format!("Removed {} messages.", removed));
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: logs,
+        data: Some(to_binary(&HandleAnswer::PurgeQueue {
+            status,
+            message: response_message,
+            code,
+            removed,
+            more_remaining,
+        })?),
+    })
+}
+
+pub fn try_purge_all<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    limit: u32,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut removed: u32 = 0;
+    let mut more_remaining = false;
+    let mut logs = vec![];
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    if !is_admin(&deps.api, &config, &env.message.sender)? {
+        status = Failure;
+        code = CODE_UNAUTHORIZED;
+        response_message.push_str("Only the admin can purge queues.");
+    } else {
+        let limit = limit.min(MAX_PURGED_PER_CALL);
+        while removed < limit {
+            let owner = match first_active_queue(&deps.storage)? {
+                Some(owner) => owner,
+                None => break,
+            };
+            let (purged, _) = purge_queue(&mut deps.storage, &owner, limit - removed)?;
+            removed += purged;
+        }
+        more_remaining = first_active_queue(&deps.storage)?.is_some();
+        record_audit(&mut deps.storage, &deps.api, &env, AuditAction::PurgeAll { removed })?;
+        logs = vec![
+            log("action", "purge_all"),
+            log("removed", removed),
+        ];
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str(&format!("Removed {} messages.", removed));
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: logs,
+        data: Some(to_binary(&HandleAnswer::PurgeAll {
+            status,
+            message: response_message,
+            code,
+            removed,
+            more_remaining,
+        })?),
+    })
+}
+
+/// Returns the code storing a key under `label` fails with, None if the label is available and
+/// the expiry is in the future
+fn viewing_key_error<S: ReadonlyStorage>(
+    storage: &S,
+    owner: &CanonicalAddr,
+    label: &str,
+    expire_after_height: Option<u64>,
+    height: u64,
+) -> StdResult<Option<u16>> {
+    if label.len() > MAX_VIEWING_KEY_LABEL_SIZE || matches!(expire_after_height, Some(expiry) if expiry <= height) {
+        return Ok(Some(CODE_INVALID_INPUT));
+    }
+    let labels = read_viewing_key_labels(storage, owner)?;
+    if !label.is_empty() && !labels.iter().any(|l| l == label) && labels.len() >= MAX_VIEWING_KEY_LABELS {
+        return Ok(Some(CODE_LIMIT_REACHED));
+    }
+    Ok(None)
+}
+
+/// Returns true if `key` matches any viewing key of `owner` that has not expired by the latest
+/// recorded height. That is the height of the latest handle, which lags behind the chain while
+/// nobody transacts with the contract, so a key can keep working past its expiry until the next
+/// handle. Every slot is checked, so the time taken does not reveal which key matched, whether any
+/// is set or whether the matching one expired
+fn check_viewing_keys<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr, key: &ViewingKey) -> StdResult<bool> {
+    let height = read_latest_height(storage);
+    let mut labels = vec![String::new()];
+    labels.extend(read_viewing_key_labels(storage, owner)?);
+    Ok(labels.iter().fold(false, |matched, label| {
+        match read_viewing_key(storage, owner, label) {
+            Some(expected_key) => {
+                let checked = key.check_viewing_key(&expected_key.hashed);
+                (checked && !expected_key.is_expired(height)) || matched
+            }
+            None => {
+                key.check_viewing_key(&[0u8; VIEWING_KEY_SIZE]);
+                matched
+            }
+        }
+    }))
+}
+
+pub fn try_set_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    key: String,
+    label: Option<String>,
+    expire_after_height: Option<u64>,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+
+    let label = label.unwrap_or_default();
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    match viewing_key_error(&deps.storage, &sender_address_raw, &label, expire_after_height, env.block.height)? {
+        Some(error_code) => {
+            status = Failure;
+            code = error_code;
+        }
+        None => {
+            write_viewing_key(&mut deps.storage, &sender_address_raw, &label, &ViewingKey(key), expire_after_height)?;
+            status = Success;
+            code = CODE_OK;
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetViewingKey {
+            status,
+            code,
+        })?),
+    })
+}
+
+pub fn try_revoke_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    label: Option<String>,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    if remove_viewing_key(&mut deps.storage, &sender_address_raw, &label.unwrap_or_default())? {
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Viewing key revoked.");
+    } else {
+        status = Failure;
+        code = CODE_NOT_FOUND;
+        response_message.push_str("No viewing key with this label.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RevokeViewingKey {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_rotate_storage_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    match read_key_generation(&deps.storage, &sender_address_raw).checked_add(1) {
+        Some(generation) => {
+            write_key_generation(&mut deps.storage, &sender_address_raw, generation)?;
+            status = Success;
+            code = CODE_OK;
+            response_message.push_str("Storage key rotated.");
+        }
+        None => {
+            status = Failure;
+            code = CODE_LIMIT_REACHED;
+            response_message.push_str("No storage keys left to rotate to.");
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RotateStorageKey {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_create_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    entropy: String,
+    label: Option<String>,
+    expire_after_height: Option<u64>,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut key = String::new();
+
+    let label = label.unwrap_or_default();
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    match viewing_key_error(&deps.storage, &sender_address_raw, &label, expire_after_height, env.block.height)? {
+        Some(error_code) => {
+            status = Failure;
+            code = error_code;
+        }
+        None => {
+            let seed = read_prng_seed(&deps.storage)?;
+            let (vk, next_seed) = ViewingKey::new(&env, &seed, entropy.as_bytes());
+            write_prng_seed(&mut deps.storage, &next_seed)?;
+            write_viewing_key(&mut deps.storage, &sender_address_raw, &label, &vk, expire_after_height)?;
+            key = vk.0;
+            status = Success;
+            code = CODE_OK;
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateViewingKey {
+            status,
+            code,
+            key,
+        })?),
+    })
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Ping {} => to_binary(&query_ping()?),
+        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::Relays {} => query_relays(deps),
+        QueryMsg::PendingConfigChange {} => query_pending_config_change(deps),
+        QueryMsg::Receipt { receipt } => query_receipt(deps, &receipt),
+        QueryMsg::ReceiptRoot { epoch } => query_receipt_root(deps, epoch),
+        QueryMsg::InboxPrice { address } => query_inbox_price(deps, &address),
+        QueryMsg::TokenGate { address } => query_token_gate(deps, &address),
+        QueryMsg::NftGate { address } => query_nft_gate(deps, &address),
+        QueryMsg::MinSenderAge { address } => query_min_sender_age(deps, &address),
+        QueryMsg::ResolveName { name } => query_resolve_name(deps, &name),
+        QueryMsg::NameOf { address } => query_name_of(deps, &address),
+        QueryMsg::Profile { owner } => query_profile(deps, &owner),
+        QueryMsg::Delegated { address, querier, key, query } => query_delegated(deps, &address, &querier, key, query),
+        QueryMsg::WithPermit { payload, signature, pubkey, query } => {
+            query_with_permit(deps, &payload, &signature, &pubkey, query)
+        }
+        QueryMsg::PermitEpoch { address } => {
+            let address_raw = deps.api.canonical_address(&address)?;
+            to_binary(&QueryAnswer::PermitEpoch { epoch: read_permit_epoch(&deps.storage, &address_raw)? })
+        }
+        _ => authenticated_queries(deps, msg),
+    }
+}
+
+pub fn authenticated_queries<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    let (addresses, key) = msg.get_validation_params();
+
+    for address in addresses {
+        let canonical_addr = deps.api.canonical_address(address)?;
+
+        // every key slot is checked even if none is set, so the time taken does not reveal
+        // whether a viewing key exists
+        if check_viewing_keys(&deps.storage, &canonical_addr, &key)? {
+            return match msg {
+                QueryMsg::Preview { address, tag, folder, .. } => query_preview(deps, &address, tag, folder),
+                QueryMsg::Conversations { address, .. } => query_conversations(deps, &address),
+                QueryMsg::UnreadBySender { address, .. } => query_unread_by_sender(deps, &address),
+                QueryMsg::Notes { address, .. } => query_notes(deps, &address),
+                QueryMsg::Folders { address, .. } => query_folders(deps, &address),
+                QueryMsg::Flagged { address, .. } => query_flagged(deps, &address),
+                QueryMsg::PollResults { address, poll_id, .. } => query_poll_results(deps, &address, poll_id),
+                QueryMsg::Aliases { address, .. } => query_aliases(deps, &address),
+                QueryMsg::Invites { address, .. } => query_invites(deps, &address),
+                QueryMsg::ReadProof { address, message_id, .. } => query_read_proof(deps, &address, message_id),
+                QueryMsg::SearchInbox { address, query, limit, .. } => query_search_inbox(deps, &address, query, limit),
+                QueryMsg::ViewingKeyLabels { address, .. } => query_viewing_key_labels(deps, &address),
+                QueryMsg::ViewProfile { address, owner, .. } => query_view_profile(deps, &address, &owner),
+                QueryMsg::Stats { address, .. } => query_stats(deps, &address),
+                QueryMsg::Revenue { address, .. } => query_revenue(deps, &address),
+                QueryMsg::AuditLog { address, page, .. } => query_audit_log(deps, &address, page),
+                QueryMsg::UserStats { address, .. } => query_user_stats(deps, &address),
+                QueryMsg::Blocklist { address, page, .. } => query_blocklist(deps, &address, page),
+                QueryMsg::ExportBlocklist { address, .. } => query_export_blocklist(deps, &address),
+                QueryMsg::MuteList { address, .. } => query_mute_list(deps, &address),
+                QueryMsg::ReceiptProof { address, receipt, .. } => query_receipt_proof(deps, &address, &receipt),
+                QueryMsg::CanSend { target, content_size, .. } => query_can_send(deps, &target, content_size),
+                _ => panic!("This query type does not require authentication"),
+            };
+        }
+    }
+
+    to_binary(&QueryAnswer::ViewingKeyError {
+        msg: "Wrong viewing key for this address or viewing key not set".to_string(),
+    })
+}
+
+/// Runs a query for `address` authenticated with the key it granted to `querier`
+fn query_delegated<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    querier: &HumanAddr,
+    key: String,
+    query: DelegatedQuery,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    let querier_raw = deps.api.canonical_address(querier)?;
+    let key = ViewingKey(key);
+    let authenticated = match read_delegated_key(&deps.storage, &address_raw, &querier_raw) {
+        Some(expected_key) => key.check_viewing_key(expected_key.as_slice()),
+        None => {
+            // check a dummy key so a missing grant takes as long as a wrong key
+            key.check_viewing_key(&[0u8; VIEWING_KEY_SIZE]);
+            false
+        }
+    };
+    if !authenticated {
+        return to_binary(&QueryAnswer::ViewingKeyError {
+            msg: "Wrong key for this address and querier or query access not granted".to_string(),
+        });
+    }
+
+    run_delegated_query(deps, address, &address_raw, query)
+}
+
+/// Runs a query for the signer of a permit. Permits with a bad signature, signed for another
+/// contract or an earlier epoch, or revoked by name all get the same error.
+fn query_with_permit<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    payload: &Binary,
+    signature: &Binary,
+    pubkey: &Binary,
+    query: DelegatedQuery,
+) -> StdResult<Binary> {
+    let params: PermitParams = from_binary(payload)?;
+    let address_raw = deps.api.canonical_address(&params.address)?;
+
+    let signed = read_signing_key(&deps.storage, &address_raw).as_deref() == Some(pubkey.as_slice())
+        && match (PublicKey::parse(pubkey.as_slice()).ok(), Signature::parse_slice(signature.as_slice()).ok()) {
+            (Some(public_key), Some(signature)) => public_key.verify(&sha_256(payload.as_slice()), signature),
+            _ => false,
+        };
+    let contract_raw: Option<CanonicalAddr> = may_load(&deps.storage, CONTRACT_ADDRESS_KEY)?;
+    let for_this_contract = contract_raw.is_some() && deps.api.canonical_address(&params.contract).ok() == contract_raw;
+    let valid = signed
+        && for_this_contract
+        && params.epoch == read_permit_epoch(&deps.storage, &address_raw)?
+        && !is_permit_revoked(&deps.storage, &address_raw, &params.permit_name);
+    if !valid {
+        return to_binary(&QueryAnswer::ViewingKeyError {
+            msg: "Invalid or revoked permit".to_string(),
+        });
+    }
+
+    run_delegated_query(deps, &params.address, &address_raw, query)
+}
+
+/// Runs a delegated query for an address that has already been authenticated
+fn run_delegated_query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    address_raw: &CanonicalAddr,
+    query: DelegatedQuery,
+) -> StdResult<Binary> {
+    let message_queue = ReadonlyMessageQueueStorage::from_storage(&deps.storage).get_message_queue(address_raw);
+    match query {
+        DelegatedQuery::UnreadCount {} => to_binary(&QueryAnswer::UnreadCount { count: message_queue.length }),
+        DelegatedQuery::UnreadBySender {} => query_unread_by_sender(deps, address),
+        DelegatedQuery::Message { message_id } => {
+            match find_message_by_id(&deps.storage, &message_queue, to_message_id(message_id))? {
+                Some(found_mes) => {
+                    // queries cannot see the block height, so scheduled content is never revealed here
+                    let (content, raw_content) = if found_mes.deliver_after_height.is_none()
+                        && found_mes.reveal_after_height.is_none() && found_mes.system.is_none() {
+                        let config: Config = load(&deps.storage, CONFIG_KEY)?;
+                        encode_payload(&config, found_mes.payload())
+                    } else {
+                        (None, None)
+                    };
+                    to_binary(&QueryAnswer::Message {
+                        message_id,
+                        sender: shown_sender(&deps.api, &found_mes)?,
+                        content,
+                        raw_content,
+                        sent_height: found_mes.height,
+                        sent_timestamp: found_mes.timestamp,
+                    })
+                }
+                None => Err(StdError::not_found("Message")),
+            }
+        }
     }
 }
 
@@ -320,3 +5827,607 @@ fn query_ping() -> StdResult<PingResponse> {
     Ok(PingResponse{ response: String::from("pong") })
 }
 
+fn query_config<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<ConfigResponse> {
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    Ok(ConfigResponse {
+        admin: match config.admin {
+            Some(admin) => Some(deps.api.human_address(&admin)?),
+            None => None,
+        },
+        max_messages: config.max_messages,
+        max_message_size: config.max_message_size,
+        discard: !config.eviction_policy.makes_room(),
+        eviction_policy: config.eviction_policy,
+        max_sent_items: config.max_sent_items,
+        max_chunks: MAX_CHUNKS,
+        max_reaction_size: MAX_REACTION_SIZE as u32,
+        max_content_type_size: MAX_CONTENT_TYPE_SIZE as u32,
+        max_tags: MAX_TAGS as u32,
+        max_tag_size: MAX_TAG_SIZE as u32,
+        max_status_size: MAX_STATUS_SIZE as u32,
+        max_display_name_size: MAX_DISPLAY_NAME_SIZE as u32,
+        max_bio_size: MAX_BIO_SIZE as u32,
+        max_pinned_messages: MAX_PINNED_MESSAGES,
+        max_notes: MAX_NOTES,
+        max_folders: MAX_FOLDERS,
+        max_folder_name_size: MAX_FOLDER_NAME_SIZE as u32,
+        max_archived_messages: MAX_ARCHIVED_MESSAGES,
+        archive_ttl_blocks: ARCHIVE_TTL_BLOCKS,
+        max_search_results: MAX_SEARCH_RESULTS,
+        max_fee_tiers: MAX_FEE_TIERS as u32,
+        max_poll_question_size: MAX_POLL_QUESTION_SIZE as u32,
+        max_poll_options: MAX_POLL_OPTIONS as u32,
+        max_poll_option_size: MAX_POLL_OPTION_SIZE as u32,
+        max_poll_targets: MAX_POLL_TARGETS as u32,
+        max_aliases: MAX_ALIASES as u32,
+        max_relays: MAX_RELAYS,
+        max_views: MAX_VIEWS,
+        max_search_query_size: MAX_SEARCH_QUERY_SIZE as u32,
+        max_viewing_key_labels: MAX_VIEWING_KEY_LABELS as u32,
+        max_viewing_key_label_size: MAX_VIEWING_KEY_LABEL_SIZE as u32,
+        padding_buckets: config.padding_buckets,
+        unread_count_fuzz: config.unread_count_fuzz,
+        postage: match config.postage {
+            Some(postage) => Some(PostageInfo {
+                token: deps.api.human_address(&postage.token)?,
+                token_code_hash: postage.token_code_hash,
+                amount: Uint128(postage.amount),
+                per_byte: Uint128(postage.per_byte),
+            }),
+            None => None,
+        },
+        send_fee: config.send_fee.map(SendFeeInfo::from),
+        status: config.status,
+        config_change_delay: config.config_change_delay,
+        sender_filter: config.sender_filter,
+        activity_logs: config.activity_logs,
+        name_fee: config.name_fee.map(FeeInfo::from),
+        min_name_size: MIN_NAME_SIZE as u32,
+        max_name_size: MAX_NAME_SIZE as u32,
+        default_ttl_blocks: config.default_ttl_blocks,
+        payload_validation: config.payload_validation,
+        max_messages_per_sender: config.max_messages_per_sender,
+        uniform_failures: config.uniform_failures,
+        receipt_nft: match config.receipt_nft {
+            Some(receipt_nft) => Some(ReceiptNftInfo {
+                contract: deps.api.human_address(&receipt_nft.contract)?,
+                code_hash: receipt_nft.code_hash,
+            }),
+            None => None,
+        },
+        pow_difficulty: config.pow_difficulty,
+        max_pow_difficulty: MAX_POW_DIFFICULTY,
+        state_version: read_state_version(&deps.storage),
+    })
+}
+
+fn query_pending_config_change<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let pending: Option<PendingConfigChange> = may_load(&deps.storage, PENDING_CONFIG_KEY)?;
+    match pending {
+        Some(pending) => to_binary(&QueryAnswer::PendingConfigChange {
+            change: Some(pending.change.into()),
+            proposed_height: Some(pending.proposed_height),
+            executable_height: Some(pending.executable_height),
+        }),
+        None => to_binary(&QueryAnswer::PendingConfigChange {
+            change: None,
+            proposed_height: None,
+            executable_height: None,
+        }),
+    }
+}
+
+fn query_receipt<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    receipt: &Binary,
+) -> StdResult<Binary> {
+    match read_receipt(&deps.storage, receipt.as_slice())? {
+        Some(found) => to_binary(&QueryAnswer::Receipt {
+            height: found.height,
+            timestamp: found.timestamp,
+            epoch: found.epoch,
+        }),
+        None => Err(StdError::not_found("Receipt")),
+    }
+}
+
+fn query_receipt_root<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    epoch: u64,
+) -> StdResult<Binary> {
+    match read_closed_epoch(&deps.storage, epoch)? {
+        Some(closed) => to_binary(&QueryAnswer::ReceiptRoot {
+            root: Binary(closed.root.unwrap_or_default().to_vec()),
+            size: closed.leaves.len() as u32,
+            start_height: closed.start_height,
+            end_height: closed.end_height,
+        }),
+        None => Err(StdError::not_found("Closed receipt epoch")),
+    }
+}
+
+fn query_receipt_proof<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    receipt: &Binary,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    // receipts of other addresses are reported as missing
+    let found = match read_receipt(&deps.storage, receipt.as_slice())? {
+        Some(found) if found.sender == address_raw || found.recipient == address_raw => found,
+        _ => return Err(StdError::not_found("Receipt")),
+    };
+    let closed = match read_closed_epoch(&deps.storage, found.epoch)? {
+        Some(closed) => closed,
+        None => return Err(StdError::generic_err("The receipt epoch is still open.")),
+    };
+    let index = found.index as usize;
+    let proof = merkle_proof(&closed.leaves, index)
+        .into_iter()
+        .map(|(sibling, left)| MerkleStep { sibling: Binary(sibling.to_vec()), left })
+        .collect();
+
+    to_binary(&QueryAnswer::ReceiptProof {
+        epoch: found.epoch,
+        index: found.index,
+        leaf: Binary(closed.leaves[index].to_vec()),
+        proof,
+        root: Binary(closed.root.unwrap_or_default().to_vec()),
+    })
+}
+
+fn query_inbox_price<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    let settings = ReadonlyUserSettingsStorage::from_storage(&deps.storage).get_settings(&address_raw);
+
+    to_binary(&QueryAnswer::InboxPrice {
+        price: settings.price.map(FeeInfo::from),
+        bond: settings.bond.map(FeeInfo::from),
+    })
+}
+
+fn query_revenue<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    if !is_admin(&deps.api, &config, address)? {
+        return Err(StdError::unauthorized());
+    }
+    let revenue: Revenue = may_load(&deps.storage, REVENUE_KEY)?.unwrap_or_default();
+
+    to_binary(&QueryAnswer::Revenue {
+        native: revenue.native.into_iter().map(|(denom, amount)| Coin { denom, amount: Uint128(amount) }).collect(),
+        token: Uint128(revenue.token),
+    })
+}
+
+fn audit_action_info<A: Api>(api: &A, action: AuditAction) -> StdResult<AuditActionInfo> {
+    Ok(match action {
+        AuditAction::ChangeAdmin { new_admin } => AuditActionInfo::ChangeAdmin {
+            new_admin: api.human_address(&new_admin)?,
+        },
+        AuditAction::RenounceAdmin => AuditActionInfo::RenounceAdmin {},
+        AuditAction::SetContractStatus { status } => AuditActionInfo::SetContractStatus { status },
+        AuditAction::Withdraw { amount, to } => AuditActionInfo::Withdraw {
+            amount: amount.into(),
+            to: api.human_address(&to)?,
+        },
+        AuditAction::RegisterContracts { count } => AuditActionInfo::RegisterContracts { count },
+        AuditAction::DeregisterContracts { count } => AuditActionInfo::DeregisterContracts { count },
+        AuditAction::RegisterRelay { relay } => AuditActionInfo::RegisterRelay { relay: api.human_address(&relay)? },
+        AuditAction::DeregisterRelay { relay } => AuditActionInfo::DeregisterRelay { relay: api.human_address(&relay)? },
+        AuditAction::ProposeConfigChange { executable_height } => {
+            AuditActionInfo::ProposeConfigChange { executable_height }
+        }
+        AuditAction::ExecuteConfigChange => AuditActionInfo::ExecuteConfigChange {},
+        AuditAction::CancelConfigChange => AuditActionInfo::CancelConfigChange {},
+        AuditAction::PurgeQueue { address, removed } => AuditActionInfo::PurgeQueue {
+            address: api.human_address(&address)?,
+            removed,
+        },
+        AuditAction::PurgeAll { removed } => AuditActionInfo::PurgeAll { removed },
+    })
+}
+
+/// Returns the read signature of a message the address sent with require_read_signature
+fn query_read_proof<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    message_id: Uint128,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    match read_read_proof(&deps.storage, &address_raw, to_message_id(message_id))? {
+        Some(proof) => to_binary(&QueryAnswer::ReadProof {
+            message_id,
+            recipient: deps.api.human_address(&proof.recipient)?,
+            pubkey: proof.pubkey,
+            signature: proof.signature,
+            read_height: proof.height,
+        }),
+        None => Err(StdError::generic_err("Read proof not found.")),
+    }
+}
+
+fn query_relays<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
+    let relays = read_relays(&deps.storage)?
+        .into_iter()
+        .map(|relay| Ok(RelayInfo { address: deps.api.human_address(&relay.address)?, code_hash: relay.code_hash }))
+        .collect::<StdResult<Vec<RelayInfo>>>()?;
+    to_binary(&QueryAnswer::Relays { relays })
+}
+
+fn query_audit_log<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    page: u32,
+) -> StdResult<Binary> {
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    if !is_admin(&deps.api, &config, address)? {
+        return Err(StdError::unauthorized());
+    }
+    let total = audit_log_len(&deps.storage)?;
+    let start = (page as u64).saturating_mul(AUDIT_PAGE_SIZE as u64);
+    let end = start.saturating_add(AUDIT_PAGE_SIZE as u64).min(total);
+    let mut entries = vec![];
+    for index in start..end {
+        if let Some(entry) = read_audit_entry(&deps.storage, index)? {
+            entries.push(AuditEntryInfo {
+                index,
+                height: entry.height,
+                timestamp: entry.timestamp,
+                actor: deps.api.human_address(&entry.actor)?,
+                action: audit_action_info(&deps.api, entry.action)?,
+            });
+        }
+    }
+
+    to_binary(&QueryAnswer::AuditLog {
+        entries,
+        total,
+        page_size: AUDIT_PAGE_SIZE,
+    })
+}
+
+fn query_stats<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    if !is_admin(&deps.api, &config, address)? {
+        return Err(StdError::unauthorized());
+    }
+    let stats = read_stats(&deps.storage)?;
+
+    to_binary(&QueryAnswer::Stats {
+        messages_sent: stats.messages_sent,
+        messages_exploded: stats.messages_exploded,
+        messages_evicted: stats.messages_evicted,
+        messages_expired: stats.messages_expired,
+        active_queues: stats.active_queues,
+    })
+}
+
+fn query_user_stats<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    let stats = read_user_stats(&deps.storage, &address_raw)?;
+
+    to_binary(&QueryAnswer::UserStats {
+        messages_sent: stats.messages_sent,
+        messages_received: stats.messages_received,
+        messages_evicted: stats.messages_evicted,
+    })
+}
+
+fn query_blocklist<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    page: u32,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    let settings = ReadonlyUserSettingsStorage::from_storage(&deps.storage).get_settings(&address_raw);
+    let addresses = read_blocklist_page(&deps.storage, &address_raw, page)?
+        .iter()
+        .map(|a| deps.api.human_address(a))
+        .collect::<StdResult<Vec<HumanAddr>>>()?;
+
+    to_binary(&QueryAnswer::Blocklist {
+        addresses,
+        total: settings.blocked_count,
+        page_size: BLOCKLIST_PAGE_SIZE,
+    })
+}
+
+fn query_export_blocklist<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    let settings = ReadonlyUserSettingsStorage::from_storage(&deps.storage).get_settings(&address_raw);
+    let mut addresses: Vec<HumanAddr> = vec![];
+    // step through the index of the first address on each page
+    for first in (0..settings.blocked_count).step_by(BLOCKLIST_PAGE_SIZE as usize) {
+        for blocked in read_blocklist_page(&deps.storage, &address_raw, first / BLOCKLIST_PAGE_SIZE)?.iter() {
+            addresses.push(deps.api.human_address(blocked)?);
+        }
+    }
+
+    to_binary(&QueryAnswer::ExportBlocklist { addresses })
+}
+
+fn query_can_send<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    target: &HumanAddr,
+    content_size: u32,
+) -> StdResult<Binary> {
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let target_address_raw = deps.api.canonical_address(target)?;
+    let message_queue_storage = ReadonlyMessageQueueStorage::from_storage(&deps.storage);
+    let message_queue = message_queue_storage.get_message_queue(&target_address_raw);
+
+    // the blocklist is deliberately not checked so senders cannot probe whether they are blocked
+    let reason = if content_size > config.max_message_size.into() {
+        Some(String::from("too_long"))
+    } else if (message_queue.length >= config.max_messages) && !config.eviction_policy.makes_room() {
+        Some(String::from("queue_full"))
+    } else {
+        None
+    };
+
+    to_binary(&QueryAnswer::CanSend {
+        can_send: reason.is_none(),
+        reason,
+    })
+}
+
+/// Returns the sender of a message as shown to the recipient, None if it was sent anonymously
+fn shown_sender<A: Api>(api: &A, mes: &Message) -> StdResult<Option<HumanAddr>> {
+    if mes.anonymous {
+        Ok(None)
+    } else {
+        api.human_address(&mes.from).map(Some)
+    }
+}
+
+/// Returns the notice shown in place of the content of a message generated by the contract
+fn system_notice<S: ReadonlyStorage>(storage: &S, system: Option<SystemMessage>) -> StdResult<Option<SystemNotice>> {
+    Ok(match system {
+        Some(SystemMessage::Reaction { message_id: reacted_id, reaction }) => {
+            Some(SystemNotice::Reaction { message_id: Uint128(u128::from(reacted_id)), reaction })
+        }
+        Some(SystemMessage::Evicted { sent_height, sent_timestamp }) => {
+            Some(SystemNotice::Evicted { sent_height, sent_timestamp })
+        }
+        Some(SystemMessage::Bounced { sent_height, sent_timestamp }) => {
+            Some(SystemNotice::Bounced { sent_height, sent_timestamp })
+        }
+        Some(SystemMessage::Poll { poll_id }) => read_poll(storage, poll_id)?
+            .map(|poll| SystemNotice::Poll { question: poll.question, options: poll.options }),
+        None => None,
+    })
+}
+
+fn message_preview<S: ReadonlyStorage, A: Api>(storage: &S, api: &A, id: u64, mes: Message) -> StdResult<MessagePreview> {
+    Ok(MessagePreview {
+        id: Uint128(u128::from(id)),
+        sender: shown_sender(api, &mes)?,
+        size: mes.content_size,
+        priority: mes.priority,
+        content_type: mes.content_type,
+        height: mes.height,
+        timestamp: mes.timestamp,
+        committed_height: mes.committed_height,
+        deliver_after_height: mes.deliver_after_height,
+        reveal_after_height: mes.reveal_after_height,
+        expire_after_height: mes.expire_after_height,
+        snoozed_until: mes.snoozed_until,
+        pinned: mes.pinned,
+        flagged: mes.flagged,
+        time_capsule: mes.time_capsule,
+        system: system_notice(storage, mes.system)?,
+        tags: mes.tags,
+    })
+}
+
+fn query_preview<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    tag: Option<String>,
+    folder: Option<String>,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    let message_queue = match folder {
+        Some(folder) => read_folder_queue(&deps.storage, &address_raw, &folder)?,
+        None => {
+            if let Some(tag) = tag {
+                let messages = tagged_messages(&deps.storage, &address_raw, &tag)?
+                    .into_iter()
+                    .map(|(id, mes)| message_preview(&deps.storage, &deps.api, id, mes))
+                    .collect::<StdResult<Vec<MessagePreview>>>()?;
+                return to_binary(&QueryAnswer::Preview { messages });
+            }
+            ReadonlyMessageQueueStorage::from_storage(&deps.storage).get_message_queue(&address_raw)
+        }
+    };
+    let message_storage = ReadonlyMessageStorage::from_storage(&deps.storage)?;
+
+    let mut messages: Vec<MessagePreview> = vec![];
+    let mut id = message_queue.front;
+    for _ in 0..message_queue.length {
+        let mes: Option<Message> = message_storage.get_message(&id)?;
+        if let Some(found_mes) = mes {
+            let next = found_mes.next;
+            // folders are outside the tag index, so their messages are filtered here
+            if !matches!(tag.as_ref(), Some(tag) if !found_mes.tags.contains(tag)) {
+                messages.push(message_preview(&deps.storage, &deps.api, id, found_mes)?);
+            }
+            id = next;
+        } else {
+            // this should never happen (queue length is longer than the linked messages)
+            return Err(StdError::generic_err("Corrupted message queue."));
+        }
+    }
+
+    to_binary(&QueryAnswer::Preview { messages })
+}
+
+/// Returns up to SEARCH_SNIPPET_SIZE bytes of `text` starting a little before `at`, cut at
+/// character boundaries
+fn snippet(text: &str, at: usize) -> String {
+    let mut start = at.saturating_sub(SEARCH_SNIPPET_SIZE / 4);
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = (start + SEARCH_SNIPPET_SIZE).min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text[start..end].to_string()
+}
+
+/// Scans the inbox for messages whose content contains the query, ignoring ascii case. Queries
+/// cannot see the block height, so messages still scheduled, sealed or already expired at the
+/// latest recorded height are skipped, like recv would skip them.
+fn query_search_inbox<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    query: String,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    if query.is_empty() || query.len() > MAX_SEARCH_QUERY_SIZE {
+        return Err(StdError::generic_err(format!("Search queries are 1-{} bytes.", MAX_SEARCH_QUERY_SIZE)));
+    }
+    let limit = limit.unwrap_or(MAX_SEARCH_RESULTS).min(MAX_SEARCH_RESULTS) as usize;
+    let needle = query.to_ascii_lowercase();
+    let height = read_latest_height(&deps.storage);
+    let address_raw = deps.api.canonical_address(address)?;
+    let message_queue = ReadonlyMessageQueueStorage::from_storage(&deps.storage).get_message_queue(&address_raw);
+    let message_storage = ReadonlyMessageStorage::from_storage(&deps.storage)?;
+
+    let mut matches: Vec<SearchMatch> = vec![];
+    let mut id = message_queue.front;
+    for _ in 0..message_queue.length {
+        if matches.len() >= limit {
+            break;
+        }
+        let mes = match message_storage.get_message(&id)? {
+            Some(mes) => mes,
+            // this should never happen (queue length is longer than the linked messages)
+            None => return Err(StdError::generic_err("Corrupted message queue.")),
+        };
+        let scheduled = matches!(mes.deliver_after_height, Some(deliver_after_height) if height <= deliver_after_height);
+        if mes.system.is_none() && !scheduled && !mes.is_sealed(height) && !mes.is_expired(height) {
+            // ascii lowercasing keeps byte offsets, so the match position indexes the content
+            let text = String::from_utf8_lossy(mes.payload()).into_owned();
+            if let Some(at) = text.to_ascii_lowercase().find(&needle) {
+                matches.push(SearchMatch {
+                    id: Uint128(u128::from(id)),
+                    sender: shown_sender(&deps.api, &mes)?,
+                    timestamp: mes.timestamp,
+                    snippet: snippet(&text, at),
+                });
+            }
+        }
+        id = mes.next;
+    }
+
+    to_binary(&QueryAnswer::SearchInbox { matches })
+}
+
+fn query_flagged<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    let message_storage = ReadonlyMessageStorage::from_storage(&deps.storage)?;
+    let mut messages: Vec<MessagePreview> = vec![];
+    for id in read_flagged_index(&deps.storage, &address_raw)? {
+        // the index may still hold ids of messages that were read, and their ids may be reused
+        if message_storage.owner_of(&id)?.as_ref() != Some(&address_raw) {
+            continue;
+        }
+        match message_storage.get_message(&id)? {
+            Some(mes) if mes.flagged => messages.push(message_preview(&deps.storage, &deps.api, id, mes)?),
+            _ => {}
+        }
+    }
+
+    to_binary(&QueryAnswer::Preview { messages })
+}
+
+fn query_folders<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    let folders = read_folders(&deps.storage, &address_raw)?
+        .into_iter()
+        .map(|f| Ok(FolderInfo {
+            length: read_folder_queue(&deps.storage, &address_raw, &f.name)?.length,
+            name: f.name,
+            max_messages: f.max_messages,
+        }))
+        .collect::<StdResult<Vec<FolderInfo>>>()?;
+
+    to_binary(&QueryAnswer::Folders { folders })
+}
+
+fn query_conversations<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    let conversation_storage = ReadonlyConversationStorage::from_storage(&deps.storage);
+    let conversations = conversation_storage
+        .get_conversations(&address_raw)
+        .into_iter()
+        .map(|c| Ok(ConversationSummary {
+            sender: deps.api.human_address(&c.sender)?,
+            unread: c.unread,
+            latest_timestamp: c.latest_timestamp,
+        }))
+        .collect::<StdResult<Vec<ConversationSummary>>>()?;
+
+    to_binary(&QueryAnswer::Conversations { conversations })
+}
+
+fn query_viewing_key_labels<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+
+    to_binary(&QueryAnswer::ViewingKeyLabels {
+        labels: read_viewing_key_labels(&deps.storage, &address_raw)?,
+        unlabeled: read_viewing_key(&deps.storage, &address_raw, "").is_some(),
+    })
+}
+
+fn query_unread_by_sender<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    let conversation_storage = ReadonlyConversationStorage::from_storage(&deps.storage);
+    let counts = conversation_storage
+        .get_conversations(&address_raw)
+        .into_iter()
+        .map(|c| Ok(SenderUnread {
+            sender: deps.api.human_address(&c.sender)?,
+            unread: c.unread,
+        }))
+        .collect::<StdResult<Vec<SenderUnread>>>()?;
+
+    to_binary(&QueryAnswer::UnreadBySender { counts })
+}
+
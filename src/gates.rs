@@ -0,0 +1,361 @@
+use cosmwasm_std::{to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, Querier,
+                   ReadonlyStorage, StdResult, Storage, Uint128};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use secret_toolkit::utils::Query;
+
+use crate::contract::BLOCK_SIZE;
+use crate::msg::{HandleAnswer, NftGateInfo, QueryAnswer, ResponseStatus, TokenGateInfo, TokenPermit};
+use crate::msg::ResponseStatus::{Success, Failure};
+use crate::msg::{CODE_OK, CODE_INVALID_INPUT};
+use crate::state::{may_load, save, remove, is_contact, read_signing_key, read_first_seen, read_latest_height};
+
+// keys for token gates take form: b"tkg{recipient.as_slice()}"
+pub static TOKEN_GATE_PREFIX: &[u8] = b"tkg";
+// keys for NFT gates take form: b"nfg{recipient.as_slice()}"
+pub static NFT_GATE_PREFIX: &[u8] = b"nfg";
+// keys for minimum sender ages take form: b"agg{recipient.as_slice()}"
+pub static AGE_GATE_PREFIX: &[u8] = b"agg";
+
+/// SNIP-20 token senders other than contacts must hold at least `min_balance` of to send to the
+/// recipient
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TokenGate {
+    pub contract: CanonicalAddr,
+    pub code_hash: String,
+    pub min_balance: u128,
+}
+
+/// SNIP-721 collection senders other than contacts must own a token of to send to the recipient
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NftGate {
+    pub contract: CanonicalAddr,
+    pub code_hash: String,
+}
+
+/// queries of the gate token, only the balance is asked for, with the sender's permit
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Snip20QueryMsg {
+    WithPermit {
+        permit: TokenPermit,
+        query: Snip20PermitQuery,
+    },
+}
+
+impl Query for Snip20QueryMsg {
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Snip20PermitQuery {
+    Balance {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Snip20QueryAnswer {
+    Balance { amount: Uint128 },
+}
+
+/// queries of the gate collection, only the tokens of the sender are asked for, with their permit
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Snip721QueryMsg {
+    WithPermit {
+        permit: TokenPermit,
+        query: Snip721PermitQuery,
+    },
+}
+
+impl Query for Snip721QueryMsg {
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Snip721PermitQuery {
+    Tokens {
+        owner: HumanAddr,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Snip721QueryAnswer {
+    TokenList { tokens: Vec<String> },
+}
+
+pub fn read_token_gate<S: ReadonlyStorage>(storage: &S, recipient: &CanonicalAddr) -> StdResult<Option<TokenGate>> {
+    may_load(&ReadonlyPrefixedStorage::new(TOKEN_GATE_PREFIX, storage), recipient.as_slice())
+}
+
+pub fn read_nft_gate<S: ReadonlyStorage>(storage: &S, recipient: &CanonicalAddr) -> StdResult<Option<NftGate>> {
+    may_load(&ReadonlyPrefixedStorage::new(NFT_GATE_PREFIX, storage), recipient.as_slice())
+}
+
+/// Returns the number of blocks senders other than contacts must have used the contract for to
+/// send to the recipient, 0 if there is no minimum
+pub fn read_min_sender_age<S: ReadonlyStorage>(storage: &S, recipient: &CanonicalAddr) -> StdResult<u64> {
+    Ok(may_load(&ReadonlyPrefixedStorage::new(AGE_GATE_PREFIX, storage), recipient.as_slice())?.unwrap_or(0))
+}
+
+/// Returns true if the sender first used the contract long enough before `height` for the target
+pub fn age_gate_passed<S: ReadonlyStorage>(
+    storage: &S,
+    target: &CanonicalAddr,
+    sender: &CanonicalAddr,
+    height: u64,
+) -> StdResult<bool> {
+    if target == sender || is_contact(storage, target, sender) {
+        return Ok(true);
+    }
+    let min_age = read_min_sender_age(storage, target)?;
+    if min_age == 0 {
+        return Ok(true);
+    }
+    Ok(matches!(read_first_seen(storage, sender), Some(first_seen) if height.saturating_sub(first_seen) >= min_age))
+}
+
+/// Returns true if the target has a token or NFT gate the sender is subject to, for deliveries
+/// that cannot carry a permit
+pub fn gated<S: ReadonlyStorage>(storage: &S, target: &CanonicalAddr, sender: &CanonicalAddr) -> StdResult<bool> {
+    if target == sender || is_contact(storage, target, sender) {
+        return Ok(false);
+    }
+    Ok(read_token_gate(storage, target)?.is_some() || read_nft_gate(storage, target)?.is_some())
+}
+
+/// Returns true if the sender passes the target's token gate. The permit must be signed with the
+/// signing key the sender registered, which ties the queried balance to the sender. A permit the
+/// token rejects fails the gate instead of the transaction.
+pub fn token_gate_passed<S: ReadonlyStorage, A: Api, Q: Querier>(
+    storage: &S,
+    api: &A,
+    querier: &Q,
+    target: &CanonicalAddr,
+    sender: &CanonicalAddr,
+    permit: Option<&TokenPermit>,
+) -> StdResult<bool> {
+    if target == sender || is_contact(storage, target, sender) {
+        return Ok(true);
+    }
+    let gate = match read_token_gate(storage, target)? {
+        Some(gate) => gate,
+        None => return Ok(true),
+    };
+    let signing_key = read_signing_key(storage, sender);
+    let permit = match permit {
+        Some(permit) if signing_key.as_deref() == Some(permit.signature.pub_key.value.as_slice()) => permit,
+        _ => return Ok(false),
+    };
+    let query = Snip20QueryMsg::WithPermit { permit: permit.clone(), query: Snip20PermitQuery::Balance {} };
+    let answer: StdResult<Snip20QueryAnswer> = query.query(querier, gate.code_hash, api.human_address(&gate.contract)?);
+    Ok(match answer {
+        Ok(Snip20QueryAnswer::Balance { amount }) => amount.u128() >= gate.min_balance,
+        Err(_) => false,
+    })
+}
+
+/// Returns true if the sender passes the target's NFT gate. The collection is asked for the
+/// tokens of the sender's own address, so the permit only has to let the contract see them. A
+/// permit the collection rejects fails the gate instead of the transaction.
+pub fn nft_gate_passed<S: ReadonlyStorage, A: Api, Q: Querier>(
+    storage: &S,
+    api: &A,
+    querier: &Q,
+    target: &CanonicalAddr,
+    sender: &CanonicalAddr,
+    permit: Option<&TokenPermit>,
+) -> StdResult<bool> {
+    if target == sender || is_contact(storage, target, sender) {
+        return Ok(true);
+    }
+    let (gate, permit) = match (read_nft_gate(storage, target)?, permit) {
+        (None, _) => return Ok(true),
+        (Some(gate), Some(permit)) => (gate, permit),
+        (Some(_), None) => return Ok(false),
+    };
+    let query = Snip721QueryMsg::WithPermit {
+        permit: permit.clone(),
+        query: Snip721PermitQuery::Tokens { owner: api.human_address(sender)?, start_after: None, limit: Some(1) },
+    };
+    let answer: StdResult<Snip721QueryAnswer> = query.query(querier, gate.code_hash, api.human_address(&gate.contract)?);
+    Ok(match answer {
+        Ok(Snip721QueryAnswer::TokenList { tokens }) => !tokens.is_empty(),
+        Err(_) => false,
+    })
+}
+
+/// Sets or, with None, removes the token senders other than contacts must hold to send to the
+/// caller
+pub fn try_set_token_gate<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    gate: Option<TokenGateInfo>,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    match gate {
+        Some(gate) if gate.min_balance.is_zero() => {
+            status = Failure;
+            code = CODE_INVALID_INPUT;
+            response_message.push_str("The minimum balance must be greater than 0.");
+        }
+        Some(gate) => {
+            let record = TokenGate {
+                contract: deps.api.canonical_address(&gate.contract)?,
+                code_hash: gate.code_hash,
+                min_balance: gate.min_balance.u128(),
+            };
+            save(&mut PrefixedStorage::new(TOKEN_GATE_PREFIX, &mut deps.storage), sender_address_raw.as_slice(), &record)?;
+            status = Success;
+            code = CODE_OK;
+            response_message.push_str(&format!("Senders must hold at least {} of the token.", gate.min_balance));
+        }
+        None => {
+            remove(&mut PrefixedStorage::new(TOKEN_GATE_PREFIX, &mut deps.storage), sender_address_raw.as_slice());
+            status = Success;
+            code = CODE_OK;
+            response_message.push_str("Token gate removed.");
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetTokenGate {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+/// Returns the token gate of an address, so senders know which token to sign a permit for
+pub fn query_token_gate<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    let gate = match read_token_gate(&deps.storage, &address_raw)? {
+        Some(gate) => Some(TokenGateInfo {
+            contract: deps.api.human_address(&gate.contract)?,
+            code_hash: gate.code_hash,
+            min_balance: Uint128(gate.min_balance),
+        }),
+        None => None,
+    };
+
+    to_binary(&QueryAnswer::TokenGate { gate })
+}
+
+/// Sets or, with None, removes the SNIP-721 collection senders other than contacts must own a
+/// token of to send to the caller
+pub fn try_set_nft_gate<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    gate: Option<NftGateInfo>,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    match gate {
+        Some(gate) => {
+            let record = NftGate {
+                contract: deps.api.canonical_address(&gate.contract)?,
+                code_hash: gate.code_hash,
+            };
+            save(&mut PrefixedStorage::new(NFT_GATE_PREFIX, &mut deps.storage), sender_address_raw.as_slice(), &record)?;
+            response_message.push_str("Senders must own a token of the collection.");
+        }
+        None => {
+            remove(&mut PrefixedStorage::new(NFT_GATE_PREFIX, &mut deps.storage), sender_address_raw.as_slice());
+            response_message.push_str("NFT gate removed.");
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetNftGate {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+/// Returns the NFT gate of an address, so senders know which collection to sign a permit for
+pub fn query_nft_gate<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    let gate = match read_nft_gate(&deps.storage, &address_raw)? {
+        Some(gate) => Some(NftGateInfo {
+            contract: deps.api.human_address(&gate.contract)?,
+            code_hash: gate.code_hash,
+        }),
+        None => None,
+    };
+
+    to_binary(&QueryAnswer::NftGate { gate })
+}
+
+/// Sets the number of blocks senders other than contacts must have used the contract for to send
+/// to the caller, 0 removes the minimum
+pub fn try_set_min_sender_age<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    blocks: u64,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut age_gate_storage = PrefixedStorage::new(AGE_GATE_PREFIX, &mut deps.storage);
+    if blocks == 0 {
+        remove(&mut age_gate_storage, sender_address_raw.as_slice());
+        response_message.push_str("Senders of any age are accepted.");
+    } else {
+        save(&mut age_gate_storage, sender_address_raw.as_slice(), &blocks)?;
+        response_message.push_str(&format!("Senders must have used the contract for at least {} blocks.", blocks));
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetMinSenderAge {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+/// Returns the minimum sender age of an address and the height from which a sender who first
+/// used the contract now would be accepted
+pub fn query_min_sender_age<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    let blocks = read_min_sender_age(&deps.storage, &address_raw)?;
+
+    to_binary(&QueryAnswer::MinSenderAge {
+        blocks,
+        accepted_from_height: read_latest_height(&deps.storage).saturating_add(blocks),
+    })
+}
@@ -0,0 +1,210 @@
+use cosmwasm_std::{to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, Querier,
+                   ReadonlyStorage, StdResult, Storage};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use serde::{Deserialize, Serialize};
+
+use secret_toolkit::crypto::sha_256;
+
+use crate::msg::{HandleAnswer, QueryAnswer, ResponseStatus};
+use crate::msg::ResponseStatus::{Success, Failure};
+use crate::msg::{CODE_OK, CODE_LIMIT_REACHED, CODE_NOT_FOUND};
+use crate::state::{may_load, save, remove, add_contact, is_contact, read_prng_seed, write_prng_seed};
+
+// keys for invite codes take form: b"inv{code}"
+pub static INVITE_PREFIX: &[u8] = b"inv";
+// keys for the invite codes of an address take form: b"ini{CanonicalAddr.as_slice().to_vec()}"
+pub static INVITE_INDEX_PREFIX: &[u8] = b"ini";
+// keys for invite-only inboxes take form: b"ivo{CanonicalAddr.as_slice().to_vec()}"
+pub static INVITE_ONLY_PREFIX: &[u8] = b"ivo";
+
+/// maximum number of unredeemed invite codes of an address
+pub const MAX_INVITES: usize = 32;
+/// prefix of generated invite codes
+pub const GENERATED_INVITE_PREFIX: &str = "invite-";
+
+/// single-use code that lets a stranger send a first message to its owner
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Invite {
+    pub owner: CanonicalAddr,
+}
+
+fn read_invite<S: ReadonlyStorage>(storage: &S, code: &str) -> StdResult<Option<Invite>> {
+    may_load(&ReadonlyPrefixedStorage::new(INVITE_PREFIX, storage), code.as_bytes())
+}
+
+fn read_invite_index<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> StdResult<Vec<String>> {
+    Ok(may_load(&ReadonlyPrefixedStorage::new(INVITE_INDEX_PREFIX, storage), owner.as_slice())?.unwrap_or_default())
+}
+
+fn write_invite_index<S: Storage>(storage: &mut S, owner: &CanonicalAddr, codes: &[String]) -> StdResult<()> {
+    save(&mut PrefixedStorage::new(INVITE_INDEX_PREFIX, storage), owner.as_slice(), &codes.to_vec())
+}
+
+/// Returns true if the address only takes messages from contacts and holders of its invite codes
+pub fn is_invite_only<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> bool {
+    ReadonlyPrefixedStorage::new(INVITE_ONLY_PREFIX, storage).get(owner.as_slice()).is_some()
+}
+
+/// Returns true if the sender needs an invite code to send to the target
+pub fn needs_invite<S: ReadonlyStorage>(storage: &S, target: &CanonicalAddr, sender: &CanonicalAddr) -> bool {
+    target != sender && is_invite_only(storage, target) && !is_contact(storage, target, sender)
+}
+
+/// Returns true if `code` is an unredeemed invite code of the target
+pub fn invite_valid<S: ReadonlyStorage>(storage: &S, target: &CanonicalAddr, code: Option<&str>) -> StdResult<bool> {
+    Ok(match code {
+        Some(code) => matches!(read_invite(storage, code)?, Some(invite) if &invite.owner == target),
+        None => false,
+    })
+}
+
+/// Uses up an invite code and, unless the sender stays anonymous, makes the sender a contact of
+/// the code's owner
+pub fn redeem_invite<S: Storage>(storage: &mut S, code: &str, sender: Option<&CanonicalAddr>) -> StdResult<()> {
+    if let Some(invite) = read_invite(storage, code)? {
+        remove(&mut PrefixedStorage::new(INVITE_PREFIX, storage), code.as_bytes());
+        let mut codes = read_invite_index(storage, &invite.owner)?;
+        codes.retain(|c| c != code);
+        write_invite_index(storage, &invite.owner, &codes)?;
+        if let Some(sender) = sender {
+            add_contact(storage, &invite.owner, sender);
+        }
+    }
+    Ok(())
+}
+
+/// Generates an invite code from the contract's PRNG seed, the block and the sender. Returns the
+/// code and the seed to store for the next one
+fn generate_invite(env: &Env, seed: &[u8]) -> (String, [u8; 32]) {
+    let mut preimage = seed.to_vec();
+    preimage.extend_from_slice(&env.block.height.to_be_bytes());
+    preimage.extend_from_slice(&env.block.time.to_be_bytes());
+    preimage.extend_from_slice(env.message.sender.0.as_bytes());
+    let next_seed = sha_256(&preimage);
+    // the code is hashed again so it reveals nothing about the seed
+    let hash = sha_256(&[&next_seed[..], b"invite"].concat());
+    let hex: String = hash[..12].iter().map(|b| format!("{:02x}", b)).collect();
+    (format!("{}{}", GENERATED_INVITE_PREFIX, hex), next_seed)
+}
+
+/// Mints a single-use invite code for the sender's inbox
+pub fn try_create_invite<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut invite: Option<String> = None;
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut codes = read_invite_index(&deps.storage, &sender_address_raw)?;
+    if codes.len() >= MAX_INVITES {
+        status = Failure;
+        code = CODE_LIMIT_REACHED;
+        response_message.push_str(&format!("You already have {} unredeemed invite codes.", MAX_INVITES));
+    } else {
+        let (mut new_code, mut next_seed) = generate_invite(&env, &read_prng_seed(&deps.storage)?);
+        while read_invite(&deps.storage, &new_code)?.is_some() {
+            let (retry, retry_seed) = generate_invite(&env, &next_seed);
+            new_code = retry;
+            next_seed = retry_seed;
+        }
+        write_prng_seed(&mut deps.storage, &next_seed)?;
+        let record = Invite { owner: sender_address_raw.clone() };
+        save(&mut PrefixedStorage::new(INVITE_PREFIX, &mut deps.storage), new_code.as_bytes(), &record)?;
+        codes.push(new_code.clone());
+        write_invite_index(&mut deps.storage, &sender_address_raw, &codes)?;
+        invite = Some(new_code);
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Invite code created.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateInvite {
+            status,
+            message: response_message,
+            code,
+            invite,
+        })?),
+    })
+}
+
+/// Revokes one of the sender's unredeemed invite codes
+pub fn try_revoke_invite<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    invite: String,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    if invite_valid(&deps.storage, &sender_address_raw, Some(&invite))? {
+        redeem_invite(&mut deps.storage, &invite, None)?;
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Invite code revoked.");
+    } else {
+        status = Failure;
+        code = CODE_NOT_FOUND;
+        response_message.push_str("Invite code not found.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RevokeInvite {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+/// Turns invite-only mode of the sender's inbox on or off
+pub fn try_set_invite_only<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    enabled: bool,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus = Success;
+    let code: u16 = CODE_OK;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let mut invite_only_storage = PrefixedStorage::new(INVITE_ONLY_PREFIX, &mut deps.storage);
+    if enabled {
+        invite_only_storage.set(sender_address_raw.as_slice(), &[1]);
+        response_message.push_str("Only contacts and holders of an invite code can send to you.");
+    } else {
+        invite_only_storage.remove(sender_address_raw.as_slice());
+        response_message.push_str("Invite codes are no longer required.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetInviteOnly {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn query_invites<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+
+    to_binary(&QueryAnswer::Invites {
+        invite_only: is_invite_only(&deps.storage, &address_raw),
+        invites: read_invite_index(&deps.storage, &address_raw)?,
+    })
+}
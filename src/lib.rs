@@ -1,6 +1,21 @@
+pub mod aliases;
+pub mod cipher;
 pub mod contract;
+pub mod gates;
+pub mod invites;
+pub mod merkle;
+pub mod migrations;
 pub mod msg;
+pub mod mutes;
+pub mod names;
+pub mod notes;
+pub mod polls;
+pub mod profile;
+pub mod queue;
+pub mod receipt_nft;
+pub mod receiver;
 pub mod state;
+pub mod viewing_key;
 
 #[cfg(target_arch = "wasm32")]
 mod wasm {
@@ -0,0 +1,104 @@
+use secret_toolkit::crypto::sha_256;
+
+/// Hashes a receipt into a leaf, domain separated from inner nodes
+pub fn leaf_hash(receipt: &[u8]) -> [u8; 32] {
+    let mut preimage = vec![0u8];
+    preimage.extend_from_slice(receipt);
+    sha_256(&preimage)
+}
+
+fn node_hash(left: &[u8], right: &[u8]) -> [u8; 32] {
+    let mut preimage = vec![1u8];
+    preimage.extend_from_slice(left);
+    preimage.extend_from_slice(right);
+    sha_256(&preimage)
+}
+
+// pairs up the nodes of a level, an odd node at the end is promoted unchanged
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| if pair.len() == 2 { node_hash(&pair[0], &pair[1]) } else { pair[0] })
+        .collect()
+}
+
+/// Returns the root of the tree over the leaf hashes, all zeros for an empty tree
+pub fn merkle_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    if leaves.is_empty() {
+        return [0u8; 32];
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level[0]
+}
+
+/// Returns the siblings on the path from leaf `index` to the root, from the bottom up, each with
+/// true if it is the left input of the parent. Levels where the node was promoted have no sibling.
+pub fn merkle_proof(leaves: &[[u8; 32]], mut index: usize) -> Vec<([u8; 32], bool)> {
+    let mut proof = vec![];
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        let sibling = index ^ 1;
+        if sibling < level.len() {
+            proof.push((level[sibling], sibling < index));
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+    proof
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(count: u8) -> Vec<[u8; 32]> {
+        (0..count).map(|i| leaf_hash(&[i])).collect()
+    }
+
+    // folds a proof the way an off-chain verifier would
+    fn root_from_proof(leaf: [u8; 32], proof: &[([u8; 32], bool)]) -> [u8; 32] {
+        proof.iter().fold(leaf, |node, (sibling, is_left)| {
+            if *is_left { node_hash(sibling, &node) } else { node_hash(&node, sibling) }
+        })
+    }
+
+    #[test]
+    fn empty_and_single_leaf() {
+        assert_eq!(merkle_root(&[]), [0u8; 32]);
+
+        let leaves = leaves(1);
+        assert_eq!(merkle_root(&leaves), leaves[0]);
+        assert!(merkle_proof(&leaves, 0).is_empty());
+    }
+
+    #[test]
+    fn proofs_round_trip() {
+        for count in 2..=9 {
+            let leaves = leaves(count);
+            let root = merkle_root(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = merkle_proof(&leaves, index);
+                assert_eq!(root_from_proof(*leaf, &proof), root, "{} leaves, leaf {}", count, index);
+            }
+        }
+    }
+
+    #[test]
+    fn odd_leaf_is_promoted() {
+        let leaves = leaves(3);
+        let root = node_hash(&node_hash(&leaves[0], &leaves[1]), &leaves[2]);
+        assert_eq!(merkle_root(&leaves), root);
+        // the last leaf has no sibling on the bottom level
+        assert_eq!(merkle_proof(&leaves, 2), vec![(node_hash(&leaves[0], &leaves[1]), true)]);
+    }
+
+    #[test]
+    fn proof_fails_for_other_leaf() {
+        let leaves = leaves(5);
+        let proof = merkle_proof(&leaves, 1);
+        assert_ne!(root_from_proof(leaves[2], &proof), merkle_root(&leaves));
+    }
+}
@@ -0,0 +1,24 @@
+use cosmwasm_std::{StdError, StdResult};
+
+use crate::state::{Format, STATE_VERSION};
+
+/// Turns the payload of a record of one version into the payload of the next version. Gets the
+/// name of the stored type so it can leave the payloads of types it does not change untouched.
+pub type Upgrade = fn(type_name: &str, format: Format, payload: Vec<u8>) -> StdResult<Vec<u8>>;
+
+/// upgrades in order, `UPGRADES[v - 1]` turns a record of version `v` into version `v + 1`.
+/// Bumping STATE_VERSION needs a matching upgrade here.
+static UPGRADES: &[Upgrade] = &[];
+
+/// Brings the payload of a record of `version` up to STATE_VERSION. Records are upgraded when
+/// they are read and written back in the current version the next time they are saved, so a
+/// new version never needs a migration over all of storage.
+pub fn upgrade_record(type_name: &str, format: Format, version: u8, mut payload: Vec<u8>) -> StdResult<Vec<u8>> {
+    for from in version.max(1)..STATE_VERSION {
+        let upgrade = UPGRADES.get(from as usize - 1).ok_or_else(|| {
+            StdError::generic_err(format!("No upgrade of {} records from version {}.", type_name, from))
+        })?;
+        payload = upgrade(type_name, format, payload)?;
+    }
+    Ok(payload)
+}
@@ -1,6 +1,11 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use cosmwasm_std::{HumanAddr, Uint128};
+use cosmwasm_std::{Binary, Coin, HumanAddr, Uint128};
+
+use crate::profile::ProfileVisibility;
+use crate::queue::EvictionPolicy;
+use crate::state::{ConfigChange, ContractStatus, CountFuzz, DeadLetterReason, Fee, PayloadValidation, QuietWindow, SendFee, SenderFilter, SessionPermission};
+use crate::viewing_key::ViewingKey;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InitMsg {
@@ -11,8 +16,220 @@ pub struct InitMsg {
     /// maximum size of a message in bytes
     pub max_message_size: i32,
     /// if discard true, will not push messages to a full queue,
-    /// else will dequeue oldest message to make room
+    /// else will dequeue oldest message to make room, ignored if eviction_policy is set
     pub discard: bool,
+    /// what a full queue does when a new message arrives, defaults to reject if discard is true
+    /// and to drop_oldest otherwise
+    pub eviction_policy: Option<EvictionPolicy>,
+    /// random string mixed into the contract secret that keys message ids, unread count noise,
+    /// pseudonyms and generated viewing keys
+    pub entropy: String,
+    /// maximum number of items kept in each sender's sent items folder,
+    /// if missing or 0 the folder is disabled
+    pub max_sent_items: Option<i32>,
+    /// admin address, defaults to the address instantiating the contract
+    pub admin: Option<HumanAddr>,
+    /// sizes in bytes that stored message content is padded to, e.g. [256, 1024, 4096],
+    /// if missing content is stored unpadded
+    pub padding_buckets: Option<Vec<u32>>,
+    /// if set, the unread counts returned by recv and size are rounded up to a bucket or
+    /// have bounded noise added, if missing they are exact
+    pub unread_count_fuzz: Option<CountFuzz>,
+    /// if set, messages can be sent by sending at least `amount` (plus `per_byte` for every
+    /// byte of content) of this SNIP-20 token to the contract
+    pub postage: Option<PostageInfo>,
+    /// if set, direct sends have to attach `base + per_byte * content size` of a native coin,
+    /// or the amount of the smallest of its `tiers` the content fits in
+    pub send_fee: Option<SendFeeInfo>,
+    /// minimum number of blocks between proposing and executing a config change,
+    /// defaults to about a day
+    pub config_change_delay: Option<u64>,
+    /// refuse messages from contracts (humans_only) or from anyone else (contracts_only),
+    /// defaults to any
+    pub sender_filter: Option<SenderFilter>,
+    /// if true, every handle emits an `activity` log attribute for indexers, defaults to false
+    pub activity_logs: Option<bool>,
+    /// if set, registering a name costs this amount of a native coin
+    pub name_fee: Option<FeeInfo>,
+    /// number of blocks after which messages sent without `expire_after_blocks` expire,
+    /// 0 or missing keeps them until they are read
+    pub default_ttl_blocks: Option<u64>,
+    /// what content is accepted: utf8 text (default), json, or raw bytes passed as base64
+    pub payload_validation: Option<PayloadValidation>,
+    /// maximum number of unread messages one sender can have in a recipient's queue,
+    /// 0 or missing means no limit beyond max_messages
+    pub max_messages_per_sender: Option<u32>,
+    /// if true, a send refused because the recipient blocked or filters out the sender fails
+    /// exactly like a send to a full queue, defaults to false
+    pub uniform_failures: Option<bool>,
+    /// if set, senders can ask for a read receipt minted on this SNIP-721 contract, which has
+    /// to accept this contract as a minter
+    pub receipt_nft: Option<ReceiptNftInfo>,
+    /// if set, sends must carry a nonce whose proof-of-work hash starts with this many zero bits,
+    /// at most 32
+    pub pow_difficulty: Option<u8>,
+}
+
+/// SNIP-721 contract read receipts are minted on
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiptNftInfo {
+    pub contract: HumanAddr,
+    pub code_hash: String,
+}
+
+/// SNIP-20 token and minimum amount paid for a message delivered through the token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PostageInfo {
+    pub token: HumanAddr,
+    pub token_code_hash: String,
+    pub amount: Uint128,
+    /// added to `amount` for every byte of content
+    #[serde(default)]
+    pub per_byte: Uint128,
+}
+
+/// native coin postage charged for direct sends
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SendFeeInfo {
+    pub denom: String,
+    pub base: Uint128,
+    pub per_byte: Uint128,
+    /// flat amounts for small content, replacing `base + per_byte * size` up to their max_size
+    #[serde(default)]
+    pub tiers: Vec<FeeTierInfo>,
+}
+
+/// flat postage for content of at most `max_size` bytes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeTierInfo {
+    pub max_size: u32,
+    pub amount: Uint128,
+}
+
+/// SNIP-20 token senders other than contacts must hold at least `min_balance` of
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TokenGateInfo {
+    pub contract: HumanAddr,
+    pub code_hash: String,
+    pub min_balance: Uint128,
+}
+
+/// SNIP-721 collection senders other than contacts must own a token of
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NftGateInfo {
+    pub contract: HumanAddr,
+    pub code_hash: String,
+}
+
+/// SNIP-24 query permit, forwarded unchanged to the token it was signed for
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TokenPermit {
+    pub params: TokenPermitParams,
+    pub signature: TokenPermitSignature,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TokenPermitParams {
+    pub permit_name: String,
+    pub allowed_tokens: Vec<HumanAddr>,
+    pub chain_id: String,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TokenPermitSignature {
+    pub pub_key: TokenPermitPubKey,
+    pub signature: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TokenPermitPubKey {
+    /// always "tendermint/PubKeySecp256k1"
+    pub r#type: String,
+    pub value: Binary,
+}
+
+/// amount of a native coin or of the contract's postage token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeInfo {
+    Native { denom: String, amount: Uint128 },
+    Token { amount: Uint128 },
+}
+
+impl From<FeeInfo> for Fee {
+    fn from(info: FeeInfo) -> Self {
+        match info {
+            FeeInfo::Native { denom, amount } => Fee::Native { denom, amount: amount.u128() },
+            FeeInfo::Token { amount } => Fee::Token { amount: amount.u128() },
+        }
+    }
+}
+
+impl From<Fee> for FeeInfo {
+    fn from(fee: Fee) -> Self {
+        match fee {
+            Fee::Native { denom, amount } => FeeInfo::Native { denom, amount: Uint128(amount) },
+            Fee::Token { amount } => FeeInfo::Token { amount: Uint128(amount) },
+        }
+    }
+}
+
+impl From<SendFee> for SendFeeInfo {
+    fn from(send_fee: SendFee) -> Self {
+        SendFeeInfo {
+            denom: send_fee.denom,
+            base: Uint128(send_fee.base),
+            per_byte: Uint128(send_fee.per_byte),
+            tiers: send_fee
+                .tiers
+                .into_iter()
+                .map(|tier| FeeTierInfo { max_size: tier.max_size, amount: Uint128(tier.amount) })
+                .collect(),
+        }
+    }
+}
+
+/// deployment parameters an admin proposal can change, missing fields keep their current value
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct ConfigChangeInfo {
+    pub max_messages: Option<u32>,
+    pub max_message_size: Option<u16>,
+    pub eviction_policy: Option<EvictionPolicy>,
+    pub max_sent_items: Option<u32>,
+    pub padding_buckets: Option<Vec<u32>>,
+    pub send_fee: Option<SendFeeInfo>,
+    pub config_change_delay: Option<u64>,
+    pub sender_filter: Option<SenderFilter>,
+    pub activity_logs: Option<bool>,
+    pub name_fee: Option<FeeInfo>,
+    pub default_ttl_blocks: Option<u64>,
+    pub payload_validation: Option<PayloadValidation>,
+    pub max_messages_per_sender: Option<u32>,
+    pub uniform_failures: Option<bool>,
+    pub pow_difficulty: Option<u8>,
+}
+
+impl From<ConfigChange> for ConfigChangeInfo {
+    fn from(change: ConfigChange) -> Self {
+        ConfigChangeInfo {
+            max_messages: change.max_messages,
+            max_message_size: change.max_message_size,
+            eviction_policy: change.eviction_policy,
+            max_sent_items: change.max_sent_items,
+            padding_buckets: change.padding_buckets,
+            send_fee: change.send_fee.map(SendFeeInfo::from),
+            config_change_delay: change.config_change_delay,
+            sender_filter: change.sender_filter,
+            activity_logs: change.activity_logs,
+            name_fee: change.name_fee.map(FeeInfo::from),
+            default_ttl_blocks: change.default_ttl_blocks,
+            payload_validation: change.payload_validation,
+            max_messages_per_sender: change.max_messages_per_sender,
+            uniform_failures: change.uniform_failures,
+            pow_difficulty: change.pow_difficulty,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -21,8 +238,140 @@ pub enum HandleMsg {
     Send {
         content: String,
         target: HumanAddr,
+        /// optional priority shown in previews, defaults to 0
+        priority: Option<u8>,
+        /// optional content type (e.g. a mime type) of at most 64 bytes
+        content_type: Option<String>,
+        /// if set, the message is not delivered until the chain passes this height
+        deliver_after_height: Option<u64>,
+        /// if set, the message is announced right away but its content is not
+        /// revealed until the chain passes this height
+        reveal_after_height: Option<u64>,
+        /// if set, the message is deleted unread once this many blocks have passed
+        expire_after_blocks: Option<u64>,
+        /// if set, the message is held back as a dead man's switch and only delivered
+        /// once the sender has not sent a heartbeat for this many blocks
+        release_if_inactive_blocks: Option<u64>,
+        /// optional labels of at most 32 bytes each, at most 4
+        tags: Option<Vec<String>>,
+        /// if set, a public receipt of the message is stored, salted with these (at least 16) bytes
+        receipt_salt: Option<Binary>,
+        /// for notes to self (target is the sender): if true, reading the note does not delete it
+        keep_after_read: Option<bool>,
+        /// if true, the recipient does not learn the sender's address and gets a single-use
+        /// reply token instead
+        anonymous: Option<bool>,
+        /// number of reads before the message explodes, 1 if missing
+        max_views: Option<u32>,
+        /// if true, the content stays sealed until reveal_after_height, which is required, and
+        /// the sender can no longer edit it
+        time_capsule: Option<bool>,
+        /// if true, the recipient has to sign the message id with their signing key to read it,
+        /// and the sender can query the signature
+        require_read_signature: Option<bool>,
+        /// if true, the sender gets a read receipt NFT when the message explodes
+        mint_read_receipt: Option<bool>,
+        /// permit for the recipient's gate token, signed with the sender's signing key
+        token_permit: Option<TokenPermit>,
+        /// permit for the recipient's gate collection, letting the contract see the sender's tokens
+        nft_permit: Option<TokenPermit>,
+        /// proof-of-work nonce, required if the deployment sets pow_difficulty
+        pow_nonce: Option<u64>,
+        /// invite code of the recipient, required for a first message to an invite-only inbox
+        invite_code: Option<String>,
+    },
+    Recv {
+        /// if set, the message is taken from this folder instead of the inbox
+        folder: Option<String>,
+        /// signature over the id of the next message, for messages that require one
+        read_signature: Option<Binary>,
+        /// if true, the sender of the message read is added to the caller's blocklist
+        block_sender: Option<bool>,
+    },
+    /// creates a folder holding at most `max_messages` messages, max_messages of the deployment
+    /// if missing
+    CreateFolder {
+        name: String,
+        max_messages: Option<u32>,
+    },
+    /// deletes an empty folder
+    DeleteFolder {
+        name: String,
+    },
+    /// moves an unread message between the inbox (no folder) and the sender's folders
+    MoveMessage {
+        message_id: Uint128,
+        from: Option<String>,
+        to: Option<String>,
+    },
+    /// delivers a poll to up to max_poll_targets addresses, of which only the tallies are kept
+    SendPoll {
+        question: String,
+        options: Vec<String>,
+        targets: Vec<HumanAddr>,
+    },
+    /// answers the poll delivered as `message_id` with the index of one of its options
+    Vote {
+        message_id: Uint128,
+        option: u32,
+    },
+    /// mints a disposable alias that delivers to the sender's inbox, for `ttl` blocks if set
+    CreateAlias {
+        ttl: Option<u64>,
+    },
+    /// burns one of the sender's aliases, so it no longer delivers
+    BurnAlias {
+        alias: String,
+    },
+    /// mints a single-use code that lets a stranger send a first message to the caller
+    CreateInvite { },
+    /// revokes one of the caller's unredeemed invite codes
+    RevokeInvite {
+        invite: String,
+    },
+    /// if enabled, only contacts and holders of an invite code can send to the caller
+    SetInviteOnly {
+        enabled: bool,
+    },
+    /// sends a message to the owner of an alias, tagged with the alias
+    SendToAlias {
+        alias: String,
+        content: String,
+        /// proof-of-work nonce, computed over the alias instead of the target address
+        pow_nonce: Option<u64>,
+    },
+    /// answers the sender of an anonymous message, using up the reply token recv returned with it
+    ReplyAnonymous {
+        token: Binary,
+        content: String,
+    },
+    /// moves an unread message from the inbox to the archive, where it expires after
+    /// archive_ttl_blocks unless it is read first
+    Archive {
+        message_id: Uint128,
+    },
+    /// reads a note to self, the oldest one if no id is given, deleting it unless it is kept
+    ReadNote {
+        note_id: Option<u32>,
+    },
+    /// deletes a note to self
+    DeleteNote {
+        note_id: u32,
+    },
+    /// receive the oldest message carrying `tag`
+    RecvByTag {
+        tag: String,
+        /// signature over the id of the next message, for messages that require one
+        read_signature: Option<Binary>,
+        /// if true, the sender of the message read is added to the caller's blocklist
+        block_sender: Option<bool>,
+    },
+    /// receive the oldest message kept after it was evicted or expired unread
+    RecvDeadLetter { },
+    /// keeps the last few messages evicted from or expiring in the sender's queue as dead letters
+    SetDeadLetters {
+        enabled: bool,
     },
-    Recv { },
     Size { },
     Block {
         address: HumanAddr,
@@ -30,73 +379,2146 @@ pub enum HandleMsg {
     Unblock {
         address: HumanAddr,
     },
+    /// blocks the sender of a message in the caller's inbox or one of the recently read ones
+    BlockSenderOf {
+        message_id: Uint128,
+    },
+    /// silently drops the address's messages to the caller, while the address is told they were
+    /// delivered
+    Mute {
+        address: HumanAddr,
+    },
+    Unmute {
+        address: HumanAddr,
+    },
+    /// price senders other than contacts pay to send to the caller, None makes the inbox free
+    SetInboxPrice {
+        price: Option<FeeInfo>,
+    },
+    /// deposit senders other than contacts attach to messages to the caller, refunded when the
+    /// message is read. Replaces the inbox price. None removes the requirement
+    SetSpamBond {
+        bond: Option<FeeInfo>,
+    },
+    /// deletes an unread message and keeps its sender's bond
+    ReportSpam {
+        message_id: Uint128,
+    },
+    /// refunds the caller's bond for a message that was deleted without being read or reported, or
+    /// the price of a message the recipient cleared
+    ClaimBond {
+        message_id: Uint128,
+    },
+    /// exempts the address from the caller's inbox price and spam bond
+    AddContact {
+        address: HumanAddr,
+    },
+    RemoveContact {
+        address: HumanAddr,
+    },
+    /// automatically blocks senders once `after` of their messages were evicted unread or reported
+    /// as spam, 0 disables
+    SetAutoBlock {
+        after: u32,
+    },
+    /// refuses messages from senders known to be contracts, or from all other senders
+    SetSenderFilter {
+        filter: SenderFilter,
+    },
+    /// requires senders other than contacts to hold a minimum balance of a SNIP-20 token, None
+    /// removes the requirement
+    SetTokenGate {
+        gate: Option<TokenGateInfo>,
+    },
+    /// requires senders other than contacts to own a token of a SNIP-721 collection, None removes
+    /// the requirement
+    SetNftGate {
+        gate: Option<NftGateInfo>,
+    },
+    /// requires senders other than contacts to have sent their first message at least `blocks`
+    /// blocks ago, 0 removes the requirement
+    SetMinSenderAge {
+        blocks: u64,
+    },
+    /// sets a short status and an away flag, shown to senders in their send responses if shared
+    SetStatus {
+        text: Option<String>,
+        away: bool,
+        share: bool,
+    },
+    /// refuses or defers new messages while `window` is active, no window ends quiet hours
+    SetQuietHours {
+        window: Option<QuietWindow>,
+        defer: bool,
+    },
+    /// asks for a notice whenever a message of the sender is evicted unread from a full queue
+    SetEvictionNotices {
+        enabled: bool,
+    },
+    /// asks for a notice whenever a send of the sender is dropped because the queue is full
+    SetBounceNotices {
+        enabled: bool,
+    },
+    /// blocks every address in the list, for example one exported from another wallet or instance
+    ImportBlocklist {
+        addresses: Vec<HumanAddr>,
+    },
+    /// sets the viewing key with the given label, the unlabeled key if there is no label
+    SetViewingKey {
+        key: String,
+        label: Option<String>,
+        /// if set, the key no longer authenticates once the chain passes this height
+        expire_after_height: Option<u64>,
+    },
+    /// generates a viewing key for the sender from the contract's seed and `entropy`
+    CreateViewingKey {
+        entropy: String,
+        label: Option<String>,
+        /// if set, the key no longer authenticates once the chain passes this height
+        expire_after_height: Option<u64>,
+    },
+    /// deletes the viewing key with the given label, the unlabeled key if there is no label
+    RevokeViewingKey {
+        label: Option<String>,
+    },
+    /// moves the sender's queued content to a fresh storage key, re-encrypting each message
+    /// the next time it is read
+    RotateStorageKey { },
+    ReadSent { },
+    ClearSent { },
+    React {
+        message_id: Uint128,
+        reaction: String,
+    },
+    Edit {
+        message_id: Uint128,
+        new_content: String,
+    },
+    /// hides a message in the sender's queue until the chain passes `until_height`, then
+    /// surfaces it at the front of the queue
+    Snooze {
+        message_id: Uint128,
+        until_height: u64,
+    },
+    /// moves a message in the sender's queue to the front, so the next recv returns it
+    MoveToFront {
+        message_id: Uint128,
+    },
+    /// keeps a message in the sender's queue when it is full or cleared
+    Pin {
+        message_id: Uint128,
+    },
+    Unpin {
+        message_id: Uint128,
+    },
+    /// flags a message of the sender, in the inbox or a folder, exempting it from the default TTL
+    Flag {
+        message_id: Uint128,
+    },
+    Unflag {
+        message_id: Uint128,
+    },
+    /// deletes the messages in the sender's queue without reading them, pinned ones only if
+    /// `force` is set
+    ClearAll {
+        force: bool,
+    },
+    Heartbeat { },
+    CommitSend {
+        /// sha256 hash of the content length as 8 big-endian bytes, followed by the utf-8 content
+        /// bytes and the utf-8 nonce bytes
+        content_hash: Binary,
+        target: HumanAddr,
+    },
+    RevealSend {
+        content: String,
+        nonce: String,
+    },
+    SendChunk {
+        /// sender-chosen id shared by all chunks of the message
+        transfer_id: u64,
+        target: HumanAddr,
+        /// position of this chunk, starting at 0
+        index: u32,
+        /// number of chunks in the message
+        total: u32,
+        data: String,
+        /// proof-of-work nonce over the assembled content, checked by the chunk completing it
+        pow_nonce: Option<u64>,
+    },
+    RecvChunk {
+        message_id: Uint128,
+        /// byte offset into the content
+        offset: u32,
+        /// number of bytes to read, capped at max_message_size
+        len: u32,
+    },
+    AckRecv {
+        message_id: Uint128,
+    },
+    Prune {
+        /// maximum number of messages to delete
+        limit: u32,
+    },
+    RegisterReceive {
+        /// code hash of the calling contract, used to notify it of new messages
+        code_hash: String,
+    },
+    RegisterReadCallback {
+        /// code hash of the calling contract, used to notify it when its messages are read
+        code_hash: String,
+    },
+    GrantQuery {
+        /// contract allowed to run delegated queries
+        querier: HumanAddr,
+        /// key the querier has to present
+        key: String,
+    },
+    RevokeQuery {
+        querier: HumanAddr,
+    },
+    Batch {
+        /// operations run in order in a single transaction
+        ops: Vec<BatchOp>,
+    },
+    SetSigningKey {
+        /// compressed or uncompressed secp256k1 public key used to sign meta-transactions
+        pubkey: Binary,
+    },
+    SendSigned {
+        /// json of a SignedSend, exactly as signed
+        payload: Binary,
+        /// 64 byte secp256k1 signature of the sha256 hash of the payload
+        signature: Binary,
+        /// public key registered by the sender with set_signing_key
+        pubkey: Binary,
+    },
+    GrantSession {
+        /// secp256k1 public key of the session
+        pubkey: Binary,
+        /// block height after which the session is no longer valid
+        expires: u64,
+        permissions: Vec<SessionPermission>,
+    },
+    RevokeSession {
+        pubkey: Binary,
+    },
+    /// stops accepting the sender's query permits with the given name, for good
+    RevokePermit {
+        permit_name: String,
+    },
+    /// stops accepting every query permit the sender signed so far by moving to a new epoch
+    RevokeAllPermits { },
+    SessionCall {
+        /// json of a SessionPayload, exactly as signed
+        payload: Binary,
+        /// 64 byte secp256k1 signature of the sha256 hash of the payload
+        signature: Binary,
+        /// public key of the session
+        pubkey: Binary,
+    },
+    ChangeAdmin {
+        address: HumanAddr,
+    },
+    /// permanently removes the admin, locking the configuration
+    RenounceAdmin { },
+    /// admin only, switches between normal operation and read-only maintenance mode
+    SetContractStatus {
+        status: ContractStatus,
+    },
+    /// admin only, pays out collected postage
+    Withdraw {
+        amount: FeeInfo,
+        to: HumanAddr,
+    },
+    /// admin only, deletes the messages in an address's queue
+    PurgeQueue {
+        address: HumanAddr,
+    },
+    /// admin only, deletes the messages in every queue, starting over where the last call stopped
+    PurgeAll {
+        /// maximum number of messages to delete
+        limit: u32,
+    },
+    /// admin only, schedules a config change that can be executed once the config change delay passed,
+    /// replaces any pending change
+    ProposeConfigChange {
+        change: ConfigChangeInfo,
+    },
+    /// closes the open receipt epoch once it is due, so its Merkle root can be queried
+    CloseReceiptEpoch { },
+    /// registers a name others can resolve to the sender's address, replacing the sender's
+    /// current name
+    RegisterName {
+        name: String,
+    },
+    /// hands the sender's name to an address that has none
+    TransferName {
+        to: HumanAddr,
+    },
+    /// frees the sender's name for anyone to register
+    ReleaseName { },
+    /// replaces the sender's profile, leaving out every field removes it
+    SetProfile {
+        display_name: Option<String>,
+        /// sha256 hash of the avatar image
+        avatar_hash: Option<Binary>,
+        bio: Option<String>,
+        visibility: ProfileVisibility,
+    },
+    /// admin only, marks addresses as contracts for sender filtering
+    RegisterContracts {
+        addresses: Vec<HumanAddr>,
+    },
+    /// admin only, removes the contract mark from addresses
+    DeregisterContracts {
+        addresses: Vec<HumanAddr>,
+    },
+    /// admin only, sanctions a relay contract with the given code hash to send on behalf of
+    /// other addresses, replacing an earlier registration of the same address
+    RegisterRelay {
+        address: HumanAddr,
+        code_hash: String,
+    },
+    /// admin only, withdraws the sanction of a relay contract
+    DeregisterRelay {
+        address: HumanAddr,
+    },
+    /// relay only, sends `content` to `target` on behalf of `origin`, who is hidden from the
+    /// recipient if origin_hidden is true
+    RelaySend {
+        origin: HumanAddr,
+        target: HumanAddr,
+        content: String,
+        origin_hidden: bool,
+        /// proof-of-work nonce, computed for the origin as sender
+        pow_nonce: Option<u64>,
+    },
+    /// admin only, applies the pending config change
+    ExecuteConfigChange { },
+    /// admin only, drops the pending config change
+    CancelConfigChange { },
+    /// SNIP-20 receiver interface, called by the postage token
+    Receive {
+        sender: HumanAddr,
+        from: HumanAddr,
+        amount: Uint128,
+        msg: Option<Binary>,
+    },
 }
 
+/// send signed offline by `sender` and submitted by a relayer
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-#[serde(rename_all = "snake_case")]
-pub enum QueryMsg {
-    // ping
-    Ping {},
+pub struct SignedSend {
+    pub sender: HumanAddr,
+    pub target: HumanAddr,
+    pub content: String,
+    /// must be greater than the last nonce the sender used
+    pub nonce: u64,
+    /// block height after which the signature is no longer accepted
+    pub expires: u64,
+    /// address of this contract, so the signature cannot be replayed elsewhere
+    pub contract: HumanAddr,
 }
 
+/// query permit signed offline by `address`
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct PingResponse {
-    pub response: String,
+pub struct PermitParams {
+    /// name the signer can revoke the permit by
+    pub permit_name: String,
+    /// address whose data the permit gives access to
+    pub address: HumanAddr,
+    /// permit epoch of the address when it signed, the permit stops working once it changes
+    pub epoch: u32,
+    /// address of this contract, so the permit cannot be used elsewhere
+    pub contract: HumanAddr,
 }
 
-/// success or failure response
-#[derive(Serialize, Deserialize, Debug, JsonSchema)]
-pub enum ResponseStatus {
-    Success,
-    Failure,
+/// action signed with a session key on behalf of `grantor`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SessionPayload {
+    pub grantor: HumanAddr,
+    pub op: SessionOp,
+    /// must be greater than the last nonce the grantor used, shared with signed sends
+    pub nonce: u64,
+    /// address of this contract, so the signature cannot be replayed elsewhere
+    pub contract: HumanAddr,
 }
 
-/// Responses from handle functions
-#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+/// actions that can be taken with a session key
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub enum HandleAnswer {
-    /// response from send attempt
+pub enum SessionOp {
     Send {
-        /// success or failure
-        status: ResponseStatus,
-        /// execution description
-        message: String,
-    },
-    /// response from receive attempt
-    Recv {
-        /// success or failure
-        status: ResponseStatus,
-        /// execution description
-        message: String,
-        /// number of unread messages
-        number_of_unread_messages: u32,
-        /// content of message
-        content: Option<String>,
-        /// sender of message
-        sender: Option<HumanAddr>,
+        target: HumanAddr,
+        content: String,
     },
-    /// response from size of message box attempt
-    Size {
-        /// success or failure
-        status: ResponseStatus,
-        /// execution description
-        message: String,
-        /// number of unread messages
-        number_of_unread_messages: u32,
+    Recv { },
+}
+
+/// operations that can be combined in a batch, with the same parameters as the requests
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchOp {
+    Send {
+        content: String,
+        target: HumanAddr,
     },
-    /// block response
+    Recv { },
+    Size { },
     Block {
-        /// success or failure
-        status: ResponseStatus,
-        /// execution description
-        message: String,
+        address: HumanAddr,
     },
-    /// unblock response
     Unblock {
+        address: HumanAddr,
+    },
+    React {
+        message_id: Uint128,
+        reaction: String,
+    },
+    AckRecv {
+        message_id: Uint128,
+    },
+    Heartbeat { },
+}
+
+/// message embedded in a SNIP-20 send to this contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    /// send `content` to `target` on behalf of the token sender
+    Deliver {
+        target: HumanAddr,
+        content: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    // ping
+    Ping {},
+    // deployment parameters
+    Config {},
+    // relay contracts sanctioned by the admin
+    Relays {},
+    // config change proposed by the admin and not yet executed
+    PendingConfigChange {},
+    // when the message with this receipt hash was sent
+    Receipt {
+        receipt: Binary,
+    },
+    // Merkle root of the receipts of a closed epoch
+    ReceiptRoot {
+        epoch: u64,
+    },
+    // address that registered the name
+    ResolveName {
+        name: String,
+    },
+    // name registered by the address
+    NameOf {
+        address: HumanAddr,
+    },
+    // profile of the owner, if it is public
+    Profile {
+        owner: HumanAddr,
+    },
+    // profile of the owner, if `address` may view it
+    ViewProfile {
+        address: HumanAddr,
+        key: String,
+        owner: HumanAddr,
+    },
+    // postage and spam bond a sender who is not a contact attaches to send to the address
+    InboxPrice {
+        address: HumanAddr,
+    },
+    // token a sender who is not a contact must hold to send to the address
+    TokenGate {
+        address: HumanAddr,
+    },
+    // collection a sender who is not a contact must own a token of to send to the address
+    NftGate {
+        address: HumanAddr,
+    },
+    // number of blocks a sender who is not a contact must have used the contract for to send to
+    // the address
+    MinSenderAge {
+        address: HumanAddr,
+    },
+    // metadata of the queued messages, without content
+    Preview {
+        address: HumanAddr,
+        key: String,
+        /// if set, only messages carrying this tag are listed
+        tag: Option<String>,
+        /// if set, the messages of this folder are listed instead of the inbox
+        folder: Option<String>,
+    },
+    // folders with their caps and number of messages
+    Folders {
+        address: HumanAddr,
+        key: String,
+    },
+    // ids and snippets of the queued messages whose content contains `query`, ignoring ascii case
+    SearchInbox {
+        address: HumanAddr,
+        key: String,
+        query: String,
+        /// maximum number of matches returned, max_search_results if missing
+        limit: Option<u32>,
+    },
+    // signature of the recipient of a message the address sent with require_read_signature
+    ReadProof {
+        address: HumanAddr,
+        key: String,
+        message_id: Uint128,
+    },
+    // live aliases of the address
+    Aliases {
+        address: HumanAddr,
+        key: String,
+    },
+    // unredeemed invite codes of the address
+    Invites {
+        address: HumanAddr,
+        key: String,
+    },
+    // vote counts of a poll the address sent
+    PollResults {
+        address: HumanAddr,
+        key: String,
+        poll_id: u64,
+    },
+    // metadata of the flagged messages in the inbox and folders, in the order they were flagged
+    Flagged {
+        address: HumanAddr,
+        key: String,
+    },
+    // unread count and latest timestamp per sender
+    Conversations {
+        address: HumanAddr,
+        key: String,
+    },
+    // notes to self, without their content
+    Notes {
+        address: HumanAddr,
+        key: String,
+    },
+    // unread count per sender only, for badge counts
+    UnreadBySender {
+        address: HumanAddr,
+        key: String,
+    },
+    // labels of the viewing keys of the address
+    ViewingKeyLabels {
+        address: HumanAddr,
+        key: String,
+    },
+    // aggregate counters, admin only
+    Stats {
+        address: HumanAddr,
+        key: String,
+    },
+    // postage collected by the contract, admin only
+    Revenue {
+        address: HumanAddr,
+        key: String,
+    },
+    // one page of the admin audit log, oldest entries first, admin only
+    AuditLog {
+        address: HumanAddr,
+        key: String,
+        page: u32,
+    },
+    // activity counters of the address
+    UserStats {
+        address: HumanAddr,
+        key: String,
+    },
+    // one page of the addresses blocked by the address
+    Blocklist {
+        address: HumanAddr,
+        key: String,
+        page: u32,
+    },
+    // proof that a receipt the address sent or received is included in its epoch's Merkle root
+    ReceiptProof {
+        address: HumanAddr,
+        key: String,
+        receipt: Binary,
+    },
+    // all addresses blocked by the address, in the format accepted by import_blocklist
+    ExportBlocklist {
+        address: HumanAddr,
+        key: String,
+    },
+    // all addresses muted by the address
+    MuteList {
+        address: HumanAddr,
+        key: String,
+    },
+    // dry run of a send from the address, the blocklist of the target is not consulted
+    CanSend {
+        address: HumanAddr,
+        key: String,
+        target: HumanAddr,
+        /// size of the content to send in bytes
+        content_size: u32,
+    },
+    // query on behalf of the address by a contract it granted a key to
+    Delegated {
+        address: HumanAddr,
+        querier: HumanAddr,
+        key: String,
+        query: DelegatedQuery,
+    },
+    // query on behalf of the address authorized with a permit it signed instead of a viewing key
+    WithPermit {
+        /// json of a PermitParams, exactly as signed
+        payload: Binary,
+        /// 64 byte secp256k1 signature of the sha256 hash of the payload
+        signature: Binary,
+        /// public key registered by the address with set_signing_key
+        pubkey: Binary,
+        query: DelegatedQuery,
+    },
+    // permit epoch of the address, to sign new permits with
+    PermitEpoch {
+        address: HumanAddr,
+    },
+}
+
+/// queries a granted contract can run on behalf of an address
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DelegatedQuery {
+    // number of queued messages
+    UnreadCount {},
+    // number of queued messages per sender
+    UnreadBySender {},
+    // a queued message, without exploding it
+    Message {
+        message_id: Uint128,
+    },
+}
+
+impl QueryMsg {
+    /// Returns the addresses and viewing key used to authenticate a query
+    pub fn get_validation_params(&self) -> (Vec<&HumanAddr>, ViewingKey) {
+        match self {
+            Self::Preview { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            Self::Conversations { address, key } => (vec![address], ViewingKey(key.clone())),
+            Self::UnreadBySender { address, key } => (vec![address], ViewingKey(key.clone())),
+            Self::Notes { address, key } => (vec![address], ViewingKey(key.clone())),
+            Self::Folders { address, key } => (vec![address], ViewingKey(key.clone())),
+            Self::Flagged { address, key } => (vec![address], ViewingKey(key.clone())),
+            Self::PollResults { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            Self::Aliases { address, key } => (vec![address], ViewingKey(key.clone())),
+            Self::Invites { address, key } => (vec![address], ViewingKey(key.clone())),
+            Self::ReadProof { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            Self::SearchInbox { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            Self::ViewingKeyLabels { address, key } => (vec![address], ViewingKey(key.clone())),
+            Self::ViewProfile { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            Self::Stats { address, key } => (vec![address], ViewingKey(key.clone())),
+            Self::Revenue { address, key } => (vec![address], ViewingKey(key.clone())),
+            Self::AuditLog { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            Self::UserStats { address, key } => (vec![address], ViewingKey(key.clone())),
+            Self::Blocklist { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            Self::ExportBlocklist { address, key } => (vec![address], ViewingKey(key.clone())),
+            Self::MuteList { address, key } => (vec![address], ViewingKey(key.clone())),
+            Self::ReceiptProof { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            Self::CanSend { address, key, .. } => (vec![address], ViewingKey(key.clone())),
+            _ => panic!("This query type does not require authentication"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PingResponse {
+    pub response: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConfigResponse {
+    /// admin address, None if the admin renounced
+    pub admin: Option<HumanAddr>,
+    /// maximum number of messages per receiver address
+    pub max_messages: u32,
+    /// maximum size of a message in bytes
+    pub max_message_size: u16,
+    /// true if messages to a full queue are refused, kept for clients that predate
+    /// eviction_policy
+    pub discard: bool,
+    /// what a full queue does when a new message arrives
+    pub eviction_policy: EvictionPolicy,
+    /// maximum number of items in each sent items folder, 0 if the folder is disabled
+    pub max_sent_items: u32,
+    /// maximum number of chunks in a chunked send
+    pub max_chunks: u32,
+    /// maximum size of a reaction in bytes
+    pub max_reaction_size: u32,
+    /// maximum size of a content type in bytes
+    pub max_content_type_size: u32,
+    /// maximum number of tags on a message
+    pub max_tags: u32,
+    /// maximum size of a tag in bytes
+    pub max_tag_size: u32,
+    /// maximum size of a status text in bytes
+    pub max_status_size: u32,
+    /// maximum size of a profile display name in bytes
+    pub max_display_name_size: u32,
+    /// maximum size of a profile bio in bytes
+    pub max_bio_size: u32,
+    /// maximum number of pinned messages in a queue
+    pub max_pinned_messages: u32,
+    pub max_notes: u32,
+    pub max_folders: u32,
+    pub max_folder_name_size: u32,
+    /// maximum number of messages in the archive
+    pub max_archived_messages: u32,
+    /// number of blocks an archived message is kept
+    pub archive_ttl_blocks: u64,
+    /// maximum number of matches returned by a search
+    pub max_search_results: u32,
+    /// maximum number of size tiers of the send fee
+    pub max_fee_tiers: u32,
+    pub max_poll_question_size: u32,
+    pub max_poll_options: u32,
+    pub max_poll_option_size: u32,
+    pub max_poll_targets: u32,
+    /// maximum number of live aliases of an address
+    pub max_aliases: u32,
+    /// maximum number of sanctioned relays
+    pub max_relays: u32,
+    /// maximum number of reads a message can be sent with
+    pub max_views: u32,
+    /// maximum size of a search query in bytes
+    pub max_search_query_size: u32,
+    /// maximum number of labeled viewing keys of an address
+    pub max_viewing_key_labels: u32,
+    /// maximum size of a viewing key label in bytes
+    pub max_viewing_key_label_size: u32,
+    /// sizes in bytes that stored message content is padded to
+    pub padding_buckets: Vec<u32>,
+    /// obfuscation of the unread counts returned by recv and size
+    pub unread_count_fuzz: Option<CountFuzz>,
+    /// token and amount accepted as postage
+    pub postage: Option<PostageInfo>,
+    /// native postage charged for direct sends
+    pub send_fee: Option<SendFeeInfo>,
+    /// read_only while sending is disabled for maintenance
+    pub status: ContractStatus,
+    /// minimum number of blocks between proposing and executing a config change
+    pub config_change_delay: u64,
+    /// kinds of senders every recipient accepts messages from
+    pub sender_filter: SenderFilter,
+    /// true if handles emit an `activity` log attribute
+    pub activity_logs: bool,
+    /// fee for registering a name
+    pub name_fee: Option<FeeInfo>,
+    pub min_name_size: u32,
+    pub max_name_size: u32,
+    /// blocks after which messages without their own expiry expire, 0 if they never do
+    pub default_ttl_blocks: u64,
+    /// what content is accepted and how it is returned
+    pub payload_validation: PayloadValidation,
+    /// maximum number of unread messages one sender can have in a queue, 0 if unlimited
+    pub max_messages_per_sender: u32,
+    /// true if refused sends fail exactly like sends to a full queue
+    pub uniform_failures: bool,
+    /// SNIP-721 contract read receipts are minted on
+    pub receipt_nft: Option<ReceiptNftInfo>,
+    /// leading zero bits the proof of work of a send must have, 0 if none is required
+    pub pow_difficulty: u8,
+    /// maximum proof-of-work difficulty
+    pub max_pow_difficulty: u8,
+    /// version of the stored structs this instance was created with
+    pub state_version: u8,
+}
+
+/// sibling hash on the path from a leaf to a Merkle root
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MerkleStep {
+    pub sibling: Binary,
+    /// true if the sibling is the left input of the parent
+    pub left: bool,
+}
+
+/// admin action recorded in the audit log
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditActionInfo {
+    ChangeAdmin { new_admin: HumanAddr },
+    RenounceAdmin {},
+    SetContractStatus { status: ContractStatus },
+    Withdraw { amount: FeeInfo, to: HumanAddr },
+    RegisterContracts { count: u32 },
+    DeregisterContracts { count: u32 },
+    RegisterRelay { relay: HumanAddr },
+    DeregisterRelay { relay: HumanAddr },
+    ProposeConfigChange { executable_height: u64 },
+    ExecuteConfigChange {},
+    CancelConfigChange {},
+    PurgeQueue { address: HumanAddr, removed: u32 },
+    PurgeAll { removed: u32 },
+}
+
+/// entry of the audit log
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AuditEntryInfo {
+    /// position in the log, starting at 0
+    pub index: u64,
+    pub height: u64,
+    pub timestamp: u64,
+    /// address that performed the action
+    pub actor: HumanAddr,
+    pub action: AuditActionInfo,
+}
+
+/// metadata of a message waiting in a queue
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MessagePreview {
+    /// id of the message
+    pub id: Uint128,
+    /// sender of the message, None if it was sent anonymously
+    pub sender: Option<HumanAddr>,
+    /// size of the content in bytes
+    pub size: u32,
+    /// sender-assigned priority
+    pub priority: u8,
+    /// sender-assigned content type
+    pub content_type: Option<String>,
+    /// block height when the message was sent
+    pub height: u64,
+    /// block time (seconds) when the message was sent
+    pub timestamp: u64,
+    /// block height the sender committed to the content at, for commit-reveal sends
+    pub committed_height: Option<u64>,
+    /// if set, the message cannot be received until the chain passes this height
+    pub deliver_after_height: Option<u64>,
+    /// if set, the content cannot be revealed until the chain passes this height
+    pub reveal_after_height: Option<u64>,
+    /// if set, the message expires once the chain passes this height
+    pub expire_after_height: Option<u64>,
+    /// if set, the recipient snoozed the message until the chain passes this height
+    pub snoozed_until: Option<u64>,
+    /// true if the recipient pinned the message
+    pub pinned: bool,
+    /// true if the recipient flagged the message
+    pub flagged: bool,
+    /// true if the content is sealed until reveal_after_height and cannot be edited by the sender
+    pub time_capsule: bool,
+    /// notice generated by the contract, such as a poll that can be answered before it is read
+    pub system: Option<SystemNotice>,
+    /// sender-assigned tags
+    pub tags: Vec<String>,
+}
+
+/// notice generated by the contract, delivered in place of content
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemNotice {
+    /// the sender of this notice reacted to message `message_id`
+    Reaction {
+        message_id: Uint128,
+        reaction: String,
+    },
+    /// the message sent to the sender of this notice at `sent_height` was evicted unread
+    Evicted {
+        sent_height: u64,
+        sent_timestamp: u64,
+    },
+    /// the message sent to the sender of this notice at `sent_height` was not delivered because
+    /// their queue was full, it can be sent again later
+    Bounced {
+        sent_height: u64,
+        sent_timestamp: u64,
+    },
+    /// the sender of this notice asks for an answer to a poll, given with vote and the id of this
+    /// message
+    Poll {
+        question: String,
+        options: Vec<String>,
+    },
+}
+
+/// record of a sent message, the content itself is not kept
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SentItemSummary {
+    /// id of the message
+    pub id: Uint128,
+    /// recipient of the message
+    pub target: HumanAddr,
+    /// size of the content in bytes
+    pub size: u32,
+    /// sha256 hash of the content
+    pub content_hash: Binary,
+    /// block time (seconds) when the message was sent
+    pub timestamp: u64,
+}
+
+/// unread messages in a queue from a single sender
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConversationSummary {
+    /// sender of the messages
+    pub sender: HumanAddr,
+    /// number of unread messages from the sender
+    pub unread: u32,
+    /// block time (seconds) of the latest unread message from the sender
+    pub latest_timestamp: u64,
+}
+
+/// profile of an address
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProfileInfo {
+    pub display_name: Option<String>,
+    /// sha256 hash of the avatar image
+    pub avatar_hash: Option<Binary>,
+    pub bio: Option<String>,
+}
+
+/// status a recipient shares with senders
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PresenceInfo {
+    pub text: Option<String>,
+    pub away: bool,
+}
+
+/// number of unread messages in a queue from a single sender
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SenderUnread {
+    pub sender: HumanAddr,
+    pub unread: u32,
+}
+
+/// folder as listed by the folders query
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FolderInfo {
+    pub name: String,
+    /// maximum number of messages in the folder
+    pub max_messages: u32,
+    /// number of messages in the folder
+    pub length: u32,
+}
+
+/// relay as listed by the relays query
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RelayInfo {
+    pub address: HumanAddr,
+    pub code_hash: String,
+}
+
+/// alias as listed by the aliases query
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AliasInfo {
+    pub alias: String,
+    /// the alias stops delivering after this height
+    pub expire_after_height: Option<u64>,
+}
+
+/// queued message whose content matched a search
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SearchMatch {
+    pub id: Uint128,
+    /// None if the message was sent anonymously
+    pub sender: Option<HumanAddr>,
+    /// block time (seconds) when the message was sent
+    pub timestamp: u64,
+    /// part of the content around the first match
+    pub snippet: String,
+}
+
+/// note to self as listed by the notes query
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NoteSummary {
+    pub note_id: u32,
+    /// size of the content in bytes
+    pub size: u32,
+    pub content_type: Option<String>,
+    /// block time (seconds) when the note was written
+    pub timestamp: u64,
+    /// true if reading the note does not delete it
+    pub keep: bool,
+}
+
+/// Responses from authenticated queries
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryAnswer {
+    /// queued messages from front to rear
+    Preview {
+        messages: Vec<MessagePreview>,
+    },
+    /// unread counts grouped by sender
+    Conversations {
+        conversations: Vec<ConversationSummary>,
+    },
+    /// aggregate counters
+    Stats {
+        /// messages added to a queue
+        messages_sent: u64,
+        /// messages read and deleted by their recipient
+        messages_exploded: u64,
+        /// messages dequeued unread to make room in a full queue
+        messages_evicted: u64,
+        /// messages deleted unread because they expired or were pruned
+        messages_expired: u64,
+        /// queues holding at least one message
+        active_queues: u64,
+    },
+    /// result of a send dry run
+    CanSend {
+        /// true if the send is expected to succeed
+        can_send: bool,
+        /// reason the send would fail: "too_long" or "queue_full"
+        reason: Option<String>,
+    },
+    /// postage collected by the contract and not withdrawn yet
+    Revenue {
+        native: Vec<Coin>,
+        token: Uint128,
+    },
+    /// one page of the audit log
+    AuditLog {
+        entries: Vec<AuditEntryInfo>,
+        /// number of entries on all pages
+        total: u64,
+        /// number of entries on a full page
+        page_size: u32,
+    },
+    /// send time of a message with a receipt
+    Receipt {
+        height: u64,
+        timestamp: u64,
+        /// epoch whose Merkle root will include the receipt
+        epoch: u64,
+    },
+    /// Merkle root of a closed receipt epoch
+    ReceiptRoot {
+        root: Binary,
+        /// number of receipts in the epoch
+        size: u32,
+        start_height: u64,
+        end_height: u64,
+    },
+    /// owner of a name, None if it is not registered
+    ResolveName {
+        address: Option<HumanAddr>,
+    },
+    /// name of an address, None if it has none
+    NameOf {
+        name: Option<String>,
+    },
+    /// profile of an address, None if it has none or it is hidden from the viewer
+    Profile {
+        profile: Option<ProfileInfo>,
+    },
+    /// inclusion proof of a receipt
+    ReceiptProof {
+        epoch: u64,
+        index: u32,
+        /// leaf hash of the receipt
+        leaf: Binary,
+        /// siblings from the leaf up to the root
+        proof: Vec<MerkleStep>,
+        root: Binary,
+    },
+    /// config change waiting to be executed, all fields None if there is none
+    PendingConfigChange {
+        change: Option<ConfigChangeInfo>,
+        proposed_height: Option<u64>,
+        executable_height: Option<u64>,
+    },
+    /// activity counters of a single address
+    UserStats {
+        /// messages the address sent
+        messages_sent: u64,
+        /// messages the address read
+        messages_received: u64,
+        /// messages dequeued unread from the address's queue to make room
+        messages_evicted: u64,
+    },
+    /// one page of a blocklist
+    Blocklist {
+        addresses: Vec<HumanAddr>,
+        /// number of blocked addresses on all pages
+        total: u32,
+        /// number of addresses on a full page
+        page_size: u32,
+    },
+    /// inbox price and spam bond of an address
+    InboxPrice {
+        price: Option<FeeInfo>,
+        bond: Option<FeeInfo>,
+    },
+    /// token gate of an address
+    TokenGate {
+        gate: Option<TokenGateInfo>,
+    },
+    /// NFT gate of an address
+    NftGate {
+        gate: Option<NftGateInfo>,
+    },
+    /// minimum sender age of an address
+    MinSenderAge {
+        /// 0 if there is no minimum
+        blocks: u64,
+        /// height from which an address that first uses the contract now is accepted
+        accepted_from_height: u64,
+    },
+    /// complete blocklist
+    ExportBlocklist {
+        addresses: Vec<HumanAddr>,
+    },
+    /// muted addresses, oldest first
+    MuteList {
+        addresses: Vec<HumanAddr>,
+    },
+    /// number of queued messages, including ones not deliverable yet
+    UnreadCount {
+        count: u32,
+    },
+    /// number of queued messages per sender, including ones not deliverable yet
+    UnreadBySender {
+        counts: Vec<SenderUnread>,
+    },
+    /// notes to self, oldest first
+    Notes {
+        notes: Vec<NoteSummary>,
+    },
+    /// matching messages from front to rear
+    SearchInbox {
+        matches: Vec<SearchMatch>,
+    },
+    /// read acknowledgment, a secp256k1 signature by `pubkey` over
+    /// sha256(contract address ++ 0x00 ++ message id as 8 big-endian bytes)
+    ReadProof {
+        message_id: Uint128,
+        recipient: HumanAddr,
+        pubkey: Binary,
+        signature: Binary,
+        /// height at which the message was read
+        read_height: u64,
+    },
+    /// sanctioned relays, in the order they were registered
+    Relays {
+        relays: Vec<RelayInfo>,
+    },
+    /// live aliases, oldest first
+    Aliases {
+        aliases: Vec<AliasInfo>,
+    },
+    /// unredeemed invite codes, oldest first
+    Invites {
+        /// true if invite codes are required
+        invite_only: bool,
+        invites: Vec<String>,
+    },
+    /// anonymous tallies of a poll
+    PollResults {
+        question: String,
+        options: Vec<String>,
+        /// number of votes per option
+        tallies: Vec<u32>,
+        /// number of recipients the poll was delivered to
+        delivered: u32,
+    },
+    /// folders, in the order they were created
+    Folders {
+        folders: Vec<FolderInfo>,
+    },
+    /// permit epoch new permits of the address have to be signed for
+    PermitEpoch {
+        epoch: u32,
+    },
+    /// labels of the labeled viewing keys, in the order they were created
+    ViewingKeyLabels {
+        labels: Vec<String>,
+        /// true if the unlabeled viewing key is set
+        unlabeled: bool,
+    },
+    /// a queued message, content is omitted while delivery or reveal is scheduled
+    Message {
+        message_id: Uint128,
+        /// None if the message was sent anonymously
+        sender: Option<HumanAddr>,
+        content: Option<String>,
+        /// content as base64, set instead of content if it is raw bytes
+        raw_content: Option<Binary>,
+        sent_height: u64,
+        sent_timestamp: u64,
+    },
+    /// returned when the address and viewing key do not match
+    ViewingKeyError {
+        msg: String,
+    },
+}
+
+/// machine-readable status codes returned in the `code` field of handle answers
+pub const CODE_OK: u16 = 0;
+/// content (or another field) is longer than allowed
+pub const CODE_TOO_LONG: u16 = 10;
+/// the message could not be delivered, deliberately the same for a full queue and a blocked sender
+pub const CODE_NOT_DELIVERED: u16 = 11;
+/// the attached funds do not cover the postage, the recipient's inbox price or spam bond
+pub const CODE_INSUFFICIENT_FEE: u16 = 12;
+/// the send carries no nonce meeting the proof-of-work difficulty
+pub const CODE_POW_REQUIRED: u16 = 13;
+/// a parameter is invalid
+pub const CODE_INVALID_INPUT: u16 = 20;
+/// a per-address limit has been reached
+pub const CODE_LIMIT_REACHED: u16 = 21;
+/// the name is already registered, or the recipient of a transfer already has one
+pub const CODE_TAKEN: u16 = 22;
+/// the referenced message, commitment or record does not exist or does not belong to the sender
+pub const CODE_NOT_FOUND: u16 = 30;
+/// there are no messages to receive
+pub const CODE_NO_MESSAGES: u16 = 31;
+/// the next message is sealed until a later block
+pub const CODE_SEALED: u16 = 32;
+/// the next message can only be read with a valid read signature over its id
+pub const CODE_SIGNATURE_REQUIRED: u16 = 33;
+/// the sender is not allowed to make this request
+pub const CODE_UNAUTHORIZED: u16 = 40;
+/// the sender does not meet the recipient's requirements for senders
+pub const CODE_GATED: u16 = 41;
+/// the contract is in read-only maintenance mode and does not accept new messages
+pub const CODE_READ_ONLY: u16 = 50;
+
+/// success or failure response
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub enum ResponseStatus {
+    Success,
+    Failure,
+}
+
+/// Responses from handle functions
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleAnswer {
+    /// response from send attempt
+    Send {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// id of the sent message
+        message_id: Option<Uint128>,
+        /// hash identifying the public receipt, if one was requested
+        receipt: Option<Binary>,
+        /// status of the recipient, if the message was sent and the recipient shares it
+        recipient_status: Option<PresenceInfo>,
+    },
+    /// response from receive attempt
+    Recv {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// number of unread messages
+        number_of_unread_messages: u32,
+        /// id of message
+        message_id: Option<Uint128>,
+        /// content of message
+        content: Option<String>,
+        /// content of message as base64, set instead of content if it is raw bytes
+        raw_content: Option<Binary>,
+        /// sender of message
+        sender: Option<HumanAddr>,
+        /// set instead of content for notices generated by the contract
+        system: Option<SystemNotice>,
+        /// set when the waiting message is sealed, block height after which it can be read
+        sealed_until: Option<u64>,
+        /// block height when the message was sent
+        sent_height: Option<u64>,
+        /// block time (seconds) when the message was sent
+        sent_timestamp: Option<u64>,
+        /// block height the sender committed to the content at, for commit-reveal sends
+        committed_height: Option<u64>,
+        /// sender-assigned priority
+        priority: Option<u8>,
+        /// sender-assigned content type
+        content_type: Option<String>,
+        /// sender-assigned tags
+        tags: Vec<String>,
+        /// true if more unread messages from the same sender remain
+        more_from_sender: bool,
+        /// set instead of sender for anonymous messages, answers the sender once with
+        /// reply_anonymous
+        reply_token: Option<Binary>,
+        /// reads left before the message explodes, only set for messages sent with max_views
+        views_left: Option<u32>,
+        /// true if the sender was added to the blocklist on request
+        sender_blocked: bool,
+    },
+    /// create folder response
+    CreateFolder {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// delete folder response
+    DeleteFolder {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// move message response
+    MoveMessage {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// send poll response
+    SendPoll {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// id to query the results with
+        poll_id: Option<u64>,
+        /// number of recipients the poll was delivered to
+        delivered: u32,
+    },
+    /// vote response
+    Vote {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// register relay response
+    RegisterRelay {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// deregister relay response
+    DeregisterRelay {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// create alias response
+    CreateAlias {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// the new alias
+        alias: Option<String>,
+        /// the alias stops delivering after this height
+        expire_after_height: Option<u64>,
+    },
+    /// burn alias response
+    BurnAlias {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// create invite response
+    CreateInvite {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// the new invite code
+        invite: Option<String>,
+    },
+    /// revoke invite response
+    RevokeInvite {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// set invite only response
+    SetInviteOnly {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// reply anonymous response
+    ReplyAnonymous {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// id of the reply
+        message_id: Option<Uint128>,
+    },
+    /// archive response
+    Archive {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// block height after which the archived message is deleted
+        expire_after_height: Option<u64>,
+    },
+    /// read note response
+    ReadNote {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// id of the note
+        note_id: Option<u32>,
+        /// content of the note
+        content: Option<String>,
+        /// content of the note as base64, set instead of content if it is raw bytes
+        raw_content: Option<Binary>,
+        /// content type given when the note was written
+        content_type: Option<String>,
+        /// block time (seconds) when the note was written
+        sent_timestamp: Option<u64>,
+    },
+    /// delete note response
+    DeleteNote {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// response from dead letter receive attempt
+    RecvDeadLetter {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// number of dead letters left
+        remaining: u32,
+        /// content of message
+        content: Option<String>,
+        /// content of message as base64, set instead of content if it is raw bytes
+        raw_content: Option<Binary>,
+        /// sender of message
+        sender: Option<HumanAddr>,
+        /// block height when the message was sent
+        sent_height: Option<u64>,
+        /// block time (seconds) when the message was sent
+        sent_timestamp: Option<u64>,
+        /// sender-assigned content type
+        content_type: Option<String>,
+        /// whether the message was evicted or expired
+        reason: Option<DeadLetterReason>,
+        /// set when the oldest dead letter is sealed, block height after which it can be read
+        sealed_until: Option<u64>,
+    },
+    /// set dead letters response
+    SetDeadLetters {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// response from size of message box attempt
+    Size {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// number of unread messages
+        number_of_unread_messages: u32,
+    },
+    /// block response
+    Block {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// block sender of response
+    BlockSenderOf {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// the address that was blocked
+        blocked: Option<HumanAddr>,
+    },
+    /// unblock response
+    Unblock {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// mute response
+    Mute {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// unmute response
+    Unmute {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// set inbox price response
+    SetInboxPrice {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// set spam bond response
+    SetSpamBond {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// report spam response
+    ReportSpam {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// claim bond response
+    ClaimBond {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// add contact response
+    AddContact {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// remove contact response
+    RemoveContact {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// set auto block response
+    SetAutoBlock {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// set sender filter response
+    SetSenderFilter {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// set token gate response
+    SetTokenGate {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// set NFT gate response
+    SetNftGate {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// set min sender age response
+    SetMinSenderAge {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// set status response
+    SetStatus {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// set quiet hours response
+    SetQuietHours {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// set eviction notices response
+    SetEvictionNotices {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// set bounce notices response
+    SetBounceNotices {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// import blocklist response
+    ImportBlocklist {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// number of addresses that were not blocked before
+        imported: u32,
+    },
+    /// read sent items response
+    ReadSent {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// sent items from oldest to newest
+        items: Vec<SentItemSummary>,
+    },
+    /// clear sent items response
+    ClearSent {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// reaction response
+    React {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// edit response
+    Edit {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// move to front response
+    MoveToFront {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// snooze response
+    Snooze {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// pin response
+    Pin {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// unpin response
+    Unpin {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// flag response
+    Flag {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// unflag response
+    Unflag {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// clear all response
+    ClearAll {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// number of messages deleted
+        removed: u32,
+        /// true if messages to clear remain, send the request again to continue
+        more_remaining: bool,
+    },
+    /// heartbeat response
+    Heartbeat {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// commit send response
+    CommitSend {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// send chunk response
+    SendChunk {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// id of the assembled message, once the last chunk arrived
+        message_id: Option<Uint128>,
+    },
+    /// chunked receive response
+    RecvChunk {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// requested bytes of the content
+        content: Option<Binary>,
+        /// size of the whole content in bytes
+        total_size: u32,
+        /// true if this chunk reached the end of the content
+        final_chunk: bool,
+    },
+    /// acknowledge chunked receive response
+    AckRecv {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// prune response
+    Prune {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// number of messages deleted
+        removed: u32,
+        /// true if the limit was reached before the whole queue was cleaned
+        more_remaining: bool,
+    },
+    /// register receive response
+    RegisterReceive {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// register read callback response
+    RegisterReadCallback {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// grant query response
+    GrantQuery {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// revoke query response
+    RevokeQuery {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// batch response
+    Batch {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// answer of each operation, in order
+        results: Vec<HandleAnswer>,
+    },
+    /// set signing key response
+    SetSigningKey {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// grant session response
+    GrantSession {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// revoke session response
+    RevokeSession {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// change admin response
+    ChangeAdmin {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// renounce admin response
+    RenounceAdmin {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// set contract status response
+    SetContractStatus {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// withdraw response
+    Withdraw {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// purge queue response
+    PurgeQueue {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// number of messages deleted
+        removed: u32,
+        /// true if the queue still holds messages
+        more_remaining: bool,
+    },
+    /// purge all response
+    PurgeAll {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// number of messages deleted
+        removed: u32,
+        /// true if any queue still holds messages
+        more_remaining: bool,
+    },
+    /// propose config change response
+    ProposeConfigChange {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// first block height at which the change can be executed
+        executable_height: Option<u64>,
+    },
+    /// close receipt epoch response
+    CloseReceiptEpoch {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// id of the closed epoch
+        epoch: Option<u64>,
+    },
+    /// register name response
+    RegisterName {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// transfer name response
+    TransferName {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// release name response
+    ReleaseName {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// set profile response
+    SetProfile {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// register contracts response
+    RegisterContracts {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// deregister contracts response
+    DeregisterContracts {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// execute config change response
+    ExecuteConfigChange {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// cancel config change response
+    CancelConfigChange {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// set viewing key response
+    SetViewingKey {
+        /// success or failure
+        status: ResponseStatus,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// revoke permit response
+    RevokePermit {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// revoke all permits response
+    RevokeAllPermits {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// permit epoch new permits have to be signed for
+        epoch: u32,
+    },
+    /// rotate storage key response
+    RotateStorageKey {
+        /// success or failure
+        status: ResponseStatus,
+        /// execution description
+        message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+    },
+    /// create viewing key response
+    CreateViewingKey {
+        /// success or failure
+        status: ResponseStatus,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
+        /// the generated key, empty if no key was created
+        key: String,
+    },
+    /// revoke viewing key response
+    RevokeViewingKey {
         /// success or failure
         status: ResponseStatus,
         /// execution description
         message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
     },
     /// generic status response
     Status {
@@ -104,5 +2526,7 @@ pub enum HandleAnswer {
         status: ResponseStatus,
         /// execution description
         message: String,
+        /// machine-readable status code, see the CODE_* constants
+        code: u16,
     },
 }
@@ -0,0 +1,167 @@
+use cosmwasm_std::{to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, Querier,
+                   ReadonlyStorage, StdResult, Storage};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use serde::{Deserialize, Serialize};
+
+use crate::msg::{HandleAnswer, QueryAnswer, ResponseStatus};
+use crate::msg::ResponseStatus::{Success, Failure};
+use crate::msg::{CODE_OK, CODE_INVALID_INPUT, CODE_LIMIT_REACHED};
+use crate::state::{may_load, save, remove, Fee};
+
+// keys for muted senders take form: b"mut{recipient CanonicalAddr}{sender CanonicalAddr}"
+pub static MUTED_PREFIX: &[u8] = b"mut";
+// keys for the mute list of an address take form: b"mtl{CanonicalAddr.as_slice().to_vec()}"
+pub static MUTE_LIST_PREFIX: &[u8] = b"mtl";
+// keys for dropped messages take form: b"mtm{message id as 8 big-endian bytes}"
+pub static MUTED_MESSAGE_PREFIX: &[u8] = b"mtm";
+// keys for the postage forfeited by muted senders take form: b"mtf{CanonicalAddr.as_slice().to_vec()}"
+pub static FORFEITED_PREFIX: &[u8] = b"mtf";
+
+/// maximum number of addresses an address can mute
+pub const MAX_MUTED: usize = 256;
+
+/// what is left of a message dropped because its target muted the sender, so requests by the
+/// sender about the message are answered as if it was still unread
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MutedMessage {
+    pub from: CanonicalAddr,
+    pub time_capsule: bool,
+}
+
+pub fn write_muted_message<S: Storage>(storage: &mut S, message_id: u64, muted_message: &MutedMessage) -> StdResult<()> {
+    save(&mut PrefixedStorage::new(MUTED_MESSAGE_PREFIX, storage), &message_id.to_be_bytes(), muted_message)
+}
+
+pub fn read_muted_message<S: ReadonlyStorage>(storage: &S, message_id: u64) -> StdResult<Option<MutedMessage>> {
+    may_load(&ReadonlyPrefixedStorage::new(MUTED_MESSAGE_PREFIX, storage), &message_id.to_be_bytes())
+}
+
+/// Keeps postage or a bond of a dropped message for the recipient, who collects it with their next
+/// read instead of in the sender's transaction, where the payout would give the mute away
+pub fn forfeit_to<S: Storage>(storage: &mut S, recipient: &CanonicalAddr, fee: &Fee) -> StdResult<()> {
+    let mut forfeited_storage = PrefixedStorage::new(FORFEITED_PREFIX, storage);
+    let mut forfeited: Vec<Fee> = may_load(&forfeited_storage, recipient.as_slice())?.unwrap_or_default();
+    forfeited.push(fee.clone());
+    save(&mut forfeited_storage, recipient.as_slice(), &forfeited)
+}
+
+/// Returns and forgets the postage and bonds forfeited to the recipient
+pub fn take_forfeited<S: Storage>(storage: &mut S, recipient: &CanonicalAddr) -> StdResult<Vec<Fee>> {
+    let mut forfeited_storage = PrefixedStorage::new(FORFEITED_PREFIX, storage);
+    let forfeited: Vec<Fee> = may_load(&forfeited_storage, recipient.as_slice())?.unwrap_or_default();
+    if !forfeited.is_empty() {
+        remove(&mut forfeited_storage, recipient.as_slice());
+    }
+    Ok(forfeited)
+}
+
+fn muted_key(recipient: &CanonicalAddr, sender: &CanonicalAddr) -> Vec<u8> {
+    let mut key = recipient.as_slice().to_vec();
+    key.extend_from_slice(sender.as_slice());
+    key
+}
+
+fn read_mute_list<S: ReadonlyStorage>(storage: &S, recipient: &CanonicalAddr) -> StdResult<Vec<CanonicalAddr>> {
+    Ok(may_load(&ReadonlyPrefixedStorage::new(MUTE_LIST_PREFIX, storage), recipient.as_slice())?.unwrap_or_default())
+}
+
+fn write_mute_list<S: Storage>(storage: &mut S, recipient: &CanonicalAddr, muted: &[CanonicalAddr]) -> StdResult<()> {
+    save(&mut PrefixedStorage::new(MUTE_LIST_PREFIX, storage), recipient.as_slice(), &muted.to_vec())
+}
+
+/// Returns true if the recipient has muted the sender, checked with a single read so the cost of
+/// a send does not grow with the mute list
+pub fn is_muted<S: ReadonlyStorage>(storage: &S, recipient: &CanonicalAddr, sender: &CanonicalAddr) -> bool {
+    ReadonlyPrefixedStorage::new(MUTED_PREFIX, storage).get(&muted_key(recipient, sender)).is_some()
+}
+
+/// Mutes an address, whose messages to the sender are then dropped while it is told they were
+/// delivered
+pub fn try_mute<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let muted_address_raw = deps.api.canonical_address(&address)?;
+    if muted_address_raw == sender_address_raw {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str("You cannot mute yourself.");
+    } else if is_muted(&deps.storage, &sender_address_raw, &muted_address_raw) {
+        // already muted, only write to the storage if needed
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str(&format!("Address {} muted.", address));
+    } else {
+        let mut muted = read_mute_list(&deps.storage, &sender_address_raw)?;
+        if muted.len() >= MAX_MUTED {
+            status = Failure;
+            code = CODE_LIMIT_REACHED;
+            response_message.push_str(&format!("A mute list can hold at most {} addresses.", MAX_MUTED));
+        } else {
+            let mut muted_storage = PrefixedStorage::new(MUTED_PREFIX, &mut deps.storage);
+            muted_storage.set(&muted_key(&sender_address_raw, &muted_address_raw), &[1]);
+            muted.push(muted_address_raw);
+            write_mute_list(&mut deps.storage, &sender_address_raw, &muted)?;
+            status = Success;
+            code = CODE_OK;
+            response_message.push_str(&format!("Address {} muted.", address));
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Mute {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+/// Takes an address off the sender's mute list. Messages dropped while it was muted stay lost
+pub fn try_unmute<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let muted_address_raw = deps.api.canonical_address(&address)?;
+    if is_muted(&deps.storage, &sender_address_raw, &muted_address_raw) {
+        // only write to the storage if needed
+        let mut muted_storage = PrefixedStorage::new(MUTED_PREFIX, &mut deps.storage);
+        muted_storage.remove(&muted_key(&sender_address_raw, &muted_address_raw));
+        let mut muted = read_mute_list(&deps.storage, &sender_address_raw)?;
+        muted.retain(|m| m != &muted_address_raw);
+        write_mute_list(&mut deps.storage, &sender_address_raw, &muted)?;
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Unmute {
+            status: Success,
+            message: format!("Address {} unmuted.", address),
+            code: CODE_OK,
+        })?),
+    })
+}
+
+pub fn query_mute_list<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    let addresses = read_mute_list(&deps.storage, &address_raw)?
+        .iter()
+        .map(|muted| deps.api.human_address(muted))
+        .collect::<StdResult<Vec<HumanAddr>>>()?;
+
+    to_binary(&QueryAnswer::MuteList { addresses })
+}
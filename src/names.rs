@@ -0,0 +1,219 @@
+use cosmwasm_std::{to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, Querier,
+                   ReadonlyStorage, StdError, StdResult, Storage};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+
+use crate::contract::{fee_covered, refund_excess};
+use crate::msg::{FeeInfo, HandleAnswer, QueryAnswer, ResponseStatus};
+use crate::msg::ResponseStatus::{Success, Failure};
+use crate::msg::{CODE_OK, CODE_INVALID_INPUT, CODE_INSUFFICIENT_FEE, CODE_NOT_FOUND, CODE_TAKEN};
+use crate::state::{load, may_load, save, credit_revenue, Config, Fee, CONFIG_KEY};
+
+// keys for the owners of names take form: b"nam{name}"
+pub static NAME_PREFIX: &[u8] = b"nam";
+// keys for the name of an address take form: b"nmo{CanonicalAddr.as_slice().to_vec()}"
+pub static NAME_OWNER_PREFIX: &[u8] = b"nmo";
+
+/// minimum length of a name
+pub const MIN_NAME_SIZE: usize = 3;
+/// maximum length of a name
+pub const MAX_NAME_SIZE: usize = 32;
+
+/// Returns true if `name` is 3 to 32 lowercase letters, digits, `-` or `_`, starts with a letter
+/// and cannot be mistaken for an address
+pub fn valid_name(name: &str) -> bool {
+    name.len() >= MIN_NAME_SIZE
+        && name.len() <= MAX_NAME_SIZE
+        && name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        && !name.starts_with("secret1")
+}
+
+/// Returns the name fee if it is a native coin with a denom, None otherwise
+pub fn valid_name_fee(fee: FeeInfo) -> Option<Fee> {
+    match fee.into() {
+        Fee::Native { denom, .. } if denom.is_empty() => None,
+        Fee::Native { denom, amount } => Some(Fee::Native { denom, amount }),
+        Fee::Token { .. } => None,
+    }
+}
+
+/// Returns the owner of a name, None if it is not registered
+pub fn read_name_owner<S: ReadonlyStorage>(storage: &S, name: &str) -> StdResult<Option<CanonicalAddr>> {
+    let name_storage = ReadonlyPrefixedStorage::new(NAME_PREFIX, storage);
+    may_load(&name_storage, name.as_bytes())
+}
+
+/// Returns the name registered by an address, None if it has none
+pub fn read_name_of<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> StdResult<Option<String>> {
+    let owner_storage = ReadonlyPrefixedStorage::new(NAME_OWNER_PREFIX, storage);
+    may_load(&owner_storage, owner.as_slice())
+}
+
+/// Links a name and its owner in both directions
+fn write_name<S: Storage>(storage: &mut S, name: &str, owner: &CanonicalAddr) -> StdResult<()> {
+    save(&mut PrefixedStorage::new(NAME_PREFIX, storage), name.as_bytes(), owner)?;
+    save(&mut PrefixedStorage::new(NAME_OWNER_PREFIX, storage), owner.as_slice(), &name.to_string())
+}
+
+/// Frees a name and unlinks it from its owner
+fn remove_name<S: Storage>(storage: &mut S, name: &str, owner: &CanonicalAddr) {
+    PrefixedStorage::new(NAME_PREFIX, storage).remove(name.as_bytes());
+    PrefixedStorage::new(NAME_OWNER_PREFIX, storage).remove(owner.as_slice());
+}
+
+pub fn try_register_name<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: String,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut messages = vec![];
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    if !valid_name(&name) {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str(&format!(
+            "Names are {}-{} lowercase letters, digits, '-' or '_' and start with a letter.",
+            MIN_NAME_SIZE, MAX_NAME_SIZE
+        ));
+    } else if read_name_owner(&deps.storage, &name)?.is_some() {
+        status = Failure;
+        code = CODE_TAKEN;
+        response_message.push_str("The name is already registered.");
+    } else if matches!(config.name_fee.as_ref(), Some(fee) if !fee_covered(fee, &env.message.sent_funds, 0)) {
+        status = Failure;
+        code = CODE_INSUFFICIENT_FEE;
+        response_message.push_str("Insufficient fee for registering a name.");
+    } else {
+        if let Some(fee) = &config.name_fee {
+            credit_revenue(&mut deps.storage, fee)?;
+        }
+        messages = refund_excess(
+            &deps.api, &config, &env.contract.address, &env.message.sender, config.name_fee.as_ref(), None,
+            &env.message.sent_funds, 0,
+        )?;
+        // an address holds a single name, registering a new one releases the old one
+        if let Some(old_name) = read_name_of(&deps.storage, &sender_address_raw)? {
+            remove_name(&mut deps.storage, &old_name, &sender_address_raw);
+        }
+        write_name(&mut deps.storage, &name, &sender_address_raw)?;
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Name registered.");
+    }
+
+    // a failed registration is reverted, so the attached fee goes back to the sender
+    if code != CODE_OK && !env.message.sent_funds.is_empty() {
+        return Err(StdError::generic_err(response_message));
+    }
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RegisterName {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_transfer_name<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    to: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let to_raw = deps.api.canonical_address(&to)?;
+    match read_name_of(&deps.storage, &sender_address_raw)? {
+        None => {
+            status = Failure;
+            code = CODE_NOT_FOUND;
+            response_message.push_str("You have not registered a name.");
+        }
+        Some(_) if read_name_of(&deps.storage, &to_raw)?.is_some() => {
+            status = Failure;
+            code = CODE_TAKEN;
+            response_message.push_str("The recipient already has a name.");
+        }
+        Some(name) => {
+            remove_name(&mut deps.storage, &name, &sender_address_raw);
+            write_name(&mut deps.storage, &name, &to_raw)?;
+            status = Success;
+            code = CODE_OK;
+            response_message.push_str("Name transferred.");
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::TransferName {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn try_release_name<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    match read_name_of(&deps.storage, &sender_address_raw)? {
+        None => {
+            status = Failure;
+            code = CODE_NOT_FOUND;
+            response_message.push_str("You have not registered a name.");
+        }
+        Some(name) => {
+            remove_name(&mut deps.storage, &name, &sender_address_raw);
+            status = Success;
+            code = CODE_OK;
+            response_message.push_str("Name released.");
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ReleaseName {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn query_resolve_name<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    name: &str,
+) -> StdResult<Binary> {
+    let address = match read_name_owner(&deps.storage, name)? {
+        Some(owner) => Some(deps.api.human_address(&owner)?),
+        None => None,
+    };
+
+    to_binary(&QueryAnswer::ResolveName { address })
+}
+
+pub fn query_name_of<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+
+    to_binary(&QueryAnswer::NameOf { name: read_name_of(&deps.storage, &address_raw)? })
+}
@@ -0,0 +1,231 @@
+use cosmwasm_std::{to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, Querier,
+                   ReadonlyStorage, StdResult, Storage};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::cipher::{seal, open};
+use crate::contract::encode_payload;
+use crate::msg::{HandleAnswer, NoteSummary, QueryAnswer, ResponseStatus};
+use crate::msg::ResponseStatus::{Success, Failure};
+use crate::msg::{CODE_OK, CODE_NOT_FOUND, CODE_NO_MESSAGES};
+use crate::state::{load, may_load, save, remove, content_key, derive_key, padded, Config, CONFIG_KEY};
+
+// keys for notes take form: b"not{CanonicalAddr.as_slice().to_vec()}{note_id.to_be_bytes()}"
+pub static NOTE_PREFIX: &[u8] = b"not";
+// keys for the note index of an address take form: b"nti{CanonicalAddr.as_slice().to_vec()}"
+pub static NOTE_INDEX_PREFIX: &[u8] = b"nti";
+
+/// maximum number of notes to self an address can keep, independent of max_messages
+pub const MAX_NOTES: u32 = 256;
+
+/// message an address sent to itself, kept apart from its queue
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Note {
+    /// content padded like message content, encrypted under the owner's note key
+    pub content: Vec<u8>,
+    /// size of the content before padding
+    pub content_size: u32,
+    pub content_type: Option<String>,
+    pub height: u64,
+    pub timestamp: u64,
+    /// true if reading the note does not delete it
+    pub keep: bool,
+}
+
+/// ids of the notes of an address, oldest first
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct NoteIndex {
+    pub ids: Vec<u32>,
+    pub next_id: u32,
+}
+
+fn note_key(owner: &CanonicalAddr, note_id: u32) -> Vec<u8> {
+    [owner.as_slice(), &note_id.to_be_bytes()].concat()
+}
+
+// every address gets its own note key, so notes cannot be moved between owners
+fn note_cipher_key<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> StdResult<[u8; 32]> {
+    Ok(derive_key(&content_key(storage)?, &[&b"note"[..], owner.as_slice()].concat()))
+}
+
+pub fn read_note_index<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> StdResult<NoteIndex> {
+    let index_storage = ReadonlyPrefixedStorage::new(NOTE_INDEX_PREFIX, storage);
+    let index: Option<NoteIndex> = may_load(&index_storage, owner.as_slice())?;
+    Ok(index.unwrap_or_default())
+}
+
+fn write_note_index<S: Storage>(storage: &mut S, owner: &CanonicalAddr, index: &NoteIndex) -> StdResult<()> {
+    save(&mut PrefixedStorage::new(NOTE_INDEX_PREFIX, storage), owner.as_slice(), index)
+}
+
+/// Returns a note with its content decrypted and unpadded, None if it does not exist
+pub fn read_note<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr, note_id: u32) -> StdResult<Option<Note>> {
+    let key = note_key(owner, note_id);
+    let note: Option<Note> = may_load(&ReadonlyPrefixedStorage::new(NOTE_PREFIX, storage), &key)?;
+    match note {
+        Some(mut note) => {
+            let mut content = open(&note_cipher_key(storage, owner)?, &key, &note.content)?;
+            content.truncate(note.content_size as usize);
+            note.content = content;
+            Ok(Some(note))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Stores a new note with its content padded to `buckets` and encrypted, and returns its id, None
+/// if the owner already has MAX_NOTES notes
+pub fn write_note<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    mut note: Note,
+    buckets: &[u32],
+) -> StdResult<Option<u32>> {
+    let mut index = read_note_index(storage, owner)?;
+    if index.ids.len() >= MAX_NOTES as usize {
+        return Ok(None);
+    }
+    let note_id = index.next_id;
+    index.next_id = index.next_id.wrapping_add(1);
+    index.ids.push(note_id);
+
+    let key = note_key(owner, note_id);
+    note.content_size = note.content.len() as u32;
+    note.content = seal(&note_cipher_key(storage, owner)?, &key, &padded(note.content, buckets))?;
+    save(&mut PrefixedStorage::new(NOTE_PREFIX, storage), &key, &note)?;
+    write_note_index(storage, owner, &index)?;
+    Ok(Some(note_id))
+}
+
+/// Deletes a note, returns false if it does not exist
+fn remove_note<S: Storage>(storage: &mut S, owner: &CanonicalAddr, note_id: u32) -> StdResult<bool> {
+    let mut index = read_note_index(storage, owner)?;
+    let count = index.ids.len();
+    index.ids.retain(|id| *id != note_id);
+    if index.ids.len() == count {
+        return Ok(false);
+    }
+    remove(&mut PrefixedStorage::new(NOTE_PREFIX, storage), &note_key(owner, note_id));
+    write_note_index(storage, owner, &index)?;
+    Ok(true)
+}
+
+/// Reads a note, the oldest one if no id is given. Notes not kept after reading are deleted.
+pub fn try_read_note<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    note_id: Option<u32>,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut content: Option<String> = None;
+    let mut raw_content: Option<Binary> = None;
+    let mut content_type: Option<String> = None;
+    let mut sent_timestamp: Option<u64> = None;
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let note_id = match note_id {
+        Some(note_id) => Some(note_id),
+        None => read_note_index(&deps.storage, &sender_address_raw)?.ids.first().copied(),
+    };
+    let note = match note_id {
+        Some(note_id) => read_note(&deps.storage, &sender_address_raw, note_id)?,
+        None => None,
+    };
+    match (note_id, note) {
+        (None, _) => {
+            status = Failure;
+            code = CODE_NO_MESSAGES;
+            response_message.push_str("You have no notes.");
+        }
+        (Some(_), None) => {
+            status = Failure;
+            code = CODE_NOT_FOUND;
+            response_message.push_str("Note not found.");
+        }
+        (Some(note_id), Some(note)) => {
+            let config: Config = load(&deps.storage, CONFIG_KEY)?;
+            let (text, raw) = encode_payload(&config, &note.content);
+            content = text;
+            raw_content = raw;
+            content_type = note.content_type;
+            sent_timestamp = Some(note.timestamp);
+            if !note.keep {
+                remove_note(&mut deps.storage, &sender_address_raw, note_id)?;
+            }
+            status = Success;
+            code = CODE_OK;
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ReadNote {
+            status,
+            message: response_message,
+            code,
+            note_id,
+            content,
+            raw_content,
+            content_type,
+            sent_timestamp,
+        })?),
+    })
+}
+
+pub fn try_delete_note<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    note_id: u32,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    if remove_note(&mut deps.storage, &sender_address_raw, note_id)? {
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str("Note deleted.");
+    } else {
+        status = Failure;
+        code = CODE_NOT_FOUND;
+        response_message.push_str("Note not found.");
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::DeleteNote {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+pub fn query_notes<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    let mut notes: Vec<NoteSummary> = vec![];
+    for note_id in read_note_index(&deps.storage, &address_raw)?.ids {
+        let note_storage = ReadonlyPrefixedStorage::new(NOTE_PREFIX, &deps.storage);
+        let note: Option<Note> = may_load(&note_storage, &note_key(&address_raw, note_id))?;
+        if let Some(note) = note {
+            notes.push(NoteSummary {
+                note_id,
+                size: note.content_size,
+                content_type: note.content_type,
+                timestamp: note.timestamp,
+                keep: note.keep,
+            });
+        }
+    }
+
+    to_binary(&QueryAnswer::Notes { notes })
+}
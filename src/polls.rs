@@ -0,0 +1,288 @@
+use cosmwasm_std::{to_binary, Api, Binary, CanonicalAddr, CosmosMsg, Env, Extern, HandleResponse, HumanAddr, Querier,
+                   ReadonlyStorage, StdError, StdResult, Storage, Uint128};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use secret_toolkit::serialization::{Bincode2, Serde};
+use serde::{Deserialize, Serialize};
+
+use crate::cipher::{seal, open};
+use crate::contract::{deliver_message, fee_covered, inbox_fee, refund_excess, to_message_id};
+use crate::msg::{HandleAnswer, QueryAnswer, ResponseStatus};
+use crate::msg::ResponseStatus::{Success, Failure};
+use crate::msg::{CODE_OK, CODE_INVALID_INPUT, CODE_TOO_LONG, CODE_INSUFFICIENT_FEE, CODE_POW_REQUIRED, CODE_NOT_FOUND,
+                 CODE_READ_ONLY};
+use crate::state::{load, may_load, save, remove, content_key, derive_key, credit_revenue, record_first_seen, Config,
+                   ContractStatus, Fee, Message, SystemMessage, CONFIG_KEY};
+
+// keys for polls take form: b"pll{poll_id.to_be_bytes()}"
+pub static POLL_PREFIX: &[u8] = b"pll";
+// keys for unanswered ballots take form: b"blt{recipient.as_slice()}{message_id.to_be_bytes()}"
+pub static BALLOT_PREFIX: &[u8] = b"blt";
+pub static POLL_COUNT_KEY: &[u8] = b"pollcount";
+
+/// maximum size of a poll question in bytes
+pub const MAX_POLL_QUESTION_SIZE: usize = 256;
+/// maximum number of options of a poll
+pub const MAX_POLL_OPTIONS: usize = 8;
+/// maximum size of a poll option in bytes
+pub const MAX_POLL_OPTION_SIZE: usize = 64;
+/// maximum number of recipients of a poll
+pub const MAX_POLL_TARGETS: usize = 16;
+
+/// question sent to several recipients, of which only the tallies are kept
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Poll {
+    pub creator: CanonicalAddr,
+    pub question: String,
+    pub options: Vec<String>,
+    /// number of votes per option
+    pub tallies: Vec<u32>,
+    /// number of recipients the poll was delivered to
+    pub delivered: u32,
+}
+
+/// a poll as it is stored, with the question and options encrypted like message content
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct StoredPoll {
+    creator: CanonicalAddr,
+    /// question and options, encrypted under the poll key
+    content: Vec<u8>,
+    tallies: Vec<u32>,
+    delivered: u32,
+}
+
+fn ballot_key(recipient: &CanonicalAddr, message_id: u64) -> Vec<u8> {
+    [recipient.as_slice(), &message_id.to_be_bytes()].concat()
+}
+
+fn poll_cipher_key<S: ReadonlyStorage>(storage: &S) -> StdResult<[u8; 32]> {
+    Ok(derive_key(&content_key(storage)?, b"poll"))
+}
+
+/// Returns a poll with its question and options decrypted, None if it does not exist
+pub fn read_poll<S: ReadonlyStorage>(storage: &S, poll_id: u64) -> StdResult<Option<Poll>> {
+    let key = poll_id.to_be_bytes();
+    let stored: Option<StoredPoll> = may_load(&ReadonlyPrefixedStorage::new(POLL_PREFIX, storage), &key)?;
+    match stored {
+        Some(stored) => {
+            let (question, options): (String, Vec<String>) =
+                Bincode2::deserialize(&open(&poll_cipher_key(storage)?, &key, &stored.content)?)?;
+            Ok(Some(Poll {
+                creator: stored.creator,
+                question,
+                options,
+                tallies: stored.tallies,
+                delivered: stored.delivered,
+            }))
+        }
+        None => Ok(None),
+    }
+}
+
+fn write_poll<S: Storage>(storage: &mut S, poll_id: u64, poll: &Poll) -> StdResult<()> {
+    let key = poll_id.to_be_bytes();
+    let content = Bincode2::serialize(&(&poll.question, &poll.options))?;
+    let stored = StoredPoll {
+        creator: poll.creator.clone(),
+        content: seal(&poll_cipher_key(storage)?, &key, &content)?,
+        tallies: poll.tallies.clone(),
+        delivered: poll.delivered,
+    };
+    save(&mut PrefixedStorage::new(POLL_PREFIX, storage), &key, &stored)
+}
+
+/// Forgets the unanswered ballot of a message that left the recipient's queue, if it is a poll, so
+/// polls that are never answered do not leave their ballots behind
+pub fn remove_ballot<S: Storage>(storage: &mut S, recipient: &CanonicalAddr, message_id: u64, mes: &Message) {
+    if let Some(SystemMessage::Poll { .. }) = mes.system {
+        remove(&mut PrefixedStorage::new(BALLOT_PREFIX, storage), &ballot_key(recipient, message_id));
+    }
+}
+
+/// Creates a poll and delivers it to every target that accepts messages from the sender without a
+/// price or bond. The send fee is charged once per delivery, as for separate sends, and the rest of
+/// the attached funds is returned.
+pub fn try_send_poll<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    question: String,
+    options: Vec<String>,
+    targets: Vec<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+    let mut poll_id: Option<u64> = None;
+    let mut delivered: u32 = 0;
+    let mut messages: Vec<CosmosMsg> = vec![];
+
+    let config: Config = load(&deps.storage, CONFIG_KEY)?;
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    record_first_seen(&mut deps.storage, &sender_address_raw, env.block.height);
+    let mut target_addresses_raw: Vec<CanonicalAddr> = vec![];
+    for target in &targets {
+        let target_address_raw = deps.api.canonical_address(target)?;
+        if target_address_raw != sender_address_raw && !target_addresses_raw.contains(&target_address_raw) {
+            target_addresses_raw.push(target_address_raw);
+        }
+    }
+    let size = question.len() + options.iter().map(|o| o.len()).sum::<usize>();
+    // the postage for every target has to be attached, but only deliveries are charged
+    let send_fee_for = |targets: usize| config.send_fee.as_ref().map(|send_fee| Fee::Native {
+        denom: send_fee.denom.clone(),
+        amount: send_fee.amount_for(size).saturating_mul(targets as u128),
+    });
+    let send_fee = send_fee_for(target_addresses_raw.len());
+
+    if config.status == ContractStatus::ReadOnly {
+        status = Failure;
+        code = CODE_READ_ONLY;
+        response_message.push_str("Sending is disabled during maintenance.");
+    } else if config.pow_difficulty > 0 {
+        // a poll carries no proof of work for each of its targets
+        status = Failure;
+        code = CODE_POW_REQUIRED;
+        response_message.push_str("Polls are disabled while sends need a proof of work.");
+    } else if question.is_empty() || options.len() < 2 || options.len() > MAX_POLL_OPTIONS
+        || options.iter().any(|o| o.is_empty()) {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str(&format!("A poll has a question and 2-{} non-empty options.", MAX_POLL_OPTIONS));
+    } else if question.len() > MAX_POLL_QUESTION_SIZE
+        || options.iter().any(|o| o.len() > MAX_POLL_OPTION_SIZE) {
+        status = Failure;
+        code = CODE_TOO_LONG;
+        response_message.push_str("Poll question or option is too long.");
+    } else if target_addresses_raw.is_empty() || target_addresses_raw.len() > MAX_POLL_TARGETS {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str(&format!("A poll goes to 1-{} other addresses.", MAX_POLL_TARGETS));
+    } else if matches!(send_fee.as_ref(), Some(fee) if !fee_covered(fee, &env.message.sent_funds, 0)) {
+        status = Failure;
+        code = CODE_INSUFFICIENT_FEE;
+        response_message.push_str("The attached funds do not cover the postage.");
+    } else {
+        let id: u64 = may_load(&deps.storage, POLL_COUNT_KEY)?.unwrap_or(0);
+        save(&mut deps.storage, POLL_COUNT_KEY, &(id + 1))?;
+        for target_address_raw in &target_addresses_raw {
+            // polls cannot carry postage, so inboxes with a price or bond are skipped
+            if inbox_fee(&deps.storage, target_address_raw, &sender_address_raw) != (None, None) {
+                continue;
+            }
+            let poll_message = Message {
+                system: Some(SystemMessage::Poll { poll_id: id }),
+                ..Message::new(vec![], sender_address_raw.clone(), env.block.height, env.block.time)
+            };
+            if let Some(message_id) = deliver_message(&mut deps.storage, &config, target_address_raw, poll_message)? {
+                let mut ballot_storage = PrefixedStorage::new(BALLOT_PREFIX, &mut deps.storage);
+                save(&mut ballot_storage, &ballot_key(target_address_raw, message_id), &id)?;
+                delivered += 1;
+            }
+        }
+        let poll = Poll {
+            creator: sender_address_raw,
+            question,
+            tallies: vec![0; options.len()],
+            options,
+            delivered,
+        };
+        write_poll(&mut deps.storage, id, &poll)?;
+        let charged = send_fee_for(delivered as usize);
+        if let Some(charged) = &charged {
+            credit_revenue(&mut deps.storage, charged)?;
+        }
+        messages.extend(refund_excess(
+            &deps.api, &config, &env.contract.address, &env.message.sender, charged.as_ref(), None,
+            &env.message.sent_funds, 0,
+        )?);
+        poll_id = Some(id);
+        status = Success;
+        code = CODE_OK;
+        response_message.push_str(&format!("Poll delivered to {} of {} recipients.", delivered, target_addresses_raw.len()));
+    }
+
+    // a failed poll is reverted, so attached funds go back to the sender
+    if code != CODE_OK && !env.message.sent_funds.is_empty() {
+        return Err(StdError::generic_err(response_message));
+    }
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SendPoll {
+            status,
+            message: response_message,
+            code,
+            poll_id,
+            delivered,
+        })?),
+    })
+}
+
+/// Adds the sender's answer to the tallies of the poll delivered as `message_id` while the message
+/// is still in the sender's queue. Each delivered poll can be answered once and the answer is not
+/// kept.
+pub fn try_vote<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    message_id: Uint128,
+    option: u32,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+    let key = ballot_key(&sender_address_raw, to_message_id(message_id));
+    let ballot: Option<u64> = may_load(&ReadonlyPrefixedStorage::new(BALLOT_PREFIX, &deps.storage), &key)?;
+    let poll = match ballot {
+        Some(poll_id) => read_poll(&deps.storage, poll_id)?.map(|poll| (poll_id, poll)),
+        None => None,
+    };
+    match poll {
+        None => {
+            status = Failure;
+            code = CODE_NOT_FOUND;
+            response_message.push_str("No unanswered poll with this message id.");
+        }
+        Some((_, poll)) if option as usize >= poll.options.len() => {
+            status = Failure;
+            code = CODE_INVALID_INPUT;
+            response_message.push_str(&format!("The poll has {} options.", poll.options.len()));
+        }
+        Some((poll_id, mut poll)) => {
+            poll.tallies[option as usize] += 1;
+            write_poll(&mut deps.storage, poll_id, &poll)?;
+            remove(&mut PrefixedStorage::new(BALLOT_PREFIX, &mut deps.storage), &key);
+            status = Success;
+            code = CODE_OK;
+            response_message.push_str("Vote counted.");
+        }
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Vote {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+/// Returns the tallies of a poll to its creator, the votes of single recipients are never stored
+pub fn query_poll_results<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    poll_id: u64,
+) -> StdResult<Binary> {
+    let address_raw = deps.api.canonical_address(address)?;
+    match read_poll(&deps.storage, poll_id)? {
+        Some(poll) if poll.creator == address_raw => to_binary(&QueryAnswer::PollResults {
+            question: poll.question,
+            options: poll.options,
+            tallies: poll.tallies,
+            delivered: poll.delivered,
+        }),
+        _ => Err(StdError::generic_err("Poll not found.")),
+    }
+}
@@ -0,0 +1,154 @@
+use cosmwasm_std::{to_binary, Api, Binary, CanonicalAddr, Env, Extern, HandleResponse, HumanAddr, Querier,
+                   ReadonlyStorage, StdResult, Storage};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::msg::{HandleAnswer, ProfileInfo, QueryAnswer, ResponseStatus};
+use crate::msg::ResponseStatus::{Success, Failure};
+use crate::msg::{CODE_OK, CODE_INVALID_INPUT, CODE_TOO_LONG};
+use crate::state::{may_load, save, remove, is_contact};
+
+// keys for profiles take form: b"prf{CanonicalAddr.as_slice().to_vec()}"
+pub static PROFILE_PREFIX: &[u8] = b"prf";
+
+/// maximum size of a display name in bytes
+pub const MAX_DISPLAY_NAME_SIZE: usize = 32;
+/// maximum size of a bio in bytes
+pub const MAX_BIO_SIZE: usize = 256;
+/// size of an avatar hash in bytes, a sha256 of the image
+pub const AVATAR_HASH_SIZE: usize = 32;
+
+/// who can read a profile
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileVisibility {
+    /// anyone, with the public profile query
+    Public,
+    /// addresses on the owner's contact list, with the authenticated view_profile query
+    ContactsOnly,
+    /// only the owner
+    Private,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Profile {
+    pub display_name: Option<String>,
+    /// sha256 hash of the avatar image, which is stored off-chain
+    pub avatar_hash: Option<Vec<u8>>,
+    pub bio: Option<String>,
+    pub visibility: ProfileVisibility,
+}
+
+impl From<Profile> for ProfileInfo {
+    fn from(profile: Profile) -> Self {
+        ProfileInfo {
+            display_name: profile.display_name,
+            avatar_hash: profile.avatar_hash.map(Binary),
+            bio: profile.bio,
+        }
+    }
+}
+
+/// Returns the profile of an address, None if it has none
+pub fn read_profile<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> StdResult<Option<Profile>> {
+    let profile_storage = ReadonlyPrefixedStorage::new(PROFILE_PREFIX, storage);
+    may_load(&profile_storage, owner.as_slice())
+}
+
+/// Returns true if `viewer` may read a profile with the given visibility
+fn can_view<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr, viewer: &CanonicalAddr, visibility: ProfileVisibility) -> bool {
+    viewer == owner
+        || match visibility {
+            ProfileVisibility::Public => true,
+            ProfileVisibility::ContactsOnly => is_contact(storage, owner, viewer),
+            ProfileVisibility::Private => false,
+        }
+}
+
+pub fn try_set_profile<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    display_name: Option<String>,
+    avatar_hash: Option<Binary>,
+    bio: Option<String>,
+    visibility: ProfileVisibility,
+) -> StdResult<HandleResponse> {
+    let status: ResponseStatus;
+    let code: u16;
+    let mut response_message = String::new();
+
+    if matches!(display_name.as_ref(), Some(name) if name.len() > MAX_DISPLAY_NAME_SIZE) {
+        status = Failure;
+        code = CODE_TOO_LONG;
+        response_message.push_str(&format!("Display name is longer than {} bytes.", MAX_DISPLAY_NAME_SIZE));
+    } else if matches!(bio.as_ref(), Some(bio) if bio.len() > MAX_BIO_SIZE) {
+        status = Failure;
+        code = CODE_TOO_LONG;
+        response_message.push_str(&format!("Bio is longer than {} bytes.", MAX_BIO_SIZE));
+    } else if matches!(avatar_hash.as_ref(), Some(hash) if hash.len() != AVATAR_HASH_SIZE) {
+        status = Failure;
+        code = CODE_INVALID_INPUT;
+        response_message.push_str(&format!("Avatar hash must be {} bytes.", AVATAR_HASH_SIZE));
+    } else {
+        let sender_address_raw = deps.api.canonical_address(&env.message.sender)?;
+        let mut profile_storage = PrefixedStorage::new(PROFILE_PREFIX, &mut deps.storage);
+        // a profile without any fields is not stored
+        if display_name.is_none() && avatar_hash.is_none() && bio.is_none() {
+            remove(&mut profile_storage, sender_address_raw.as_slice());
+            response_message.push_str("Profile removed.");
+        } else {
+            let profile = Profile {
+                display_name,
+                avatar_hash: avatar_hash.map(|hash| hash.0),
+                bio,
+                visibility,
+            };
+            save(&mut profile_storage, sender_address_raw.as_slice(), &profile)?;
+            response_message.push_str("Profile set.");
+        }
+        status = Success;
+        code = CODE_OK;
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetProfile {
+            status,
+            message: response_message,
+            code,
+        })?),
+    })
+}
+
+/// Returns the profile of `owner` if it is public
+pub fn query_profile<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    owner: &HumanAddr,
+) -> StdResult<Binary> {
+    let owner_raw = deps.api.canonical_address(owner)?;
+    let profile = match read_profile(&deps.storage, &owner_raw)? {
+        Some(profile) if profile.visibility == ProfileVisibility::Public => Some(profile.into()),
+        _ => None,
+    };
+
+    to_binary(&QueryAnswer::Profile { profile })
+}
+
+/// Returns the profile of `owner` if `viewer` may read it. Hidden and missing profiles look the
+/// same, so viewers cannot tell whether an address has a profile it hides from them
+pub fn query_view_profile<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    viewer: &HumanAddr,
+    owner: &HumanAddr,
+) -> StdResult<Binary> {
+    let viewer_raw = deps.api.canonical_address(viewer)?;
+    let owner_raw = deps.api.canonical_address(owner)?;
+    let profile = match read_profile(&deps.storage, &owner_raw)? {
+        Some(profile) if can_view(&deps.storage, &owner_raw, &viewer_raw, profile.visibility) => Some(profile.into()),
+        _ => None,
+    };
+
+    to_binary(&QueryAnswer::Profile { profile })
+}
@@ -0,0 +1,175 @@
+use cosmwasm_std::{CanonicalAddr, ReadonlyStorage, StdError, StdResult, Storage};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::state::{Message, MessageQueue, MessageQueueStorage, MessageStorage, ReadonlyMessageStorage};
+
+/// what a full queue does when a new message arrives
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EvictionPolicy {
+    /// evict the message that was queued first
+    DropOldest,
+    /// evict the message that was queued last
+    DropNewest,
+    /// evict the message with the lowest sender-assigned priority, the oldest of equals
+    DropLowestPriority,
+    /// refuse the new message
+    Reject,
+}
+
+impl Default for EvictionPolicy {
+    fn default() -> Self {
+        EvictionPolicy::DropOldest
+    }
+}
+
+/// Chooses the message a full queue gives up for a new one. Pinned messages are only chosen if
+/// every message in the queue is pinned.
+pub trait Eviction {
+    /// Returns false if a full queue refuses new messages instead of evicting one
+    fn makes_room(&self) -> bool {
+        true
+    }
+
+    /// Returns the id and contents of the message to evict from a full queue, None if the new
+    /// message has to be refused
+    fn victim<S: ReadonlyStorage>(&self, storage: &S, queue: &MessageQueue) -> StdResult<Option<(u64, Message)>>;
+}
+
+impl Eviction for EvictionPolicy {
+    fn makes_room(&self) -> bool {
+        match self {
+            EvictionPolicy::DropOldest => OldestFirst.makes_room(),
+            EvictionPolicy::DropNewest => NewestFirst.makes_room(),
+            EvictionPolicy::DropLowestPriority => LowestPriorityFirst.makes_room(),
+            EvictionPolicy::Reject => RejectWhenFull.makes_room(),
+        }
+    }
+
+    fn victim<S: ReadonlyStorage>(&self, storage: &S, queue: &MessageQueue) -> StdResult<Option<(u64, Message)>> {
+        match self {
+            EvictionPolicy::DropOldest => OldestFirst.victim(storage, queue),
+            EvictionPolicy::DropNewest => NewestFirst.victim(storage, queue),
+            EvictionPolicy::DropLowestPriority => LowestPriorityFirst.victim(storage, queue),
+            EvictionPolicy::Reject => RejectWhenFull.victim(storage, queue),
+        }
+    }
+}
+
+/// Calls `visit` with the messages of the queue, from the front or from the rear, until it returns
+/// false
+fn walk<S: ReadonlyStorage, F: FnMut(u64, &Message) -> bool>(
+    storage: &S,
+    queue: &MessageQueue,
+    from_rear: bool,
+    mut visit: F,
+) -> StdResult<()> {
+    let message_storage = ReadonlyMessageStorage::from_storage(storage)?;
+    let mut id = if from_rear { queue.rear } else { queue.front };
+    for _ in 0..queue.length {
+        match message_storage.get_message(&id)? {
+            Some(mes) => {
+                let next = if from_rear { mes.prev } else { mes.next };
+                if !visit(id, &mes) {
+                    break;
+                }
+                id = next;
+            }
+            // this should never happen (queue length is longer than the linked messages)
+            None => return Err(StdError::generic_err("Corrupted message queue.")),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the first unpinned message from the front or the rear, the first message if all are
+/// pinned
+fn first_unpinned<S: ReadonlyStorage>(
+    storage: &S,
+    queue: &MessageQueue,
+    from_rear: bool,
+) -> StdResult<Option<(u64, Message)>> {
+    let mut first: Option<(u64, Message)> = None;
+    let mut unpinned: Option<(u64, Message)> = None;
+    walk(storage, queue, from_rear, |id, mes| {
+        if first.is_none() {
+            first = Some((id, mes.clone()));
+        }
+        if !mes.pinned {
+            unpinned = Some((id, mes.clone()));
+        }
+        unpinned.is_none()
+    })?;
+    Ok(unpinned.or(first))
+}
+
+/// Splices a message out of the owner's queue and relinks it at the front, so it is the next one
+/// recv returns. A snoozed message is woken up. Returns false if the queue holds no message with
+/// that id.
+pub fn move_to_front<S: Storage>(storage: &mut S, owner: &CanonicalAddr, id: u64) -> StdResult<bool> {
+    let mut queue = MessageQueueStorage::from_storage(storage).get_message_queue(owner);
+    let mut found: Option<Message> = None;
+    walk(&*storage, &queue, false, |message_id, mes| {
+        if message_id == id {
+            found = Some(mes.clone());
+        }
+        found.is_none()
+    })?;
+    match found {
+        Some(mut mes) => {
+            mes.snoozed_until = None;
+            MessageStorage::from_storage(storage)?.move_to_front(&mut queue, &id, mes)?;
+            MessageQueueStorage::from_storage(storage).set_message_queue(owner, queue);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+pub struct OldestFirst;
+
+impl Eviction for OldestFirst {
+    fn victim<S: ReadonlyStorage>(&self, storage: &S, queue: &MessageQueue) -> StdResult<Option<(u64, Message)>> {
+        first_unpinned(storage, queue, false)
+    }
+}
+
+pub struct NewestFirst;
+
+impl Eviction for NewestFirst {
+    fn victim<S: ReadonlyStorage>(&self, storage: &S, queue: &MessageQueue) -> StdResult<Option<(u64, Message)>> {
+        first_unpinned(storage, queue, true)
+    }
+}
+
+pub struct LowestPriorityFirst;
+
+impl Eviction for LowestPriorityFirst {
+    fn victim<S: ReadonlyStorage>(&self, storage: &S, queue: &MessageQueue) -> StdResult<Option<(u64, Message)>> {
+        let mut lowest: Option<(u64, Message)> = None;
+        let mut lowest_unpinned: Option<(u64, Message)> = None;
+        walk(storage, queue, false, |id, mes| {
+            if !matches!(lowest.as_ref(), Some((_, found)) if mes.priority >= found.priority) {
+                lowest = Some((id, mes.clone()));
+            }
+            if !mes.pinned && !matches!(lowest_unpinned.as_ref(), Some((_, found)) if mes.priority >= found.priority) {
+                lowest_unpinned = Some((id, mes.clone()));
+            }
+            true
+        })?;
+        Ok(lowest_unpinned.or(lowest))
+    }
+}
+
+pub struct RejectWhenFull;
+
+impl Eviction for RejectWhenFull {
+    fn makes_room(&self) -> bool {
+        false
+    }
+
+    fn victim<S: ReadonlyStorage>(&self, _storage: &S, _queue: &MessageQueue) -> StdResult<Option<(u64, Message)>> {
+        Ok(None)
+    }
+}
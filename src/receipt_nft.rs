@@ -0,0 +1,83 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Api, CosmosMsg, HumanAddr, StdResult, Storage};
+use secret_toolkit::crypto::sha_256;
+use secret_toolkit::utils::HandleCallback;
+
+use crate::contract::BLOCK_SIZE;
+use crate::state::{may_load, save, Config, Message};
+
+pub static RECEIPT_NFT_COUNT_KEY: &[u8] = b"rcptnftcount";
+
+/// metadata of a SNIP-721 token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Metadata {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+}
+
+/// handle messages of the SNIP-721 contract read receipts are minted on, which has to accept
+/// this contract as a minter
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Snip721HandleMsg {
+    MintNft {
+        token_id: Option<String>,
+        owner: Option<HumanAddr>,
+        public_metadata: Option<Metadata>,
+        private_metadata: Option<Metadata>,
+        memo: Option<String>,
+        padding: Option<String>,
+    },
+}
+
+impl HandleCallback for Snip721HandleMsg {
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+}
+
+/// Returns the commitment a read receipt carries, which is
+/// sha256(message id as 8 big-endian bytes ++ content), so the sender can check it against the
+/// message they sent
+pub fn read_commitment(message_id: u64, content: &[u8]) -> [u8; 32] {
+    sha_256(&[&message_id.to_be_bytes()[..], content].concat())
+}
+
+/// Returns the mint of a read receipt for the sender of a message that asked for one when it is
+/// exploded at `height`, None if it did not ask or no receipt contract is configured. Only the
+/// owner can see the metadata, so the token reveals nothing about the message to others.
+pub fn mint_read_receipt<S: Storage, A: Api>(
+    storage: &mut S,
+    api: &A,
+    config: &Config,
+    message_id: u64,
+    mes: &Message,
+    height: u64,
+) -> StdResult<Option<CosmosMsg>> {
+    let receipt_nft = match &config.receipt_nft {
+        Some(receipt_nft) if mes.mint_receipt => receipt_nft,
+        _ => return Ok(None),
+    };
+    let count: u64 = may_load(storage, RECEIPT_NFT_COUNT_KEY)?.unwrap_or(0);
+    save(storage, RECEIPT_NFT_COUNT_KEY, &(count + 1))?;
+
+    let commitment: String = read_commitment(message_id, mes.payload()).iter().map(|b| format!("{:02x}", b)).collect();
+    let metadata = Metadata {
+        name: Some(format!("Read receipt {}", count)),
+        description: Some(format!(
+            "{{\"message_id\":\"{}\",\"commitment\":\"{}\",\"read_height\":{}}}",
+            message_id, commitment, height
+        )),
+        image: None,
+    };
+    let msg = Snip721HandleMsg::MintNft {
+        token_id: Some(format!("read-receipt-{}", count)),
+        owner: Some(api.human_address(&mes.from)?),
+        public_metadata: None,
+        private_metadata: Some(metadata),
+        memo: None,
+        padding: None,
+    };
+    msg.to_cosmos_msg(receipt_nft.code_hash.clone(), api.human_address(&receipt_nft.contract)?, None).map(Some)
+}
@@ -0,0 +1,27 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{HumanAddr, Uint128};
+use secret_toolkit::utils::HandleCallback;
+
+use crate::contract::BLOCK_SIZE;
+
+/// notifications sent to contracts that registered a receiver or a read callback
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiverHandleMsg {
+    /// a message from `sender` was queued for the receiving contract
+    MessageReceived {
+        sender: HumanAddr,
+        message_id: Uint128,
+    },
+    /// message `message_id` sent by the receiving contract was read by `recipient`
+    MessageRead {
+        recipient: HumanAddr,
+        message_id: Uint128,
+    },
+}
+
+impl HandleCallback for ReceiverHandleMsg {
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+}
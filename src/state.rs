@@ -1,132 +1,2600 @@
-use std::{any::type_name,collections::HashSet};
+use std::any::type_name;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{CanonicalAddr, Storage, ReadonlyStorage, StdResult, StdError};
+use cosmwasm_std::{Binary, CanonicalAddr, HumanAddr, Storage, ReadonlyStorage, StdResult, StdError};
 use serde::de::DeserializeOwned;
-use secret_toolkit::serialization::{Bincode2, Serde};
-use cosmwasm_storage::{PrefixedStorage};
+use secret_toolkit::serialization::{Bincode2, Json, Serde};
+use secret_toolkit::crypto::sha_256;
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+
+use crate::cipher::{seal, open};
+use crate::migrations::upgrade_record;
+use crate::queue::{Eviction, EvictionPolicy};
+use crate::viewing_key::{ViewingKey, VIEWING_KEY_SIZE};
 
-pub static SEQ_KEY: &[u8] = b"seq";
 pub static CONFIG_KEY: &[u8] = b"config";
+pub static STATS_KEY: &[u8] = b"stats";
+pub static CONTRACT_SECRET_KEY: &[u8] = b"secret";
+pub static PRNG_SEED_KEY: &[u8] = b"prngseed";
+pub static REVENUE_KEY: &[u8] = b"revenue";
+pub static PENDING_CONFIG_KEY: &[u8] = b"pendingcfg";
+pub static RECEIPT_EPOCH_KEY: &[u8] = b"rcpepoch";
+pub static AUDIT_COUNT_KEY: &[u8] = b"auditcount";
+pub static STATE_VERSION_KEY: &[u8] = b"stateversion";
+pub static LATEST_HEIGHT_KEY: &[u8] = b"latestheight";
+pub static CONTRACT_ADDRESS_KEY: &[u8] = b"contractaddr";
+pub static RELAYS_KEY: &[u8] = b"relays";
 // keys for messages take form: b"mes{message_id.to_be_bytes()}"
 pub static MESSAGE_PREFIX: &[u8] = b"mes";
 // keys for message box queues take form: b"box{CanonicalAddr.as_slice().to_vec()}"
 pub static MESSAGE_QUEUE_PREFIX: &[u8] = b"box";
+// keys for hashed viewing keys take form: b"vk{CanonicalAddr.as_slice().to_vec()}{label}", the
+// unlabeled key has an empty label
+pub static VIEWING_KEY_PREFIX: &[u8] = b"vk";
+// keys for the labels of an address's labeled viewing keys take form:
+// b"vkl{CanonicalAddr.as_slice().to_vec()}"
+pub static VIEWING_KEY_LABELS_PREFIX: &[u8] = b"vkl";
+// keys for blocked senders take form: b"blk{recipient.as_slice()}{sender.as_slice()}"
+pub static BLOCKED_PREFIX: &[u8] = b"blk";
+// keys for spam bonds take form: b"bnd{message_id.to_be_bytes()}"
+pub static BOND_PREFIX: &[u8] = b"bnd";
+// keys for contacts take form: b"ctc{owner.as_slice()}{contact.as_slice()}"
+pub static CONTACT_PREFIX: &[u8] = b"ctc";
+// keys for per-sender offense counters take form: b"ofn{recipient.as_slice()}{sender.as_slice()}"
+pub static OFFENSE_PREFIX: &[u8] = b"ofn";
+// keys for contracts registered by the admin take form: b"ktr{CanonicalAddr.as_slice().to_vec()}"
+pub static REGISTERED_CONTRACT_PREFIX: &[u8] = b"ktr";
+// keys for message receipts take form: b"rct{receipt_hash}"
+pub static RECEIPT_PREFIX: &[u8] = b"rct";
+// keys for closed receipt epochs take form: b"rep{epoch_id.to_be_bytes()}"
+pub static RECEIPT_EPOCH_PREFIX: &[u8] = b"rep";
+// keys for audit log entries take form: b"adt{index.to_be_bytes()}"
+pub static AUDIT_PREFIX: &[u8] = b"adt";
+// keys for tag indexes take form: b"tgi{recipient.as_slice()}{sha256(tag)}"
+pub static TAG_INDEX_PREFIX: &[u8] = b"tgi";
+// keys for the folders of an address take form: b"fdl{CanonicalAddr.as_slice().to_vec()}"
+pub static FOLDERS_PREFIX: &[u8] = b"fdl";
+// keys for folder queues take form: b"fdq{owner.as_slice()}{folder name}"
+pub static FOLDER_QUEUE_PREFIX: &[u8] = b"fdq";
+// keys for reply tokens take form: b"rpt{sha256(token)}"
+pub static REPLY_TOKEN_PREFIX: &[u8] = b"rpt";
+// keys for flagged indexes take form: b"fgi{CanonicalAddr.as_slice().to_vec()}"
+pub static FLAGGED_INDEX_PREFIX: &[u8] = b"fgi";
+// keys for blocklist pages take form: b"blp{recipient.as_slice()}{page.to_be_bytes()}"
+pub static BLOCKLIST_PAGE_PREFIX: &[u8] = b"blp";
+// keys for dead letters take form: b"dlt{recipient.as_slice()}{counter.to_be_bytes()}"
+pub static DEAD_LETTER_PREFIX: &[u8] = b"dlt";
+// keys for dead letter indexes take form: b"dli{CanonicalAddr.as_slice().to_vec()}"
+pub static DEAD_LETTER_INDEX_PREFIX: &[u8] = b"dli";
+// keys for per-user settings take form: b"set{CanonicalAddr.as_slice().to_vec()}"
+pub static USER_SETTINGS_PREFIX: &[u8] = b"set";
+// keys for per-sender indexes take form: b"cnv{CanonicalAddr.as_slice().to_vec()}"
+pub static CONVERSATION_PREFIX: &[u8] = b"cnv";
+// keys for sent items folders take form: b"snt{CanonicalAddr.as_slice().to_vec()}"
+pub static SENT_ITEMS_PREFIX: &[u8] = b"snt";
+// keys for recently received message records take form: b"rcv{CanonicalAddr.as_slice().to_vec()}"
+pub static RECEIVED_PREFIX: &[u8] = b"rcv";
+// keys for dead man's switch messages take form: b"rls{CanonicalAddr.as_slice().to_vec()}" (target)
+pub static SCHEDULED_RELEASE_PREFIX: &[u8] = b"rls";
+// keys for last heartbeat heights take form: b"hbt{CanonicalAddr.as_slice().to_vec()}"
+pub static HEARTBEAT_PREFIX: &[u8] = b"hbt";
+// keys for per-address activity counters take form: b"ust{CanonicalAddr.as_slice().to_vec()}"
+pub static USER_STATS_PREFIX: &[u8] = b"ust";
+// keys for pending send commitments take form: b"cmt{CanonicalAddr.as_slice().to_vec()}"
+pub static COMMITMENT_PREFIX: &[u8] = b"cmt";
+// keys for pending chunked transfers take form: b"xfr{CanonicalAddr.as_slice().to_vec()}" (sender)
+pub static TRANSFER_PREFIX: &[u8] = b"xfr";
+// keys for chunk data take form: b"chk{CanonicalAddr}{transfer_id.to_be_bytes()}{index.to_be_bytes()}"
+pub static CHUNK_PREFIX: &[u8] = b"chk";
+// keys for per-recipient message sequences take form: b"sqn{CanonicalAddr.as_slice().to_vec()}"
+pub static SEQUENCE_PREFIX: &[u8] = b"sqn";
+// keys for registered receiver code hashes take form: b"rch{CanonicalAddr.as_slice().to_vec()}"
+pub static RECEIVER_PREFIX: &[u8] = b"rch";
+// keys for registered read callback code hashes take form: b"rdc{CanonicalAddr.as_slice().to_vec()}"
+pub static SENDER_CALLBACK_PREFIX: &[u8] = b"rdc";
+// keys for hashed delegated query keys take form: b"dlg{owner.as_slice()}{querier.as_slice()}"
+pub static DELEGATION_PREFIX: &[u8] = b"dlg";
+// keys for the content key generation of a recipient take form: b"skg{CanonicalAddr.as_slice().to_vec()}"
+pub static KEY_GENERATION_PREFIX: &[u8] = b"skg";
+// keys for registered signing public keys take form: b"sgk{CanonicalAddr.as_slice().to_vec()}"
+pub static SIGNING_KEY_PREFIX: &[u8] = b"sgk";
+// keys for read proofs take form: b"rdp{sender.as_slice()}{message_id.to_be_bytes()}"
+pub static READ_PROOF_PREFIX: &[u8] = b"rdp";
+// keys for the last used signed send nonces take form: b"nce{CanonicalAddr.as_slice().to_vec()}"
+pub static NONCE_PREFIX: &[u8] = b"nce";
+// keys for query permit epochs take form: b"pep{CanonicalAddr.as_slice().to_vec()}"
+pub static PERMIT_EPOCH_PREFIX: &[u8] = b"pep";
+// keys for revoked query permit names take form: b"rvp{owner.as_slice()}{permit_name}"
+pub static REVOKED_PERMIT_PREFIX: &[u8] = b"rvp";
+// keys for session keys take form: b"ses{CanonicalAddr.as_slice().to_vec()}" (grantor)
+pub static SESSION_PREFIX: &[u8] = b"ses";
+// keys for active queue index links take form: b"aqi{CanonicalAddr.as_slice().to_vec()}"
+pub static ACTIVE_QUEUE_PREFIX: &[u8] = b"aqi";
+// keys for the height an address first sent a message take form: b"fsn{CanonicalAddr.as_slice().to_vec()}"
+pub static FIRST_SEEN_PREFIX: &[u8] = b"fsn";
+pub static ACTIVE_QUEUE_HEAD_KEY: &[u8] = b"aqhead";
+
+/// number of recently received messages per address that can still be reacted to
+pub const MAX_RECENTLY_RECEIVED: u32 = 16;
+/// number of unrevealed send commitments an address can hold
+pub const MAX_PENDING_COMMITMENTS: u32 = 16;
+/// maximum number of chunks in a chunked transfer
+pub const MAX_CHUNKS: u32 = 16;
+/// number of unfinished chunked transfers an address can hold
+pub const MAX_PENDING_TRANSFERS: u32 = 4;
+/// unfinished chunked transfers without a new chunk for this many blocks are garbage collected
+pub const TRANSFER_TIMEOUT_BLOCKS: u64 = 14_400;
+/// number of session keys an address can grant at once
+pub const MAX_SESSIONS: u32 = 8;
+/// number of addresses a user can block
+pub const MAX_BLOCKED: u32 = 256;
+/// number of addresses in each stored page of a blocklist
+pub const BLOCKLIST_PAGE_SIZE: u32 = 32;
+
+/// Derives the message id for a recipient's sequence number as a keyed hash with the contract
+/// secret, so ids reveal nothing about the order or number of messages sent
+pub fn derive_message_id(secret: &[u8], recipient: &CanonicalAddr, seq: u64) -> u64 {
+    let mut preimage = secret.to_vec();
+    preimage.extend_from_slice(recipient.as_slice());
+    preimage.extend_from_slice(&seq.to_be_bytes());
+    let hash = sha_256(&preimage);
+    let mut id_bytes = [0u8; 8];
+    id_bytes.copy_from_slice(&hash[..8]);
+    u64::from_be_bytes(id_bytes)
+}
+
+/// Derives a key for `domain` from the contract secret, so keys for different uses are independent
+pub fn derive_key(secret: &[u8], domain: &[u8]) -> [u8; 32] {
+    sha_256(&[secret, domain].concat())
+}
+
+/// Derives the reply token of an anonymous message from the contract secret, the message id and
+/// the height it was sent at, so the token never has to be stored
+pub fn reply_token(secret: &[u8], message_id: u64, height: u64) -> [u8; 32] {
+    sha_256(&[secret, b"reply", &message_id.to_be_bytes(), &height.to_be_bytes()].concat())
+}
+
+/// Returns the key content is encrypted with at rest, derived from the contract secret
+pub fn content_key<S: ReadonlyStorage>(storage: &S) -> StdResult<[u8; 32]> {
+    let secret: Vec<u8> = load(storage, CONTRACT_SECRET_KEY)?;
+    Ok(derive_key(&secret, b"content"))
+}
+
+/// Returns the generation of the key content queued for `owner` is encrypted with, 0 until the
+/// owner first rotates it
+pub fn read_key_generation<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> u32 {
+    let generation_storage = ReadonlyPrefixedStorage::new(KEY_GENERATION_PREFIX, storage);
+    let generation: Option<u32> = may_load(&generation_storage, owner.as_slice()).ok().flatten();
+    generation.unwrap_or(0)
+}
+
+pub fn write_key_generation<S: Storage>(storage: &mut S, owner: &CanonicalAddr, generation: u32) -> StdResult<()> {
+    let mut generation_storage = PrefixedStorage::new(KEY_GENERATION_PREFIX, storage);
+    save(&mut generation_storage, owner.as_slice(), &generation)
+}
+
+// every generation of every recipient gets its own key. All of them derive from the content key,
+// so anyone holding the contract secret can derive every one of them
+fn recipient_key(content_key: &[u8; 32], owner: &CanonicalAddr, generation: u32) -> [u8; 32] {
+    derive_key(content_key, &[&b"recipient"[..], &generation.to_be_bytes(), owner.as_slice()].concat())
+}
+
+/// Encrypts message content under the key of `owner` at `generation`. The owner and generation
+/// are stored in front of it, encrypted under the content key, so the message can be decrypted
+/// without knowing who it is for while the stored record does not name its recipient
+fn seal_message(content_key: &[u8; 32], id: u64, owner: &CanonicalAddr, generation: u32, content: &[u8]) -> StdResult<Vec<u8>> {
+    let mut header = vec![owner.as_slice().len() as u8];
+    header.extend_from_slice(owner.as_slice());
+    header.extend_from_slice(&generation.to_be_bytes());
+    let header = seal(&derive_key(content_key, b"header"), &id.to_be_bytes(), &header)?;
+    let mut sealed = vec![header.len() as u8];
+    sealed.extend(header);
+    sealed.extend(seal(&recipient_key(content_key, owner, generation), &id.to_be_bytes(), content)?);
+    Ok(sealed)
+}
+
+/// Decrypts the header written by seal_message, returning the owner and generation of the key
+/// the content was encrypted with, along with the encrypted content
+fn open_message_header<'a>(content_key: &[u8; 32], id: u64, sealed: &'a [u8]) -> StdResult<(CanonicalAddr, u32, &'a [u8])> {
+    let header_size = sealed.first().map_or(0, |size| *size as usize);
+    if sealed.len() < 1 + header_size {
+        return Err(StdError::generic_err("Stored content is corrupted."));
+    }
+    let header = open(&derive_key(content_key, b"header"), &id.to_be_bytes(), &sealed[1..1 + header_size])?;
+    let owner_size = header.first().map_or(0, |size| *size as usize);
+    if header.len() != 5 + owner_size {
+        return Err(StdError::generic_err("Stored content is corrupted."));
+    }
+    let owner = CanonicalAddr(Binary(header[1..1 + owner_size].to_vec()));
+    let mut generation_bytes = [0u8; 4];
+    generation_bytes.copy_from_slice(&header[1 + owner_size..]);
+    Ok((owner, u32::from_be_bytes(generation_bytes), &sealed[1 + header_size..]))
+}
+
+/// Decrypts message content encrypted by seal_message, along with the owner and generation of
+/// the key it was encrypted with
+fn open_message(content_key: &[u8; 32], id: u64, sealed: &[u8]) -> StdResult<(CanonicalAddr, u32, Vec<u8>)> {
+    let (owner, generation, content) = open_message_header(content_key, id, sealed)?;
+    let key = recipient_key(content_key, &owner, generation);
+    let content = open(&key, &id.to_be_bytes(), content)?;
+    Ok((owner, generation, content))
+}
+
+/// Derives a stable pseudonym for an address within `context`. Without the contract secret it
+/// cannot be linked to the address or to the pseudonyms of the address in other contexts
+pub fn derive_pseudonym(secret: &[u8], address: &CanonicalAddr, context: &[u8]) -> [u8; 32] {
+    sha_256(&[secret, b"pseudonym", address.as_slice(), context].concat())
+}
+
+/// Returns the seed for generated viewing keys. Instances created before the seed was stored
+/// start from a key derived from the contract secret
+pub fn read_prng_seed<S: ReadonlyStorage>(storage: &S) -> StdResult<Vec<u8>> {
+    match may_load(storage, PRNG_SEED_KEY)? {
+        Some(seed) => Ok(seed),
+        None => {
+            let secret: Vec<u8> = load(storage, CONTRACT_SECRET_KEY)?;
+            Ok(derive_key(&secret, b"prng").to_vec())
+        }
+    }
+}
+
+pub fn write_prng_seed<S: Storage>(storage: &mut S, seed: &[u8]) -> StdResult<()> {
+    save(storage, PRNG_SEED_KEY, &seed.to_vec())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Message {
+    /// content, padded with zeros to a storage bucket size
+    pub content: Vec<u8>,
+    /// length of the content without padding
+    pub content_size: u32,
+    /// address of the sender
+    pub from: CanonicalAddr,
+    /// block height when the message was sent
+    pub height: u64,
+    /// block time (seconds) when the message was sent
+    pub timestamp: u64,
+    /// sender-assigned priority, 0 is the default
+    pub priority: u8,
+    /// sender-assigned content type, e.g. a mime type
+    pub content_type: Option<String>,
+    /// set for messages generated by the contract instead of a sender, content is empty
+    pub system: Option<SystemMessage>,
+    /// if set, the message is hidden from the recipient until the chain passes this height
+    pub deliver_after_height: Option<u64>,
+    /// if set, the content is refused to the recipient until the chain passes this height
+    pub reveal_after_height: Option<u64>,
+    /// if set, the message is deleted unread once the chain passes this height
+    pub expire_after_height: Option<u64>,
+    /// block height of the commitment the message was revealed from, for commit-reveal sends
+    pub committed_height: Option<u64>,
+    /// if set, the recipient snoozed the message until the chain passes this height
+    pub snoozed_until: Option<u64>,
+    /// pinned by the recipient, kept when the queue is full or cleared
+    pub pinned: bool,
+    /// flagged by the recipient, exempt from the default TTL
+    pub flagged: bool,
+    /// the sender is hidden from the recipient, who can answer with a reply token instead
+    pub anonymous: bool,
+    /// number of reads left before the message explodes, None if the first read explodes it
+    pub views_left: Option<u32>,
+    /// the content stays sealed until reveal_after_height and the sender cannot edit it
+    pub time_capsule: bool,
+    /// the recipient has to sign the message id with their signing key to read the message
+    pub signed_read: bool,
+    /// the sender gets a read receipt NFT when the message explodes
+    pub mint_receipt: bool,
+    /// true if expire_after_height was set from the deployment's default_ttl_blocks
+    pub expires_by_default: bool,
+    /// postage paid by the sender, paid out to the recipient when the message is read
+    pub fee: Option<Fee>,
+    /// sender-assigned tags the recipient can filter by
+    pub tags: Vec<String>,
+    /// true once the final chunk of the content was fetched with a chunked receive
+    pub fetched: bool,
+    /// true once a read that did not explode the message returned it
+    pub viewed: bool,
+    /// id of prev message, 0 means first in queue
+    pub prev: u64,
+    /// id of next message in queue, 0 means last in queue
+    pub next: u64,
+}
+
+impl Message {
+    /// Returns a message with default settings, not yet linked into a queue
+    pub fn new(content: Vec<u8>, from: CanonicalAddr, height: u64, timestamp: u64) -> Self {
+        Message {
+            content_size: content.len() as u32,
+            content,
+            from,
+            height,
+            timestamp,
+            priority: 0,
+            content_type: None,
+            system: None,
+            deliver_after_height: None,
+            reveal_after_height: None,
+            expire_after_height: None,
+            committed_height: None,
+            snoozed_until: None,
+            pinned: false,
+            flagged: false,
+            anonymous: false,
+            views_left: None,
+            time_capsule: false,
+            signed_read: false,
+            mint_receipt: false,
+            expires_by_default: false,
+            fee: None,
+            tags: vec![],
+            fetched: false,
+            viewed: false,
+            prev: 0,
+            next: 0,
+        }
+    }
+
+    /// Returns the sender the per-sender index counts the message under. All anonymous messages
+    /// share the anonymous sender, so the index does not reveal who sent them.
+    pub fn index_sender(&self) -> CanonicalAddr {
+        if self.anonymous {
+            anonymous_sender()
+        } else {
+            self.from.clone()
+        }
+    }
+
+    /// Returns true if the next read explodes the message
+    pub fn is_last_view(&self) -> bool {
+        !matches!(self.views_left, Some(views) if views > 1)
+    }
+
+    /// Returns the content without padding
+    pub fn payload(&self) -> &[u8] {
+        let end = (self.content_size as usize).min(self.content.len());
+        &self.content[..end]
+    }
+
+    /// Replaces the content, padding it to the smallest of `buckets` (sorted ascending) that fits.
+    /// Content larger than every bucket is padded to a multiple of the largest one.
+    pub fn set_content(&mut self, content: Vec<u8>, buckets: &[u32]) {
+        self.content_size = content.len() as u32;
+        self.content = padded(content, buckets);
+    }
+
+    /// Returns true if the message can be received at the given block height
+    pub fn is_visible(&self, height: u64) -> bool {
+        let delivered = match self.deliver_after_height {
+            Some(deliver_after_height) => height > deliver_after_height,
+            None => true,
+        };
+        let awake = match self.snoozed_until {
+            Some(snoozed_until) => height > snoozed_until,
+            None => true,
+        };
+        delivered && awake && !self.is_expired(height)
+    }
+
+    /// Returns true if the message has expired at the given block height
+    pub fn is_expired(&self, height: u64) -> bool {
+        match self.expire_after_height {
+            Some(_) if self.flagged && self.expires_by_default => false,
+            Some(expire_after_height) => height > expire_after_height,
+            None => false,
+        }
+    }
+
+    /// Returns true if the content cannot be revealed yet at the given block height
+    pub fn is_sealed(&self, height: u64) -> bool {
+        match self.reveal_after_height {
+            Some(reveal_after_height) => height <= reveal_after_height,
+            None => false,
+        }
+    }
+}
+
+/// compact messages generated by the contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub enum SystemMessage {
+    /// the recipient of message `message_id` reacted to it
+    Reaction {
+        message_id: u64,
+        reaction: String,
+    },
+    /// a message the recipient of this notice sent at `sent_height` was evicted unread from the
+    /// queue of the notice's sender
+    Evicted {
+        sent_height: u64,
+        sent_timestamp: u64,
+    },
+    /// a message the recipient of this notice sent at `sent_height` was not delivered because the
+    /// queue of the notice's sender was full
+    Bounced {
+        sent_height: u64,
+        sent_timestamp: u64,
+    },
+    /// the sender of the message asks the recipient to answer poll `poll_id`
+    Poll {
+        poll_id: u64,
+    },
+}
+
+/// Pads content with zeros to the smallest of `buckets` (sorted ascending) that fits, or to a
+/// multiple of the largest one. Content is returned as is if there are no buckets.
+pub fn padded(mut content: Vec<u8>, buckets: &[u32]) -> Vec<u8> {
+    if let Some(largest) = buckets.last() {
+        let largest = *largest as usize;
+        let padded_size = match buckets.iter().find(|b| **b as usize >= content.len()) {
+            Some(bucket) => *bucket as usize,
+            // content is longer than the largest bucket here, so it is never empty
+            None => ((content.len() - 1) / largest + 1) * largest,
+        };
+        content.resize(padded_size, 0);
+    }
+    content
+}
+
+/// messages by id, with the content encrypted with the current key of the recipient
+pub struct MessageStorage<'a, S: Storage> {
+    storage: &'a mut S,
+    key: [u8; 32],
+}
+
+impl<'a, S: Storage> MessageStorage<'a, S> {
+    pub fn from_storage(storage: &'a mut S) -> StdResult<Self> {
+        let key = content_key(storage)?;
+        Ok(Self { storage, key })
+    }
+
+    fn as_readonly(&self) -> ReadonlyMessageStorageImpl<'_, S> {
+        ReadonlyMessageStorageImpl(self.storage, self.key)
+    }
+
+    fn write_message(&mut self, key: &u64, mut mes: Message, owner: &CanonicalAddr) -> StdResult<()> {
+        let generation = read_key_generation(self.storage, owner);
+        mes.content = seal_message(&self.key, *key, owner, generation, &mes.content)?;
+        let mut message_storage = PrefixedStorage::new(MESSAGE_PREFIX, self.storage);
+        save(&mut message_storage, &key.to_be_bytes(), &mes)
+    }
+
+    /// Replaces a stored message, encrypting it with the current key of its recipient
+    pub fn set_message(&mut self, key: &u64, mes: Message) -> StdResult<()> {
+        let owner = self.as_readonly().owner_of(key)?.unwrap_or(CanonicalAddr(Binary(vec![])));
+        self.write_message(key, mes, &owner)
+    }
+
+    pub fn remove_message(&mut self, key: &u64) {
+        remove(&mut PrefixedStorage::new(MESSAGE_PREFIX, self.storage), &key.to_be_bytes());
+    }
+
+    /// Returns a message, re-encrypting it if its recipient rotated their key since it was stored
+    pub fn get_message(&mut self, key: &u64) -> StdResult<Option<Message>> {
+        let (mes, owner, generation) = match self.as_readonly().get_sealed(key)? {
+            Some(sealed) => sealed,
+            None => return Ok(None),
+        };
+        if generation < read_key_generation(self.storage, &owner) {
+            self.write_message(key, mes.clone(), &owner)?;
+        }
+        Ok(Some(mes))
+    }
+
+    /// Appends a message to the rear of the queue, first removing the message chosen by `policy`
+    /// if the queue already holds `max_length` messages. Returns the id and contents of the removed
+    /// message, if any.
+    pub fn push_message<P: Eviction>(
+        &mut self,
+        queue: &mut MessageQueue,
+        owner: &CanonicalAddr,
+        key: u64,
+        mut mes: Message,
+        max_length: u32,
+        policy: &P,
+    ) -> StdResult<Option<(u64, Message)>> {
+        let mut evicted: Option<(u64, Message)> = None;
+        if queue.length >= max_length {
+            match policy.victim(&*self.storage, queue)? {
+                Some((victim_id, victim_message)) => {
+                    self.unlink_message(queue, &victim_id, &victim_message)?;
+                    evicted = Some((victim_id, victim_message));
+                }
+                // callers check the policy before pushing to a full queue
+                None => return Err(StdError::generic_err("Message queue is full.")),
+            }
+        }
+
+        if let Some(mut rear_message) = self.get_message(&queue.rear)? {
+            rear_message.next = key;
+            self.set_message(&queue.rear, rear_message)?;
+            mes.prev = queue.rear;
+        } else {
+            // message is first entry in queue
+            queue.front = key;
+        }
+        self.write_message(&key, mes, owner)?;
+        queue.rear = key;
+        queue.length += 1;
+        Ok(evicted)
+    }
+
+    /// Moves a message from anywhere in the queue to its front, relinking its neighbours
+    pub fn move_to_front(&mut self, queue: &mut MessageQueue, key: &u64, mut mes: Message) -> StdResult<()> {
+        if *key != queue.front {
+            if let Some(mut prev_message) = self.get_message(&mes.prev)? {
+                prev_message.next = mes.next;
+                self.set_message(&mes.prev, prev_message)?;
+            }
+            if *key == queue.rear {
+                queue.rear = mes.prev;
+            } else if let Some(mut next_message) = self.get_message(&mes.next)? {
+                next_message.prev = mes.prev;
+                self.set_message(&mes.next, next_message)?;
+            }
+            if let Some(mut front_message) = self.get_message(&queue.front)? {
+                front_message.prev = *key;
+                self.set_message(&queue.front, front_message)?;
+            }
+            mes.prev = 0;
+            mes.next = queue.front;
+            queue.front = *key;
+        }
+        self.set_message(key, mes)
+    }
+
+    /// Removes a message from anywhere in the queue, relinking its neighbours
+    pub fn unlink_message(&mut self, queue: &mut MessageQueue, key: &u64, mes: &Message) -> StdResult<()> {
+        self.detach_message(queue, key, mes)?;
+        self.remove_message(key);
+        Ok(())
+    }
+
+    /// Takes a message out of the queue, relinking its neighbours, but keeps it stored so it can
+    /// be pushed to another queue
+    pub fn detach_message(&mut self, queue: &mut MessageQueue, key: &u64, mes: &Message) -> StdResult<()> {
+        let is_front = *key == queue.front;
+        let is_rear = *key == queue.rear;
+
+        if is_front {
+            queue.front = mes.next;
+        } else if let Some(mut prev_message) = self.get_message(&mes.prev)? {
+            prev_message.next = mes.next;
+            self.set_message(&mes.prev, prev_message)?;
+        }
+
+        if is_rear {
+            queue.rear = if is_front { 0 } else { mes.prev };
+        } else if let Some(mut next_message) = self.get_message(&mes.next)? {
+            next_message.prev = if is_front { 0 } else { mes.prev };
+            self.set_message(&mes.next, next_message)?;
+        }
+
+        queue.length -= 1;
+        Ok(())
+    }
+}
+
+pub struct ReadonlyMessageStorage<'a, S: ReadonlyStorage> {
+    storage: &'a S,
+    key: [u8; 32],
+}
+
+impl<'a, S: ReadonlyStorage> ReadonlyMessageStorage<'a, S> {
+    pub fn from_storage(storage: &'a S) -> StdResult<Self> {
+        Ok(Self {
+            storage,
+            key: content_key(storage)?,
+        })
+    }
+
+    fn as_readonly(&self) -> ReadonlyMessageStorageImpl<'_, S> {
+        ReadonlyMessageStorageImpl(self.storage, self.key)
+    }
+
+    pub fn get_message(&self, key: &u64) -> StdResult<Option<Message>> {
+        self.as_readonly().get(key)
+    }
+
+    /// Returns the recipient of a message without decrypting its content
+    pub fn owner_of(&self, key: &u64) -> StdResult<Option<CanonicalAddr>> {
+        self.as_readonly().owner_of(key)
+    }
+}
+
+struct ReadonlyMessageStorageImpl<'a, S: ReadonlyStorage>(&'a S, [u8; 32]);
+
+impl<'a, S: ReadonlyStorage> ReadonlyMessageStorageImpl<'a, S> {
+    fn load(&self, key: &u64) -> Option<Message> {
+        let message_storage = ReadonlyPrefixedStorage::new(MESSAGE_PREFIX, self.0);
+        may_load(&message_storage, &key.to_be_bytes()).ok().unwrap()
+    }
+
+    pub fn get(&self, key: &u64) -> StdResult<Option<Message>> {
+        Ok(self.get_sealed(key)?.map(|(mes, _, _)| mes))
+    }
+
+    /// Returns a message with the recipient and generation of the key it was encrypted with
+    pub fn get_sealed(&self, key: &u64) -> StdResult<Option<(Message, CanonicalAddr, u32)>> {
+        match self.load(key) {
+            Some(mut mes) => {
+                let (owner, generation, content) = open_message(&self.1, *key, &mes.content)?;
+                mes.content = content;
+                Ok(Some((mes, owner, generation)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the recipient of a message without decrypting its content
+    pub fn owner_of(&self, key: &u64) -> StdResult<Option<CanonicalAddr>> {
+        match self.load(key) {
+            Some(mes) => Ok(Some(open_message_header(&self.1, *key, &mes.content)?.0)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// hot queue pointers, rewritten on every send and receive; keep this record small and put
+/// per-user settings in UserSettings instead
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct MessageQueue {
+    /// id of front message
+    pub front: u64,
+    /// id of end message
+    pub rear: u64,
+    /// length of queue
+    pub length: u32,
+}
+
+pub struct MessageQueueStorage<'a, S: Storage> {
+    storage: PrefixedStorage<'a, S>,
+}
+
+impl<'a, S: Storage> MessageQueueStorage<'a, S> {
+    pub fn from_storage(storage: &'a mut S) -> Self {
+        Self {
+            storage: PrefixedStorage::new(MESSAGE_QUEUE_PREFIX, storage),
+        }
+    }
+
+    fn as_readonly(&self) -> ReadonlyMessageQueueStorageImpl<PrefixedStorage<S>> {
+        ReadonlyMessageQueueStorageImpl(&self.storage)
+    }
+
+    /// empty queues are not stored
+    pub fn set_message_queue(&mut self, key: &CanonicalAddr, queue: MessageQueue) {
+        if queue.length == 0 {
+            remove(&mut self.storage, key.as_slice());
+        } else {
+            save(&mut self.storage, key.as_slice(), &queue).ok();
+        }
+    }
+
+    pub fn get_message_queue(&mut self, key: &CanonicalAddr) -> MessageQueue {
+        self.as_readonly().get(key)
+    }
+}
+
+pub struct ReadonlyMessageQueueStorage<'a, S: ReadonlyStorage> {
+    storage: ReadonlyPrefixedStorage<'a, S>,
+}
+
+impl<'a, S: ReadonlyStorage> ReadonlyMessageQueueStorage<'a, S> {
+    pub fn from_storage(storage: &'a S) -> Self {
+        Self {
+            storage: ReadonlyPrefixedStorage::new(MESSAGE_QUEUE_PREFIX, storage),
+        }
+    }
+
+    fn as_readonly(&self) -> ReadonlyMessageQueueStorageImpl<'_, ReadonlyPrefixedStorage<'_, S>> {
+        ReadonlyMessageQueueStorageImpl(&self.storage)
+    }
+
+    pub fn get_message_queue(&self, key: &CanonicalAddr) -> MessageQueue {
+        self.as_readonly().get(key)
+    }
+}
+
+struct ReadonlyMessageQueueStorageImpl<'a, S: ReadonlyStorage>(&'a S);
+
+impl<'a, S: ReadonlyStorage> ReadonlyMessageQueueStorageImpl<'a, S> {
+    pub fn get(&self, key: &CanonicalAddr) -> MessageQueue {
+        let queue: Option<MessageQueue> = may_load(self.0, &key.as_slice().to_vec()).ok().unwrap();
+        queue.unwrap_or_default()
+    }
+}
+
+/// per-sender index entry of a recipient's queue
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Conversation {
+    /// address of the sender
+    pub sender: CanonicalAddr,
+    /// number of unread messages from the sender
+    pub unread: u32,
+    /// block time (seconds) of the latest unread message from the sender
+    pub latest_timestamp: u64,
+}
+
+pub struct ConversationStorage<'a, S: Storage> {
+    storage: PrefixedStorage<'a, S>,
+}
+
+impl<'a, S: Storage> ConversationStorage<'a, S> {
+    pub fn from_storage(storage: &'a mut S) -> Self {
+        Self {
+            storage: PrefixedStorage::new(CONVERSATION_PREFIX, storage),
+        }
+    }
+
+    fn as_readonly(&self) -> ReadonlyConversationStorageImpl<'_, PrefixedStorage<'_, S>> {
+        ReadonlyConversationStorageImpl(&self.storage)
+    }
+
+    pub fn set_conversations(&mut self, key: &CanonicalAddr, conversations: Vec<Conversation>) {
+        if conversations.is_empty() {
+            remove(&mut self.storage, key.as_slice());
+        } else {
+            save(&mut self.storage, key.as_slice(), &conversations).ok();
+        }
+    }
+
+    pub fn get_conversations(&mut self, key: &CanonicalAddr) -> Vec<Conversation> {
+        self.as_readonly().get(key)
+    }
+
+    /// records a new message from `sender` in the index of `recipient`
+    pub fn add_message(&mut self, recipient: &CanonicalAddr, sender: &CanonicalAddr, timestamp: u64) {
+        let mut conversations = self.get_conversations(recipient);
+        if let Some(conversation) = conversations.iter_mut().find(|c| &c.sender == sender) {
+            conversation.unread += 1;
+            conversation.latest_timestamp = timestamp;
+        } else {
+            conversations.push(Conversation {
+                sender: sender.clone(),
+                unread: 1,
+                latest_timestamp: timestamp,
+            });
+        }
+        self.set_conversations(recipient, conversations);
+    }
+
+    /// records that a message from `sender` left the queue of `recipient`
+    pub fn remove_message(&mut self, recipient: &CanonicalAddr, sender: &CanonicalAddr) {
+        let mut conversations = self.get_conversations(recipient);
+        if let Some(conversation) = conversations.iter_mut().find(|c| &c.sender == sender) {
+            conversation.unread = conversation.unread.saturating_sub(1);
+        }
+        conversations.retain(|c| c.unread > 0);
+        self.set_conversations(recipient, conversations);
+    }
+}
+
+pub struct ReadonlyConversationStorage<'a, S: ReadonlyStorage> {
+    storage: ReadonlyPrefixedStorage<'a, S>,
+}
+
+impl<'a, S: ReadonlyStorage> ReadonlyConversationStorage<'a, S> {
+    pub fn from_storage(storage: &'a S) -> Self {
+        Self {
+            storage: ReadonlyPrefixedStorage::new(CONVERSATION_PREFIX, storage),
+        }
+    }
+
+    fn as_readonly(&self) -> ReadonlyConversationStorageImpl<'_, ReadonlyPrefixedStorage<'_, S>> {
+        ReadonlyConversationStorageImpl(&self.storage)
+    }
+
+    pub fn get_conversations(&self, key: &CanonicalAddr) -> Vec<Conversation> {
+        self.as_readonly().get(key)
+    }
+}
+
+struct ReadonlyConversationStorageImpl<'a, S: ReadonlyStorage>(&'a S);
+
+impl<'a, S: ReadonlyStorage> ReadonlyConversationStorageImpl<'a, S> {
+    pub fn get(&self, key: &CanonicalAddr) -> Vec<Conversation> {
+        let conversations: Option<Vec<Conversation>> = may_load(self.0, key.as_slice()).ok().unwrap();
+        conversations.unwrap_or_default()
+    }
+}
+
+/// record of a message kept in the sender's sent items folder
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SentItem {
+    /// id of the message
+    pub id: u64,
+    /// address of the recipient
+    pub to: CanonicalAddr,
+    /// size of the content in bytes
+    pub size: u32,
+    /// sha256 hash of the content
+    pub content_hash: Vec<u8>,
+    /// block time (seconds) when the message was sent
+    pub timestamp: u64,
+}
+
+pub struct SentItemsStorage<'a, S: Storage> {
+    storage: PrefixedStorage<'a, S>,
+}
+
+impl<'a, S: Storage> SentItemsStorage<'a, S> {
+    pub fn from_storage(storage: &'a mut S) -> Self {
+        Self {
+            storage: PrefixedStorage::new(SENT_ITEMS_PREFIX, storage),
+        }
+    }
+
+    fn as_readonly(&self) -> ReadonlySentItemsStorageImpl<'_, PrefixedStorage<'_, S>> {
+        ReadonlySentItemsStorageImpl(&self.storage)
+    }
+
+    pub fn set_sent_items(&mut self, key: &CanonicalAddr, items: Vec<SentItem>) {
+        if items.is_empty() {
+            remove(&mut self.storage, key.as_slice());
+        } else {
+            save(&mut self.storage, key.as_slice(), &items).ok();
+        }
+    }
+
+    pub fn get_sent_items(&mut self, key: &CanonicalAddr) -> Vec<SentItem> {
+        self.as_readonly().get(key)
+    }
+
+    /// replaces the size and hash recorded for the item with the given id, if it is still in the folder
+    pub fn update_sent_item(&mut self, key: &CanonicalAddr, id: u64, size: u32, content_hash: Vec<u8>) {
+        let mut items = self.get_sent_items(key);
+        if let Some(item) = items.iter_mut().find(|item| item.id == id) {
+            item.size = size;
+            item.content_hash = content_hash;
+            self.set_sent_items(key, items);
+        }
+    }
+
+    /// appends an item to the folder, dropping the oldest items beyond `cap`
+    pub fn push_sent_item(&mut self, key: &CanonicalAddr, item: SentItem, cap: u32) {
+        let mut items = self.get_sent_items(key);
+        items.push(item);
+        let cap = cap as usize;
+        if items.len() > cap {
+            items.drain(0..items.len() - cap);
+        }
+        self.set_sent_items(key, items);
+    }
+}
+
+struct ReadonlySentItemsStorageImpl<'a, S: ReadonlyStorage>(&'a S);
+
+impl<'a, S: ReadonlyStorage> ReadonlySentItemsStorageImpl<'a, S> {
+    pub fn get(&self, key: &CanonicalAddr) -> Vec<SentItem> {
+        let items: Option<Vec<SentItem>> = may_load(self.0, key.as_slice()).ok().unwrap();
+        items.unwrap_or_default()
+    }
+}
+
+/// record of a message that was received and can still be reacted to
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceivedMessage {
+    /// id of the message
+    pub id: u64,
+    /// address of the sender
+    pub from: CanonicalAddr,
+}
+
+pub struct ReceivedStorage<'a, S: Storage> {
+    storage: PrefixedStorage<'a, S>,
+}
+
+impl<'a, S: Storage> ReceivedStorage<'a, S> {
+    pub fn from_storage(storage: &'a mut S) -> Self {
+        Self {
+            storage: PrefixedStorage::new(RECEIVED_PREFIX, storage),
+        }
+    }
+
+    fn as_readonly(&self) -> ReadonlyReceivedStorageImpl<'_, PrefixedStorage<'_, S>> {
+        ReadonlyReceivedStorageImpl(&self.storage)
+    }
+
+    pub fn set_received(&mut self, key: &CanonicalAddr, received: Vec<ReceivedMessage>) {
+        if received.is_empty() {
+            remove(&mut self.storage, key.as_slice());
+        } else {
+            save(&mut self.storage, key.as_slice(), &received).ok();
+        }
+    }
+
+    pub fn get_received(&mut self, key: &CanonicalAddr) -> Vec<ReceivedMessage> {
+        self.as_readonly().get(key)
+    }
+
+    /// remembers a received message, forgetting the oldest beyond MAX_RECENTLY_RECEIVED
+    pub fn push_received(&mut self, key: &CanonicalAddr, received_message: ReceivedMessage) {
+        let mut received = self.get_received(key);
+        received.push(received_message);
+        let cap = MAX_RECENTLY_RECEIVED as usize;
+        if received.len() > cap {
+            received.drain(0..received.len() - cap);
+        }
+        self.set_received(key, received);
+    }
+
+    /// forgets and returns the received message with the given id
+    pub fn take_received(&mut self, key: &CanonicalAddr, id: u64) -> Option<ReceivedMessage> {
+        let mut received = self.get_received(key);
+        let position = received.iter().position(|r| r.id == id)?;
+        let received_message = received.remove(position);
+        self.set_received(key, received);
+        Some(received_message)
+    }
+}
+
+struct ReadonlyReceivedStorageImpl<'a, S: ReadonlyStorage>(&'a S);
+
+impl<'a, S: ReadonlyStorage> ReadonlyReceivedStorageImpl<'a, S> {
+    pub fn get(&self, key: &CanonicalAddr) -> Vec<ReceivedMessage> {
+        let received: Option<Vec<ReceivedMessage>> = may_load(self.0, key.as_slice()).ok().unwrap();
+        received.unwrap_or_default()
+    }
+}
+
+/// dead man's switch message held back until its sender stops sending heartbeats
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ScheduledRelease {
+    /// address of the sender
+    pub from: CanonicalAddr,
+    pub content: Vec<u8>,
+    /// sender-assigned priority
+    pub priority: u8,
+    /// sender-assigned content type
+    pub content_type: Option<String>,
+    /// block height when the message was deposited
+    pub height: u64,
+    /// block time (seconds) when the message was deposited
+    pub timestamp: u64,
+    /// number of blocks without a heartbeat from the sender after which the message is released
+    pub release_if_inactive_blocks: u64,
+    /// postage paid by the sender, passed on to the released message
+    pub fee: Option<Fee>,
+    /// spam bond deposited by the sender, held for the released message
+    pub bond: Option<Fee>,
+    /// sender-assigned tags
+    pub tags: Vec<String>,
+    /// held back by the recipient's quiet hours instead of a dead man's switch
+    pub quiet: bool,
+    /// the released message hides its sender
+    pub anonymous: bool,
+    /// number of reads before the released message explodes
+    pub views_left: Option<u32>,
+    /// unlock height if the released message is a time capsule
+    pub unlock_height: Option<u64>,
+    /// the released message needs a read signature
+    pub signed_read: bool,
+    /// the released message mints a read receipt NFT
+    pub mint_receipt: bool,
+}
+
+/// scheduled releases by sender, with the content encrypted with the content key
+pub struct ScheduledReleaseStorage<'a, S: Storage> {
+    storage: PrefixedStorage<'a, S>,
+    key: [u8; 32],
+}
+
+impl<'a, S: Storage> ScheduledReleaseStorage<'a, S> {
+    pub fn from_storage(storage: &'a mut S) -> StdResult<Self> {
+        let key = content_key(storage)?;
+        Ok(Self {
+            storage: PrefixedStorage::new(SCHEDULED_RELEASE_PREFIX, storage),
+            key,
+        })
+    }
+
+    fn as_readonly(&self) -> ReadonlyScheduledReleaseStorageImpl<'_, PrefixedStorage<'_, S>> {
+        ReadonlyScheduledReleaseStorageImpl(&self.storage, self.key)
+    }
+
+    pub fn set_releases(&mut self, key: &CanonicalAddr, mut releases: Vec<ScheduledRelease>) -> StdResult<()> {
+        if releases.is_empty() {
+            remove(&mut self.storage, key.as_slice());
+            Ok(())
+        } else {
+            for release in releases.iter_mut() {
+                release.content = seal(&self.key, key.as_slice(), &release.content)?;
+            }
+            save(&mut self.storage, key.as_slice(), &releases)
+        }
+    }
+
+    pub fn get_releases(&mut self, key: &CanonicalAddr) -> StdResult<Vec<ScheduledRelease>> {
+        self.as_readonly().get(key)
+    }
+}
+
+struct ReadonlyScheduledReleaseStorageImpl<'a, S: ReadonlyStorage>(&'a S, [u8; 32]);
+
+impl<'a, S: ReadonlyStorage> ReadonlyScheduledReleaseStorageImpl<'a, S> {
+    pub fn get(&self, key: &CanonicalAddr) -> StdResult<Vec<ScheduledRelease>> {
+        let releases: Option<Vec<ScheduledRelease>> = may_load(self.0, key.as_slice())?;
+        let mut releases = releases.unwrap_or_default();
+        for release in releases.iter_mut() {
+            release.content = open(&self.1, key.as_slice(), &release.content)?;
+        }
+        Ok(releases)
+    }
+}
+
+/// commitment to a message that will be revealed and sent later
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Commitment {
+    /// sha256 hash of the content followed by the nonce
+    pub content_hash: Vec<u8>,
+    /// address of the recipient
+    pub to: CanonicalAddr,
+    /// block height when the commitment was made
+    pub height: u64,
+}
+
+pub struct CommitmentStorage<'a, S: Storage> {
+    storage: PrefixedStorage<'a, S>,
+}
+
+impl<'a, S: Storage> CommitmentStorage<'a, S> {
+    pub fn from_storage(storage: &'a mut S) -> Self {
+        Self {
+            storage: PrefixedStorage::new(COMMITMENT_PREFIX, storage),
+        }
+    }
+
+    fn as_readonly(&self) -> ReadonlyCommitmentStorageImpl<'_, PrefixedStorage<'_, S>> {
+        ReadonlyCommitmentStorageImpl(&self.storage)
+    }
+
+    pub fn set_commitments(&mut self, key: &CanonicalAddr, commitments: Vec<Commitment>) {
+        if commitments.is_empty() {
+            remove(&mut self.storage, key.as_slice());
+        } else {
+            save(&mut self.storage, key.as_slice(), &commitments).ok();
+        }
+    }
+
+    pub fn get_commitments(&mut self, key: &CanonicalAddr) -> Vec<Commitment> {
+        self.as_readonly().get(key)
+    }
+
+    /// forgets and returns the commitment with the given hash
+    pub fn take_commitment(&mut self, key: &CanonicalAddr, content_hash: &[u8]) -> Option<Commitment> {
+        let mut commitments = self.get_commitments(key);
+        let position = commitments.iter().position(|c| c.content_hash.as_slice() == content_hash)?;
+        let commitment = commitments.remove(position);
+        self.set_commitments(key, commitments);
+        Some(commitment)
+    }
+}
+
+struct ReadonlyCommitmentStorageImpl<'a, S: ReadonlyStorage>(&'a S);
+
+impl<'a, S: ReadonlyStorage> ReadonlyCommitmentStorageImpl<'a, S> {
+    pub fn get(&self, key: &CanonicalAddr) -> Vec<Commitment> {
+        let commitments: Option<Vec<Commitment>> = may_load(self.0, key.as_slice()).ok().unwrap();
+        commitments.unwrap_or_default()
+    }
+}
+
+/// actions a session key may take on behalf of its grantor
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionPermission {
+    Send,
+    Recv,
+}
+
+/// temporary key allowed to act on behalf of the address that granted it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Session {
+    /// secp256k1 public key of the session
+    pub pubkey: Vec<u8>,
+    /// block height after which the session is no longer valid
+    pub expires: u64,
+    pub permissions: Vec<SessionPermission>,
+}
+
+pub struct SessionStorage<'a, S: Storage> {
+    storage: PrefixedStorage<'a, S>,
+}
+
+impl<'a, S: Storage> SessionStorage<'a, S> {
+    pub fn from_storage(storage: &'a mut S) -> Self {
+        Self {
+            storage: PrefixedStorage::new(SESSION_PREFIX, storage),
+        }
+    }
+
+    fn as_readonly(&self) -> ReadonlySessionStorageImpl<'_, PrefixedStorage<'_, S>> {
+        ReadonlySessionStorageImpl(&self.storage)
+    }
+
+    pub fn set_sessions(&mut self, key: &CanonicalAddr, sessions: Vec<Session>) {
+        if sessions.is_empty() {
+            remove(&mut self.storage, key.as_slice());
+        } else {
+            save(&mut self.storage, key.as_slice(), &sessions).ok();
+        }
+    }
+
+    pub fn get_sessions(&mut self, key: &CanonicalAddr) -> Vec<Session> {
+        self.as_readonly().get(key)
+    }
+}
+
+struct ReadonlySessionStorageImpl<'a, S: ReadonlyStorage>(&'a S);
+
+impl<'a, S: ReadonlyStorage> ReadonlySessionStorageImpl<'a, S> {
+    pub fn get(&self, key: &CanonicalAddr) -> Vec<Session> {
+        let sessions: Option<Vec<Session>> = may_load(self.0, key.as_slice()).ok().unwrap();
+        sessions.unwrap_or_default()
+    }
+}
+
+/// chunked transfer that has not received all of its chunks yet
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingTransfer {
+    /// sender-chosen id of the transfer
+    pub transfer_id: u64,
+    /// address of the recipient
+    pub to: CanonicalAddr,
+    /// flags of the chunks received so far, one per chunk
+    pub received: Vec<bool>,
+    /// block height when the latest chunk arrived
+    pub last_update_height: u64,
+}
+
+impl PendingTransfer {
+    pub fn is_complete(&self) -> bool {
+        self.received.iter().all(|r| *r)
+    }
+}
+
+pub struct TransferStorage<'a, S: Storage> {
+    storage: PrefixedStorage<'a, S>,
+}
+
+impl<'a, S: Storage> TransferStorage<'a, S> {
+    pub fn from_storage(storage: &'a mut S) -> Self {
+        Self {
+            storage: PrefixedStorage::new(TRANSFER_PREFIX, storage),
+        }
+    }
+
+    fn as_readonly(&self) -> ReadonlyTransferStorageImpl<'_, PrefixedStorage<'_, S>> {
+        ReadonlyTransferStorageImpl(&self.storage)
+    }
+
+    pub fn set_transfers(&mut self, key: &CanonicalAddr, transfers: Vec<PendingTransfer>) {
+        if transfers.is_empty() {
+            remove(&mut self.storage, key.as_slice());
+        } else {
+            save(&mut self.storage, key.as_slice(), &transfers).ok();
+        }
+    }
+
+    pub fn get_transfers(&mut self, key: &CanonicalAddr) -> Vec<PendingTransfer> {
+        self.as_readonly().get(key)
+    }
+}
+
+struct ReadonlyTransferStorageImpl<'a, S: ReadonlyStorage>(&'a S);
+
+impl<'a, S: ReadonlyStorage> ReadonlyTransferStorageImpl<'a, S> {
+    pub fn get(&self, key: &CanonicalAddr) -> Vec<PendingTransfer> {
+        let transfers: Option<Vec<PendingTransfer>> = may_load(self.0, key.as_slice()).ok().unwrap();
+        transfers.unwrap_or_default()
+    }
+}
+
+fn chunk_key(sender: &CanonicalAddr, transfer_id: u64, index: u32) -> Vec<u8> {
+    let mut key = sender.as_slice().to_vec();
+    key.extend_from_slice(&transfer_id.to_be_bytes());
+    key.extend_from_slice(&index.to_be_bytes());
+    key
+}
+
+/// Stores the data of one chunk of a transfer
+pub fn write_chunk<S: Storage>(storage: &mut S, sender: &CanonicalAddr, transfer_id: u64, index: u32, data: &[u8]) -> StdResult<()> {
+    let content_key = content_key(storage)?;
+    let key = chunk_key(sender, transfer_id, index);
+    let mut chunk_storage = PrefixedStorage::new(CHUNK_PREFIX, storage);
+    chunk_storage.set(&key, &seal(&content_key, &key, data)?);
+    Ok(())
+}
+
+/// Removes and returns the data of one chunk of a transfer
+pub fn take_chunk<S: Storage>(storage: &mut S, sender: &CanonicalAddr, transfer_id: u64, index: u32) -> StdResult<Option<Vec<u8>>> {
+    let content_key = content_key(storage)?;
+    let mut chunk_storage = PrefixedStorage::new(CHUNK_PREFIX, storage);
+    let key = chunk_key(sender, transfer_id, index);
+    let data = chunk_storage.get(&key);
+    chunk_storage.remove(&key);
+    data.map(|data| open(&content_key, &key, &data)).transpose()
+}
+
+/// Removes the data of one chunk of a transfer without decrypting it
+pub fn remove_chunk<S: Storage>(storage: &mut S, sender: &CanonicalAddr, transfer_id: u64, index: u32) {
+    let mut chunk_storage = PrefixedStorage::new(CHUNK_PREFIX, storage);
+    chunk_storage.remove(&chunk_key(sender, transfer_id, index));
+}
+
+/// Stores the block height of the latest heartbeat from an address
+pub fn write_heartbeat<S: Storage>(storage: &mut S, owner: &CanonicalAddr, height: u64) -> StdResult<()> {
+    let mut heartbeat_storage = PrefixedStorage::new(HEARTBEAT_PREFIX, storage);
+    save(&mut heartbeat_storage, owner.as_slice(), &height)
+}
+
+/// Returns the block height of the latest heartbeat from an address, 0 if there was none
+pub fn read_heartbeat<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> StdResult<u64> {
+    let heartbeat_storage = ReadonlyPrefixedStorage::new(HEARTBEAT_PREFIX, storage);
+    let height: Option<u64> = may_load(&heartbeat_storage, owner.as_slice())?;
+    Ok(height.unwrap_or_default())
+}
+
+/// Stores the next sequence number for messages to an address
+pub fn write_sequence<S: Storage>(storage: &mut S, owner: &CanonicalAddr, seq: u64) -> StdResult<()> {
+    let mut sequence_storage = PrefixedStorage::new(SEQUENCE_PREFIX, storage);
+    save(&mut sequence_storage, owner.as_slice(), &seq)
+}
+
+/// Returns the next sequence number for messages to an address, None if it never received one
+pub fn read_sequence<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> StdResult<Option<u64>> {
+    let sequence_storage = ReadonlyPrefixedStorage::new(SEQUENCE_PREFIX, storage);
+    may_load(&sequence_storage, owner.as_slice())
+}
+
+/// Stores the code hash of a contract that wants to be notified of messages sent to it
+pub fn write_receiver<S: Storage>(storage: &mut S, owner: &CanonicalAddr, code_hash: &str) -> StdResult<()> {
+    let mut receiver_storage = PrefixedStorage::new(RECEIVER_PREFIX, storage);
+    save(&mut receiver_storage, owner.as_slice(), &code_hash.to_string())
+}
+
+/// Returns the registered code hash of an address, None if it did not register a receiver
+pub fn read_receiver<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> StdResult<Option<String>> {
+    let receiver_storage = ReadonlyPrefixedStorage::new(RECEIVER_PREFIX, storage);
+    may_load(&receiver_storage, owner.as_slice())
+}
+
+fn tag_key(recipient: &CanonicalAddr, tag: &str) -> Vec<u8> {
+    let mut key = recipient.as_slice().to_vec();
+    key.extend_from_slice(&sha_256(tag.as_bytes()));
+    key
+}
+
+/// folder a recipient created to keep messages apart from their inbox
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Folder {
+    pub name: String,
+    /// maximum number of messages in the folder
+    pub max_messages: u32,
+}
+
+/// Returns the folders of an address, in the order they were created
+pub fn read_folders<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> StdResult<Vec<Folder>> {
+    let folder_storage = ReadonlyPrefixedStorage::new(FOLDERS_PREFIX, storage);
+    Ok(may_load(&folder_storage, owner.as_slice())?.unwrap_or_default())
+}
+
+pub fn write_folders<S: Storage>(storage: &mut S, owner: &CanonicalAddr, folders: &[Folder]) -> StdResult<()> {
+    let mut folder_storage = PrefixedStorage::new(FOLDERS_PREFIX, storage);
+    save(&mut folder_storage, owner.as_slice(), &folders.to_vec())
+}
+
+/// Returns the queue of one of the owner's folders, empty if it holds no messages
+pub fn read_folder_queue<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr, name: &str) -> StdResult<MessageQueue> {
+    let queue_storage = ReadonlyPrefixedStorage::new(FOLDER_QUEUE_PREFIX, storage);
+    Ok(may_load(&queue_storage, &[owner.as_slice(), name.as_bytes()].concat())?.unwrap_or_default())
+}
+
+/// Stores the queue of one of the owner's folders, removing the record once it is empty
+pub fn write_folder_queue<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    name: &str,
+    queue: &MessageQueue,
+) -> StdResult<()> {
+    let mut queue_storage = PrefixedStorage::new(FOLDER_QUEUE_PREFIX, storage);
+    let key = [owner.as_slice(), name.as_bytes()].concat();
+    if queue.length == 0 {
+        queue_storage.remove(&key);
+        Ok(())
+    } else {
+        save(&mut queue_storage, &key, queue)
+    }
+}
+
+/// Stores the ids of the recipient's messages carrying a tag, oldest first, removing the index if empty
+pub fn write_tag_index<S: Storage>(storage: &mut S, recipient: &CanonicalAddr, tag: &str, ids: &[u64]) -> StdResult<()> {
+    let mut tag_storage = PrefixedStorage::new(TAG_INDEX_PREFIX, storage);
+    if ids.is_empty() {
+        tag_storage.remove(&tag_key(recipient, tag));
+        Ok(())
+    } else {
+        save(&mut tag_storage, &tag_key(recipient, tag), &ids.to_vec())
+    }
+}
+
+/// Returns the ids indexed for a tag, which may include messages that already left the queue
+pub fn read_tag_index<S: ReadonlyStorage>(storage: &S, recipient: &CanonicalAddr, tag: &str) -> StdResult<Vec<u64>> {
+    let tag_storage = ReadonlyPrefixedStorage::new(TAG_INDEX_PREFIX, storage);
+    Ok(may_load(&tag_storage, &tag_key(recipient, tag))?.unwrap_or_default())
+}
+
+/// Stores the ids of the recipient's flagged messages, in the order they were flagged, removing
+/// the index if empty
+pub fn write_flagged_index<S: Storage>(storage: &mut S, recipient: &CanonicalAddr, ids: &[u64]) -> StdResult<()> {
+    let mut index_storage = PrefixedStorage::new(FLAGGED_INDEX_PREFIX, storage);
+    if ids.is_empty() {
+        index_storage.remove(recipient.as_slice());
+        Ok(())
+    } else {
+        save(&mut index_storage, recipient.as_slice(), &ids.to_vec())
+    }
+}
+
+/// Returns the ids indexed as flagged, which may include messages that were read or unflagged
+pub fn read_flagged_index<S: ReadonlyStorage>(storage: &S, recipient: &CanonicalAddr) -> StdResult<Vec<u64>> {
+    let index_storage = ReadonlyPrefixedStorage::new(FLAGGED_INDEX_PREFIX, storage);
+    Ok(may_load(&index_storage, recipient.as_slice())?.unwrap_or_default())
+}
+
+/// public record that a message with a salted hash was sent, stored under the hash
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Receipt {
+    /// block height when the message was sent
+    pub height: u64,
+    /// block time (seconds) when the message was sent
+    pub timestamp: u64,
+    /// only revealed to the sender and recipient, who can request inclusion proofs
+    pub sender: CanonicalAddr,
+    pub recipient: CanonicalAddr,
+    /// epoch whose Merkle tree holds the receipt
+    pub epoch: u64,
+    /// position of the receipt in the epoch's tree
+    pub index: u32,
+}
+
+/// receipts folded into one Merkle tree, the open epoch is stored under RECEIPT_EPOCH_KEY
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiptEpoch {
+    pub id: u64,
+    pub start_height: u64,
+    /// height at which the epoch was closed, 0 while it is open
+    pub end_height: u64,
+    /// leaf hashes of the receipts in the order they were stored
+    pub leaves: Vec<[u8; 32]>,
+    /// Merkle root over the leaves, set once the epoch is closed
+    pub root: Option<[u8; 32]>,
+}
+
+pub fn write_closed_epoch<S: Storage>(storage: &mut S, epoch: &ReceiptEpoch) -> StdResult<()> {
+    let mut epoch_storage = PrefixedStorage::new(RECEIPT_EPOCH_PREFIX, storage);
+    save(&mut epoch_storage, &epoch.id.to_be_bytes(), epoch)
+}
+
+/// Returns a closed epoch, None if it does not exist or is still open
+pub fn read_closed_epoch<S: ReadonlyStorage>(storage: &S, id: u64) -> StdResult<Option<ReceiptEpoch>> {
+    let epoch_storage = ReadonlyPrefixedStorage::new(RECEIPT_EPOCH_PREFIX, storage);
+    may_load(&epoch_storage, &id.to_be_bytes())
+}
+
+pub fn write_receipt<S: Storage>(storage: &mut S, hash: &[u8], receipt: &Receipt) -> StdResult<()> {
+    let mut receipt_storage = PrefixedStorage::new(RECEIPT_PREFIX, storage);
+    save(&mut receipt_storage, hash, receipt)
+}
+
+pub fn read_receipt<S: ReadonlyStorage>(storage: &S, hash: &[u8]) -> StdResult<Option<Receipt>> {
+    let receipt_storage = ReadonlyPrefixedStorage::new(RECEIPT_PREFIX, storage);
+    may_load(&receipt_storage, hash)
+}
+
+/// why a message ended up in the dead letters
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadLetterReason {
+    /// dequeued unread to make room in a full queue
+    Evicted,
+    /// expired unread
+    Expired,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DeadLetter {
+    pub message: Message,
+    pub reason: DeadLetterReason,
+}
+
+/// counters of the dead letters a recipient holds, oldest first
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+struct DeadLetterIndex {
+    next: u64,
+    slots: Vec<u64>,
+}
+
+fn dead_letter_key(recipient: &CanonicalAddr, counter: u64) -> Vec<u8> {
+    let mut key = recipient.as_slice().to_vec();
+    key.extend_from_slice(&counter.to_be_bytes());
+    key
+}
+
+fn read_dead_letter_index<S: ReadonlyStorage>(storage: &S, recipient: &CanonicalAddr) -> StdResult<DeadLetterIndex> {
+    let index_storage = ReadonlyPrefixedStorage::new(DEAD_LETTER_INDEX_PREFIX, storage);
+    Ok(may_load(&index_storage, recipient.as_slice())?.unwrap_or_default())
+}
+
+fn write_dead_letter_index<S: Storage>(storage: &mut S, recipient: &CanonicalAddr, index: &DeadLetterIndex) -> StdResult<()> {
+    let mut index_storage = PrefixedStorage::new(DEAD_LETTER_INDEX_PREFIX, storage);
+    if index.slots.is_empty() {
+        index_storage.remove(recipient.as_slice());
+        Ok(())
+    } else {
+        save(&mut index_storage, recipient.as_slice(), index)
+    }
+}
+
+/// Adds a dead letter for the recipient, deleting the oldest ones beyond `capacity`
+pub fn push_dead_letter<S: Storage>(
+    storage: &mut S,
+    recipient: &CanonicalAddr,
+    letter: &DeadLetter,
+    capacity: usize,
+) -> StdResult<()> {
+    let mut index = read_dead_letter_index(storage, recipient)?;
+    let key = dead_letter_key(recipient, index.next);
+    let mut sealed = letter.clone();
+    sealed.message.content = seal(&content_key(storage)?, &key, &letter.message.content)?;
+    let mut letter_storage = PrefixedStorage::new(DEAD_LETTER_PREFIX, storage);
+    save(&mut letter_storage, &key, &sealed)?;
+    index.slots.push(index.next);
+    index.next += 1;
+    while index.slots.len() > capacity {
+        let dropped = index.slots.remove(0);
+        letter_storage.remove(&dead_letter_key(recipient, dropped));
+    }
+    write_dead_letter_index(storage, recipient, &index)
+}
+
+/// Returns the oldest dead letter of the recipient, None if there is none
+pub fn read_oldest_dead_letter<S: ReadonlyStorage>(storage: &S, recipient: &CanonicalAddr) -> StdResult<Option<DeadLetter>> {
+    let index = read_dead_letter_index(storage, recipient)?;
+    match index.slots.first() {
+        Some(counter) => {
+            let key = dead_letter_key(recipient, *counter);
+            let letter_storage = ReadonlyPrefixedStorage::new(DEAD_LETTER_PREFIX, storage);
+            let letter: Option<DeadLetter> = may_load(&letter_storage, &key)?;
+            match letter {
+                Some(mut letter) => {
+                    letter.message.content = open(&content_key(storage)?, &key, &letter.message.content)?;
+                    Ok(Some(letter))
+                }
+                None => Ok(None),
+            }
+        }
+        None => Ok(None),
+    }
+}
+
+/// Deletes the oldest dead letter of the recipient and returns the number of letters left
+pub fn remove_oldest_dead_letter<S: Storage>(storage: &mut S, recipient: &CanonicalAddr) -> StdResult<u32> {
+    let mut index = read_dead_letter_index(storage, recipient)?;
+    if index.slots.is_empty() {
+        return Ok(0);
+    }
+    let counter = index.slots.remove(0);
+    let mut letter_storage = PrefixedStorage::new(DEAD_LETTER_PREFIX, storage);
+    letter_storage.remove(&dead_letter_key(recipient, counter));
+    write_dead_letter_index(storage, recipient, &index)?;
+    Ok(index.slots.len() as u32)
+}
+
+/// admin actions recorded in the audit log
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum AuditAction {
+    ChangeAdmin { new_admin: CanonicalAddr },
+    RenounceAdmin,
+    SetContractStatus { status: ContractStatus },
+    Withdraw { amount: Fee, to: CanonicalAddr },
+    RegisterContracts { count: u32 },
+    DeregisterContracts { count: u32 },
+    RegisterRelay { relay: CanonicalAddr },
+    DeregisterRelay { relay: CanonicalAddr },
+    ProposeConfigChange { executable_height: u64 },
+    ExecuteConfigChange,
+    CancelConfigChange,
+    PurgeQueue { address: CanonicalAddr, removed: u32 },
+    PurgeAll { removed: u32 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AuditEntry {
+    pub height: u64,
+    pub timestamp: u64,
+    /// address that performed the action
+    pub actor: CanonicalAddr,
+    pub action: AuditAction,
+}
+
+/// Appends an entry to the audit log, entries are never changed or removed
+pub fn append_audit_entry<S: Storage>(storage: &mut S, entry: &AuditEntry) -> StdResult<()> {
+    let count: u64 = may_load(storage, AUDIT_COUNT_KEY)?.unwrap_or(0);
+    let mut audit_storage = PrefixedStorage::new(AUDIT_PREFIX, storage);
+    save(&mut audit_storage, &count.to_be_bytes(), entry)?;
+    save(storage, AUDIT_COUNT_KEY, &(count + 1))
+}
+
+/// Returns the number of entries in the audit log
+pub fn audit_log_len<S: ReadonlyStorage>(storage: &S) -> StdResult<u64> {
+    Ok(may_load(storage, AUDIT_COUNT_KEY)?.unwrap_or(0))
+}
+
+pub fn read_audit_entry<S: ReadonlyStorage>(storage: &S, index: u64) -> StdResult<Option<AuditEntry>> {
+    let audit_storage = ReadonlyPrefixedStorage::new(AUDIT_PREFIX, storage);
+    may_load(&audit_storage, &index.to_be_bytes())
+}
+
+/// Marks an address as a contract for sender filtering, or removes the mark
+pub fn write_registered_contract<S: Storage>(storage: &mut S, address: &CanonicalAddr, registered: bool) {
+    let mut contract_storage = PrefixedStorage::new(REGISTERED_CONTRACT_PREFIX, storage);
+    if registered {
+        contract_storage.set(address.as_slice(), &[1]);
+    } else {
+        contract_storage.remove(address.as_slice());
+    }
+}
+
+/// Returns true if the admin registered the address as a contract
+pub fn is_registered_contract<S: ReadonlyStorage>(storage: &S, address: &CanonicalAddr) -> bool {
+    let contract_storage = ReadonlyPrefixedStorage::new(REGISTERED_CONTRACT_PREFIX, storage);
+    contract_storage.get(address.as_slice()).is_some()
+}
+
+/// relay contract sanctioned by the admin to send on behalf of other addresses
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Relay {
+    pub address: CanonicalAddr,
+    /// code hash the relay has to present with every send
+    pub code_hash: String,
+}
+
+/// Returns the sanctioned relays, in the order they were registered
+pub fn read_relays<S: ReadonlyStorage>(storage: &S) -> StdResult<Vec<Relay>> {
+    Ok(may_load(storage, RELAYS_KEY)?.unwrap_or_default())
+}
+
+pub fn write_relays<S: Storage>(storage: &mut S, relays: &[Relay]) -> StdResult<()> {
+    save(storage, RELAYS_KEY, &relays.to_vec())
+}
+
+/// Stores the code hash of a contract that wants to be notified when its messages are read
+pub fn write_sender_callback<S: Storage>(storage: &mut S, owner: &CanonicalAddr, code_hash: &str) -> StdResult<()> {
+    let mut callback_storage = PrefixedStorage::new(SENDER_CALLBACK_PREFIX, storage);
+    save(&mut callback_storage, owner.as_slice(), &code_hash.to_string())
+}
+
+/// Returns the registered read callback code hash of an address, None if it did not register one
+pub fn read_sender_callback<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> StdResult<Option<String>> {
+    let callback_storage = ReadonlyPrefixedStorage::new(SENDER_CALLBACK_PREFIX, storage);
+    may_load(&callback_storage, owner.as_slice())
+}
+
+/// Stores the public key an address signs meta-transactions with
+pub fn write_signing_key<S: Storage>(storage: &mut S, owner: &CanonicalAddr, pubkey: &[u8]) {
+    let mut signing_key_storage = PrefixedStorage::new(SIGNING_KEY_PREFIX, storage);
+    signing_key_storage.set(owner.as_slice(), pubkey);
+}
+
+/// Returns the registered signing public key of an address, if any
+pub fn read_signing_key<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> Option<Vec<u8>> {
+    let signing_key_storage = ReadonlyPrefixedStorage::new(SIGNING_KEY_PREFIX, storage);
+    signing_key_storage.get(owner.as_slice())
+}
+
+/// Stores the last nonce an address used for a signed send
+pub fn write_nonce<S: Storage>(storage: &mut S, owner: &CanonicalAddr, nonce: u64) -> StdResult<()> {
+    let mut nonce_storage = PrefixedStorage::new(NONCE_PREFIX, storage);
+    save(&mut nonce_storage, owner.as_slice(), &nonce)
+}
+
+/// Returns the last nonce an address used for a signed send, 0 if there was none
+pub fn read_nonce<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> StdResult<u64> {
+    let nonce_storage = ReadonlyPrefixedStorage::new(NONCE_PREFIX, storage);
+    let nonce: Option<u64> = may_load(&nonce_storage, owner.as_slice())?;
+    Ok(nonce.unwrap_or_default())
+}
+
+/// Sets the permit epoch of an address, permits signed for an earlier epoch are no longer accepted
+pub fn write_permit_epoch<S: Storage>(storage: &mut S, owner: &CanonicalAddr, epoch: u32) -> StdResult<()> {
+    let mut epoch_storage = PrefixedStorage::new(PERMIT_EPOCH_PREFIX, storage);
+    save(&mut epoch_storage, owner.as_slice(), &epoch)
+}
+
+/// Returns the permit epoch of an address, 0 if it never revoked all its permits
+pub fn read_permit_epoch<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> StdResult<u32> {
+    let epoch_storage = ReadonlyPrefixedStorage::new(PERMIT_EPOCH_PREFIX, storage);
+    let epoch: Option<u32> = may_load(&epoch_storage, owner.as_slice())?;
+    Ok(epoch.unwrap_or_default())
+}
+
+/// Revokes every permit of an address signed with the given name, for good
+pub fn revoke_permit<S: Storage>(storage: &mut S, owner: &CanonicalAddr, permit_name: &str) {
+    let mut revoked_storage = PrefixedStorage::new(REVOKED_PERMIT_PREFIX, storage);
+    revoked_storage.set(&[owner.as_slice(), permit_name.as_bytes()].concat(), &[1]);
+}
+
+/// Returns true if the address revoked its permits with the given name
+pub fn is_permit_revoked<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr, permit_name: &str) -> bool {
+    let revoked_storage = ReadonlyPrefixedStorage::new(REVOKED_PERMIT_PREFIX, storage);
+    revoked_storage.get(&[owner.as_slice(), permit_name.as_bytes()].concat()).is_some()
+}
+
+/// neighbours of an address in the index of queues holding at least one message
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct ActiveQueueLink {
+    pub prev: Option<CanonicalAddr>,
+    pub next: Option<CanonicalAddr>,
+}
+
+/// Adds an address to the index of active queues, if it is not in it already
+pub fn link_active_queue<S: Storage>(storage: &mut S, owner: &CanonicalAddr) -> StdResult<()> {
+    let existing: Option<ActiveQueueLink> =
+        may_load(&ReadonlyPrefixedStorage::new(ACTIVE_QUEUE_PREFIX, storage), owner.as_slice())?;
+    if existing.is_some() {
+        return Ok(());
+    }
+    let head: Option<CanonicalAddr> = may_load(storage, ACTIVE_QUEUE_HEAD_KEY)?;
+    let mut link_storage = PrefixedStorage::new(ACTIVE_QUEUE_PREFIX, storage);
+    if let Some(head) = &head {
+        let mut head_link: ActiveQueueLink = load(&link_storage, head.as_slice())?;
+        head_link.prev = Some(owner.clone());
+        save(&mut link_storage, head.as_slice(), &head_link)?;
+    }
+    save(&mut link_storage, owner.as_slice(), &ActiveQueueLink { prev: None, next: head })?;
+    save(storage, ACTIVE_QUEUE_HEAD_KEY, &Some(owner.clone()))
+}
+
+/// Removes an address from the index of active queues, if it is in it
+pub fn unlink_active_queue<S: Storage>(storage: &mut S, owner: &CanonicalAddr) -> StdResult<()> {
+    let mut link_storage = PrefixedStorage::new(ACTIVE_QUEUE_PREFIX, storage);
+    let link: ActiveQueueLink = match may_load(&link_storage, owner.as_slice())? {
+        Some(link) => link,
+        None => return Ok(()),
+    };
+    link_storage.remove(owner.as_slice());
+    if let Some(next) = &link.next {
+        let mut next_link: ActiveQueueLink = load(&link_storage, next.as_slice())?;
+        next_link.prev = link.prev.clone();
+        save(&mut link_storage, next.as_slice(), &next_link)?;
+    }
+    match &link.prev {
+        Some(prev) => {
+            let mut prev_link: ActiveQueueLink = load(&link_storage, prev.as_slice())?;
+            prev_link.next = link.next;
+            save(&mut link_storage, prev.as_slice(), &prev_link)
+        }
+        None => save(storage, ACTIVE_QUEUE_HEAD_KEY, &link.next),
+    }
+}
+
+/// Returns the most recently activated address in the index of active queues, None if it is empty
+pub fn first_active_queue<S: ReadonlyStorage>(storage: &S) -> StdResult<Option<CanonicalAddr>> {
+    let head: Option<Option<CanonicalAddr>> = may_load(storage, ACTIVE_QUEUE_HEAD_KEY)?;
+    Ok(head.flatten())
+}
+
+/// aggregate counters for capacity planning, stored as JSON so counters can be added later
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(default)]
+pub struct Stats {
+    /// messages added to a queue
+    pub messages_sent: u64,
+    /// messages read and deleted by their recipient
+    pub messages_exploded: u64,
+    /// messages dequeued unread to make room in a full queue
+    pub messages_evicted: u64,
+    /// messages deleted unread because they expired, were pruned or were reported as spam
+    pub messages_expired: u64,
+    /// queues holding at least one message
+    pub active_queues: u64,
+}
+
+/// activity counters of a single address, stored as JSON so counters can be added later
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(default)]
+pub struct UserStats {
+    /// messages the address added to a queue
+    pub messages_sent: u64,
+    /// messages the address read
+    pub messages_received: u64,
+    /// messages dequeued unread from the address's queue to make room
+    pub messages_evicted: u64,
+}
+
+pub fn write_stats<S: Storage>(storage: &mut S, stats: &Stats) -> StdResult<()> {
+    save_as(storage, STATS_KEY, stats, Format::Json)
+}
+
+pub fn read_stats<S: ReadonlyStorage>(storage: &S) -> StdResult<Stats> {
+    load(storage, STATS_KEY)
+}
+
+/// Stores the activity counters of an address
+pub fn write_user_stats<S: Storage>(storage: &mut S, owner: &CanonicalAddr, stats: &UserStats) -> StdResult<()> {
+    let mut user_stats_storage = PrefixedStorage::new(USER_STATS_PREFIX, storage);
+    save_as(&mut user_stats_storage, owner.as_slice(), stats, Format::Json)
+}
+
+/// Returns the activity counters of an address, all zero if it has no activity
+pub fn read_user_stats<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> StdResult<UserStats> {
+    let user_stats_storage = ReadonlyPrefixedStorage::new(USER_STATS_PREFIX, storage);
+    let stats: Option<UserStats> = may_load(&user_stats_storage, owner.as_slice())?;
+    Ok(stats.unwrap_or_default())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Config {
+    /// address allowed to run admin queries and handles, None once the admin renounced
+    pub admin: Option<CanonicalAddr>,
+    /// maximum number of messages
+    pub max_messages: u32,
+    /// what a full queue does when a new message arrives
+    pub eviction_policy: EvictionPolicy,
+    pub max_message_size: u16,
+    /// maximum number of items in each sent items folder, 0 disables the folder
+    pub max_sent_items: u32,
+    /// first sequence number of each recipient
+    pub seq_start: u64,
+    /// sizes in bytes, sorted ascending, that stored content is padded to
+    pub padding_buckets: Vec<u32>,
+    /// obfuscation of the unread counts returned by recv and size, exact if None
+    pub unread_count_fuzz: Option<CountFuzz>,
+    /// token accepted as postage for messages embedded in SNIP-20 sends, None disables them
+    pub postage: Option<Postage>,
+    /// native postage charged for direct sends, None makes them free
+    pub send_fee: Option<SendFee>,
+    /// operating mode set by the admin
+    pub status: ContractStatus,
+    /// minimum number of blocks between proposing and executing a config change
+    pub config_change_delay: u64,
+    /// kinds of senders every recipient accepts messages from
+    pub sender_filter: SenderFilter,
+    /// emit the same `activity` log attribute on every handle so indexers can chart volume
+    pub activity_logs: bool,
+    /// native fee for registering a name
+    pub name_fee: Option<Fee>,
+    /// blocks after which messages without their own expiry expire, 0 if they never do
+    pub default_ttl_blocks: u64,
+    /// what content is accepted and how it is returned
+    pub payload_validation: PayloadValidation,
+    /// maximum number of unread messages one sender can have in a queue, 0 if unlimited
+    pub max_messages_per_sender: u32,
+    /// refused sends fail at the same point and with the same answer as sends to a full queue
+    pub uniform_failures: bool,
+    /// SNIP-721 contract read receipts are minted on, None disables them
+    pub receipt_nft: Option<ReceiptNft>,
+    /// leading zero bits the proof of work of a send must have, 0 if none is required
+    pub pow_difficulty: u8,
+}
+
+/// content accepted by a deployment
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadValidation {
+    /// any text, returned as `content`
+    Utf8,
+    /// text that parses as JSON, returned as `content`
+    Json,
+    /// base64 encoded bytes, returned decoded as `raw_content`
+    Raw,
+}
+
+impl Default for PayloadValidation {
+    fn default() -> Self {
+        PayloadValidation::Utf8
+    }
+}
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct Message {
-    pub content: Vec<u8>,
-    /// address of the sender
-    pub from: CanonicalAddr,
-    /// id of prev message, 0 means first in queue
-    pub prev: u128,
-    /// id of next message in queue, 0 means last in queue
-    pub next: u128,
+/// kinds of senders a recipient accepts messages from
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SenderFilter {
+    Any,
+    /// refuse senders known to be contracts
+    HumansOnly,
+    /// refuse senders not known to be contracts
+    ContractsOnly,
 }
 
-pub struct MessageStorage<'a, S: Storage> {
-    storage: PrefixedStorage<'a, S>,
+impl Default for SenderFilter {
+    fn default() -> Self {
+        SenderFilter::Any
+    }
 }
 
-impl<'a, S: Storage> MessageStorage<'a, S> {
-    pub fn from_storage(storage: &'a mut S) -> Self {
-        Self {
-            storage: PrefixedStorage::new(MESSAGE_PREFIX, storage),
+impl SenderFilter {
+    pub fn allows(&self, is_contract: bool) -> bool {
+        match self {
+            SenderFilter::Any => true,
+            SenderFilter::HumansOnly => !is_contract,
+            SenderFilter::ContractsOnly => is_contract,
         }
     }
+}
+
+/// parameters changed by an admin proposal, None keeps the current value
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct ConfigChange {
+    pub max_messages: Option<u32>,
+    pub max_message_size: Option<u16>,
+    pub eviction_policy: Option<EvictionPolicy>,
+    pub max_sent_items: Option<u32>,
+    pub padding_buckets: Option<Vec<u32>>,
+    pub send_fee: Option<SendFee>,
+    pub config_change_delay: Option<u64>,
+    pub sender_filter: Option<SenderFilter>,
+    pub activity_logs: Option<bool>,
+    pub name_fee: Option<Fee>,
+    pub default_ttl_blocks: Option<u64>,
+    pub payload_validation: Option<PayloadValidation>,
+    pub max_messages_per_sender: Option<u32>,
+    pub uniform_failures: Option<bool>,
+    pub pow_difficulty: Option<u8>,
+}
+
+impl ConfigChange {
+    pub fn is_empty(&self) -> bool {
+        *self == ConfigChange::default()
+    }
 
-    fn as_readonly(&self) -> ReadonlyMessageStorageImpl<PrefixedStorage<S>> {
-        ReadonlyMessageStorageImpl(&self.storage)
+    pub fn apply(self, config: &mut Config) {
+        if let Some(max_messages) = self.max_messages {
+            config.max_messages = max_messages;
+        }
+        if let Some(max_message_size) = self.max_message_size {
+            config.max_message_size = max_message_size;
+        }
+        if let Some(eviction_policy) = self.eviction_policy {
+            config.eviction_policy = eviction_policy;
+        }
+        if let Some(max_sent_items) = self.max_sent_items {
+            config.max_sent_items = max_sent_items;
+        }
+        if let Some(padding_buckets) = self.padding_buckets {
+            config.padding_buckets = padding_buckets;
+        }
+        if let Some(send_fee) = self.send_fee {
+            config.send_fee = Some(send_fee);
+        }
+        if let Some(config_change_delay) = self.config_change_delay {
+            config.config_change_delay = config_change_delay;
+        }
+        if let Some(sender_filter) = self.sender_filter {
+            config.sender_filter = sender_filter;
+        }
+        if let Some(activity_logs) = self.activity_logs {
+            config.activity_logs = activity_logs;
+        }
+        if let Some(name_fee) = self.name_fee {
+            config.name_fee = Some(name_fee);
+        }
+        if let Some(default_ttl_blocks) = self.default_ttl_blocks {
+            config.default_ttl_blocks = default_ttl_blocks;
+        }
+        if let Some(payload_validation) = self.payload_validation {
+            config.payload_validation = payload_validation;
+        }
+        if let Some(max_messages_per_sender) = self.max_messages_per_sender {
+            config.max_messages_per_sender = max_messages_per_sender;
+        }
+        if let Some(uniform_failures) = self.uniform_failures {
+            config.uniform_failures = uniform_failures;
+        }
+        if let Some(pow_difficulty) = self.pow_difficulty {
+            config.pow_difficulty = pow_difficulty;
+        }
     }
+}
+
+/// config change waiting for its delay to pass, stored under PENDING_CONFIG_KEY
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingConfigChange {
+    pub change: ConfigChange,
+    pub proposed_height: u64,
+    /// first block height at which the change can be executed
+    pub executable_height: u64,
+}
+
+/// operating modes of the contract
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractStatus {
+    /// all requests are accepted
+    Normal,
+    /// new messages are refused, reading, blocking and queries still work
+    ReadOnly,
+}
 
-    pub fn set_message(&mut self, key: &u128, mes: Message) {
-        save(&mut self.storage, &key.to_be_bytes(), &mes).ok();
+/// SNIP-721 contract that mints read receipts for senders
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiptNft {
+    pub contract: CanonicalAddr,
+    pub code_hash: String,
+}
+
+/// SNIP-20 token and minimum amount paid for a message delivered through the token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Postage {
+    pub token: CanonicalAddr,
+    pub token_code_hash: String,
+    pub amount: u128,
+    /// added to `amount` for every byte of content
+    pub per_byte: u128,
+}
+
+impl Postage {
+    /// Returns the postage for content of `size` bytes
+    pub fn amount_for(&self, size: usize) -> u128 {
+        self.amount.saturating_add(self.per_byte.saturating_mul(size as u128))
     }
+}
+
+/// postage collected by the contract that the admin can withdraw
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct Revenue {
+    /// collected amounts per native coin denomination
+    pub native: Vec<(String, u128)>,
+    /// collected amount of the postage token
+    pub token: u128,
+}
 
-    pub fn remove_message(&mut self, key: &u128) {
-        remove(&mut self.storage, &key.to_be_bytes());
+impl Revenue {
+    pub fn credit(&mut self, fee: &Fee) {
+        match fee {
+            Fee::Native { denom, amount } => match self.native.iter_mut().find(|(d, _)| d == denom) {
+                Some((_, collected)) => *collected = collected.saturating_add(*amount),
+                None => self.native.push((denom.clone(), *amount)),
+            },
+            Fee::Token { amount } => self.token = self.token.saturating_add(*amount),
+        }
     }
 
-    pub fn get_message(&mut self, key: &u128) -> Option<Message> {
-        self.as_readonly().get(key)
+    /// Takes `fee` out of the collected postage, returns false if not enough was collected
+    pub fn debit(&mut self, fee: &Fee) -> bool {
+        let collected = match fee {
+            Fee::Native { denom, .. } => match self.native.iter_mut().find(|(d, _)| d == denom) {
+                Some((_, collected)) => collected,
+                None => return false,
+            },
+            Fee::Token { .. } => &mut self.token,
+        };
+        let amount = match fee {
+            Fee::Native { amount, .. } | Fee::Token { amount } => *amount,
+        };
+        if *collected < amount {
+            return false;
+        }
+        *collected -= amount;
+        self.native.retain(|(_, collected)| *collected > 0);
+        true
     }
 }
 
-struct ReadonlyMessageStorageImpl<'a, S: ReadonlyStorage>(&'a S);
+/// Adds postage that the contract keeps to the collected revenue
+pub fn credit_revenue<S: Storage>(storage: &mut S, fee: &Fee) -> StdResult<()> {
+    let mut revenue: Revenue = may_load(storage, REVENUE_KEY)?.unwrap_or_default();
+    revenue.credit(fee);
+    save(storage, REVENUE_KEY, &revenue)
+}
 
-impl<'a, S: ReadonlyStorage> ReadonlyMessageStorageImpl<'a, S> {
-    pub fn get(&self, key: &u128) -> Option<Message> {
-        let mes: Option<Message> = may_load(self.0, &key.to_be_bytes()).ok().unwrap();
-        mes
+/// native coin postage of `base + per_byte * size` charged for direct sends, unless the size
+/// falls into one of the flat `tiers`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SendFee {
+    pub denom: String,
+    pub base: u128,
+    pub per_byte: u128,
+    /// sorted by max_size, ascending
+    pub tiers: Vec<FeeTier>,
+}
+
+/// flat postage for content of at most `max_size` bytes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeTier {
+    pub max_size: u32,
+    pub amount: u128,
+}
+
+impl SendFee {
+    /// Returns the postage for content of `size` bytes, the amount of the smallest tier it fits
+    /// in or `base + per_byte * size` if it is larger than every tier
+    pub fn amount_for(&self, size: usize) -> u128 {
+        match self.tiers.iter().find(|tier| size <= tier.max_size as usize) {
+            Some(tier) => tier.amount,
+            None => self.base.saturating_add(self.per_byte.saturating_mul(size as u128)),
+        }
     }
 }
 
+/// ways to blur a reported message count
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct MessageQueue {
-    /// id of front message
-    pub front: u128,
-    /// id of end message
-    pub rear: u128,
-    /// length of queue
-    pub length: u32,
-    /// set of blocked addresses (canonical)
-    pub blocked: HashSet<Vec<u8>>,
+#[serde(rename_all = "snake_case")]
+pub enum CountFuzz {
+    /// round the count up to a multiple of `size`
+    Bucket { size: u32 },
+    /// add a pseudorandom amount between 0 and `max`, fixed per address and block
+    Noise { max: u32 },
 }
 
-pub struct MessageQueueStorage<'a, S: Storage> {
+/// cold per-user settings, only rewritten when the user changes them
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct UserSettings {
+    /// number of addresses on the user's blocklist
+    pub blocked_count: u32,
+    /// senders are blocked automatically once this many of their messages were evicted unread or
+    /// reported as spam, 0 disables automatic blocking
+    pub auto_block_after: u32,
+    /// postage senders other than contacts pay to send a message to the user
+    pub price: Option<Fee>,
+    /// deposit senders other than contacts attach to a message to the user, refunded when the
+    /// message is read and forfeited if it is reported as spam, never set together with price
+    pub bond: Option<Fee>,
+    /// kinds of senders the user accepts messages from, on top of the deployment-wide filter
+    pub sender_filter: SenderFilter,
+    /// deliver a notice to the user when one of their messages is evicted unread
+    pub eviction_notices: bool,
+    /// deliver a notice to the user when one of their sends is dropped because the queue is full
+    pub bounce_notices: bool,
+    /// keep messages evicted from or expiring in the user's queue as dead letters
+    pub dead_letters: bool,
+    /// status the user set, None if it was never set or was cleared
+    pub presence: Option<Presence>,
+    /// window in which the user does not take new messages
+    pub quiet_hours: Option<QuietHours>,
+}
+
+/// seconds in a day, for daily quiet hours
+pub const SECONDS_PER_DAY: u64 = 86400;
+
+/// recurring or one-off window in which a recipient does not take new messages
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuietWindow {
+    /// from block `start` up to, not including, block `end`
+    Heights { start: u64, end: u64 },
+    /// every day from `start` up to `end` seconds after midnight UTC, past midnight if `start`
+    /// is greater than `end`
+    Daily { start: u32, end: u32 },
+}
+
+impl QuietWindow {
+    /// Returns true if start and end describe a non-empty window
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            QuietWindow::Heights { start, end } => start < end,
+            QuietWindow::Daily { start, end } => {
+                start != end && u64::from(start) < SECONDS_PER_DAY && u64::from(end) < SECONDS_PER_DAY
+            }
+        }
+    }
+
+    /// Returns true if the window is active at the given block height and time
+    pub fn contains(&self, height: u64, time: u64) -> bool {
+        match *self {
+            QuietWindow::Heights { start, end } => height >= start && height < end,
+            QuietWindow::Daily { start, end } => {
+                let second = (time % SECONDS_PER_DAY) as u32;
+                if start < end {
+                    second >= start && second < end
+                } else {
+                    second >= start || second < end
+                }
+            }
+        }
+    }
+}
+
+/// quiet hours of a user
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct QuietHours {
+    pub window: QuietWindow,
+    /// hold messages sent during the window and deliver them once it is over, instead of
+    /// refusing them
+    pub defer: bool,
+}
+
+/// short status a user can show to the senders of their messages
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Presence {
+    /// free text such as "on vacation until monday"
+    pub text: Option<String>,
+    pub away: bool,
+    /// echo the status to senders in the response to their send
+    pub shared: bool,
+}
+
+/// amount of a native coin or of the postage token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Fee {
+    Native { denom: String, amount: u128 },
+    Token { amount: u128 },
+}
+
+pub struct UserSettingsStorage<'a, S: Storage> {
     storage: PrefixedStorage<'a, S>,
 }
 
-impl<'a, S: Storage> MessageQueueStorage<'a, S> {
+impl<'a, S: Storage> UserSettingsStorage<'a, S> {
     pub fn from_storage(storage: &'a mut S) -> Self {
         Self {
-            storage: PrefixedStorage::new(MESSAGE_QUEUE_PREFIX, storage),
+            storage: PrefixedStorage::new(USER_SETTINGS_PREFIX, storage),
         }
     }
 
-    fn as_readonly(&self) -> ReadonlyMessageQueueStorageImpl<PrefixedStorage<S>> {
-        ReadonlyMessageQueueStorageImpl(&self.storage)
+    fn as_readonly(&self) -> ReadonlyUserSettingsStorageImpl<'_, PrefixedStorage<'_, S>> {
+        ReadonlyUserSettingsStorageImpl(&self.storage)
     }
 
-    pub fn set_message_queue(&mut self, key: &CanonicalAddr, queue: MessageQueue) {
-        save(&mut self.storage, &key.as_slice().to_vec(), &queue).ok();
+    /// default settings are not stored
+    pub fn set_settings(&mut self, key: &CanonicalAddr, settings: UserSettings) {
+        if settings == UserSettings::default() {
+            remove(&mut self.storage, key.as_slice());
+        } else {
+            save(&mut self.storage, key.as_slice(), &settings).ok();
+        }
     }
 
-    pub fn get_message_queue(&mut self, key: &CanonicalAddr) -> MessageQueue {
+    pub fn get_settings(&mut self, key: &CanonicalAddr) -> UserSettings {
         self.as_readonly().get(key)
     }
 }
 
-struct ReadonlyMessageQueueStorageImpl<'a, S: ReadonlyStorage>(&'a S);
+pub struct ReadonlyUserSettingsStorage<'a, S: ReadonlyStorage> {
+    storage: ReadonlyPrefixedStorage<'a, S>,
+}
 
-impl<'a, S: ReadonlyStorage> ReadonlyMessageQueueStorageImpl<'a, S> {
-    pub fn get(&self, key: &CanonicalAddr) -> MessageQueue {
-        let queue: Option<MessageQueue> = may_load(self.0, &key.as_slice().to_vec()).ok().unwrap();
-        if let Some(found_queue) = queue {
-            found_queue
-        } else {
-            MessageQueue {
-                front: 0,
-                rear: 0,
-                length: 0,
-                blocked: HashSet::new()
-            }
+impl<'a, S: ReadonlyStorage> ReadonlyUserSettingsStorage<'a, S> {
+    pub fn from_storage(storage: &'a S) -> Self {
+        Self {
+            storage: ReadonlyPrefixedStorage::new(USER_SETTINGS_PREFIX, storage),
+        }
+    }
+
+    fn as_readonly(&self) -> ReadonlyUserSettingsStorageImpl<'_, ReadonlyPrefixedStorage<'_, S>> {
+        ReadonlyUserSettingsStorageImpl(&self.storage)
+    }
+
+    pub fn get_settings(&self, key: &CanonicalAddr) -> UserSettings {
+        self.as_readonly().get(key)
+    }
+}
+
+struct ReadonlyUserSettingsStorageImpl<'a, S: ReadonlyStorage>(&'a S);
+
+impl<'a, S: ReadonlyStorage> ReadonlyUserSettingsStorageImpl<'a, S> {
+    pub fn get(&self, key: &CanonicalAddr) -> UserSettings {
+        let settings: Option<UserSettings> = may_load(self.0, key.as_slice()).ok().unwrap();
+        settings.unwrap_or_default()
+    }
+}
+
+fn pair_key(first: &CanonicalAddr, second: &CanonicalAddr) -> Vec<u8> {
+    let mut key = first.as_slice().to_vec();
+    key.extend_from_slice(second.as_slice());
+    key
+}
+
+fn blocklist_page_key(recipient: &CanonicalAddr, page: u32) -> Vec<u8> {
+    let mut key = recipient.as_slice().to_vec();
+    key.extend_from_slice(&page.to_be_bytes());
+    key
+}
+
+/// Returns one page of the blocklist of a recipient, empty past the last page
+pub fn read_blocklist_page<S: ReadonlyStorage>(storage: &S, recipient: &CanonicalAddr, page: u32) -> StdResult<Vec<CanonicalAddr>> {
+    let page_storage = ReadonlyPrefixedStorage::new(BLOCKLIST_PAGE_PREFIX, storage);
+    let addresses: Option<Vec<CanonicalAddr>> = may_load(&page_storage, &blocklist_page_key(recipient, page))?;
+    Ok(addresses.unwrap_or_default())
+}
+
+fn write_blocklist_page<S: Storage>(storage: &mut S, recipient: &CanonicalAddr, page: u32, addresses: &[CanonicalAddr]) -> StdResult<()> {
+    let mut page_storage = PrefixedStorage::new(BLOCKLIST_PAGE_PREFIX, storage);
+    let key = blocklist_page_key(recipient, page);
+    if addresses.is_empty() {
+        page_storage.remove(&key);
+        Ok(())
+    } else {
+        save(&mut page_storage, &key, &addresses.to_vec())
+    }
+}
+
+/// Adds a sender to the blocklist of a recipient that currently holds `count` addresses.
+/// The membership record keeps the sender's position so it can be removed without scanning.
+pub fn block_sender<S: Storage>(storage: &mut S, recipient: &CanonicalAddr, sender: &CanonicalAddr, count: u32) -> StdResult<()> {
+    let page = count / BLOCKLIST_PAGE_SIZE;
+    let mut addresses = read_blocklist_page(storage, recipient, page)?;
+    addresses.push(sender.clone());
+    write_blocklist_page(storage, recipient, page, &addresses)?;
+    let mut blocked_storage = PrefixedStorage::new(BLOCKED_PREFIX, storage);
+    save(&mut blocked_storage, &pair_key(recipient, sender), &count)
+}
+
+/// Removes a sender from the blocklist of a recipient that currently holds `count` addresses,
+/// moving the last blocked address into its position
+pub fn unblock_sender<S: Storage>(storage: &mut S, recipient: &CanonicalAddr, sender: &CanonicalAddr, count: u32) -> StdResult<()> {
+    let mut blocked_storage = PrefixedStorage::new(BLOCKED_PREFIX, storage);
+    let position: u32 = match may_load(&blocked_storage, &pair_key(recipient, sender))? {
+        Some(position) => position,
+        None => return Ok(()),
+    };
+    blocked_storage.remove(&pair_key(recipient, sender));
+
+    let last_page = count.saturating_sub(1) / BLOCKLIST_PAGE_SIZE;
+    let mut last_addresses = read_blocklist_page(storage, recipient, last_page)?;
+    let last = match last_addresses.pop() {
+        Some(last) => last,
+        None => return Err(StdError::generic_err("Corrupted blocklist.")),
+    };
+    write_blocklist_page(storage, recipient, last_page, &last_addresses)?;
+    if last != *sender {
+        let page = position / BLOCKLIST_PAGE_SIZE;
+        let mut addresses = read_blocklist_page(storage, recipient, page)?;
+        match addresses.get_mut((position % BLOCKLIST_PAGE_SIZE) as usize) {
+            Some(slot) => *slot = last.clone(),
+            None => return Err(StdError::generic_err("Corrupted blocklist.")),
         }
+        write_blocklist_page(storage, recipient, page, &addresses)?;
+        let mut blocked_storage = PrefixedStorage::new(BLOCKED_PREFIX, storage);
+        save(&mut blocked_storage, &pair_key(recipient, &last), &position)?;
     }
+    Ok(())
+}
+
+/// Returns the all-zero address anonymous messages are counted under in the per-sender index
+pub fn anonymous_sender() -> CanonicalAddr {
+    CanonicalAddr(Binary(vec![0u8; 20]))
 }
 
+/// sender and recipient of an anonymous message, stored under the hash of its reply token until
+/// the recipient replies
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct Config {
-    /// maximum number of messages
-    pub max_messages: u32,
-    /// if discard true, will not push messages to full queue,
-    /// else will dequeue oldest message to make room
-    pub discard: bool,
-    pub max_message_size: u16,
+pub struct ReplyToken {
+    pub sender: CanonicalAddr,
+    pub recipient: CanonicalAddr,
+}
+
+pub fn write_reply_token<S: Storage>(storage: &mut S, token_hash: &[u8], reply_token: &ReplyToken) -> StdResult<()> {
+    save(&mut PrefixedStorage::new(REPLY_TOKEN_PREFIX, storage), token_hash, reply_token)
+}
+
+pub fn read_reply_token<S: ReadonlyStorage>(storage: &S, token_hash: &[u8]) -> StdResult<Option<ReplyToken>> {
+    may_load(&ReadonlyPrefixedStorage::new(REPLY_TOKEN_PREFIX, storage), token_hash)
+}
+
+pub fn remove_reply_token<S: Storage>(storage: &mut S, token_hash: &[u8]) {
+    remove(&mut PrefixedStorage::new(REPLY_TOKEN_PREFIX, storage), token_hash);
+}
+
+/// deposit held in escrow for a message until it is read or reported as spam, also holding the
+/// price of a message its recipient cleared unread
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Bond {
+    /// address of the sender the deposit is refunded to
+    pub sender: CanonicalAddr,
+    pub amount: Fee,
+}
+
+/// Stores the bond deposited for a message
+pub fn write_bond<S: Storage>(storage: &mut S, message_id: u64, bond: &Bond) -> StdResult<()> {
+    let mut bond_storage = PrefixedStorage::new(BOND_PREFIX, storage);
+    save(&mut bond_storage, &message_id.to_be_bytes(), bond)
+}
+
+/// Returns the bond deposited for a message, if any
+pub fn read_bond<S: ReadonlyStorage>(storage: &S, message_id: u64) -> StdResult<Option<Bond>> {
+    let bond_storage = ReadonlyPrefixedStorage::new(BOND_PREFIX, storage);
+    may_load(&bond_storage, &message_id.to_be_bytes())
+}
+
+/// signature of the recipient over the id of a message it read, kept for the sender
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReadProof {
+    pub recipient: CanonicalAddr,
+    /// signing key of the recipient when the message was read
+    pub pubkey: Binary,
+    pub signature: Binary,
+    pub height: u64,
+}
+
+/// Returns the hash a recipient signs to read a message, which is
+/// sha256(contract address ++ 0x00 ++ message id as 8 big-endian bytes)
+pub fn read_proof_hash(contract: &HumanAddr, message_id: u64) -> [u8; 32] {
+    let mut preimage = contract.as_str().as_bytes().to_vec();
+    preimage.push(0);
+    preimage.extend_from_slice(&message_id.to_be_bytes());
+    sha_256(&preimage)
+}
+
+fn read_proof_key(sender: &CanonicalAddr, message_id: u64) -> Vec<u8> {
+    [sender.as_slice(), &message_id.to_be_bytes()].concat()
+}
+
+pub fn write_read_proof<S: Storage>(
+    storage: &mut S,
+    sender: &CanonicalAddr,
+    message_id: u64,
+    proof: &ReadProof,
+) -> StdResult<()> {
+    save(&mut PrefixedStorage::new(READ_PROOF_PREFIX, storage), &read_proof_key(sender, message_id), proof)
+}
+
+pub fn read_read_proof<S: ReadonlyStorage>(
+    storage: &S,
+    sender: &CanonicalAddr,
+    message_id: u64,
+) -> StdResult<Option<ReadProof>> {
+    may_load(&ReadonlyPrefixedStorage::new(READ_PROOF_PREFIX, storage), &read_proof_key(sender, message_id))
+}
+
+/// Removes and returns the bond deposited for a message, if any
+pub fn take_bond<S: Storage>(storage: &mut S, message_id: u64) -> StdResult<Option<Bond>> {
+    let bond = read_bond(storage, message_id)?;
+    if bond.is_some() {
+        let mut bond_storage = PrefixedStorage::new(BOND_PREFIX, storage);
+        bond_storage.remove(&message_id.to_be_bytes());
+    }
+    Ok(bond)
+}
+
+/// Adds an address to the contacts of an owner
+pub fn add_contact<S: Storage>(storage: &mut S, owner: &CanonicalAddr, contact: &CanonicalAddr) {
+    let mut contact_storage = PrefixedStorage::new(CONTACT_PREFIX, storage);
+    contact_storage.set(&pair_key(owner, contact), &[1]);
+}
+
+/// Removes an address from the contacts of an owner
+pub fn remove_contact<S: Storage>(storage: &mut S, owner: &CanonicalAddr, contact: &CanonicalAddr) {
+    let mut contact_storage = PrefixedStorage::new(CONTACT_PREFIX, storage);
+    contact_storage.remove(&pair_key(owner, contact));
+}
+
+/// Returns true if the owner added the address to its contacts
+pub fn is_contact<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr, contact: &CanonicalAddr) -> bool {
+    let contact_storage = ReadonlyPrefixedStorage::new(CONTACT_PREFIX, storage);
+    contact_storage.get(&pair_key(owner, contact)).is_some()
+}
+
+/// Stores how many messages from the sender the recipient lost unread, removing the record at 0
+pub fn write_offense_count<S: Storage>(storage: &mut S, recipient: &CanonicalAddr, sender: &CanonicalAddr, count: u32) -> StdResult<()> {
+    let mut offense_storage = PrefixedStorage::new(OFFENSE_PREFIX, storage);
+    if count == 0 {
+        offense_storage.remove(&pair_key(recipient, sender));
+        Ok(())
+    } else {
+        save(&mut offense_storage, &pair_key(recipient, sender), &count)
+    }
+}
+
+/// Returns how many messages from the sender the recipient lost unread
+pub fn read_offense_count<S: ReadonlyStorage>(storage: &S, recipient: &CanonicalAddr, sender: &CanonicalAddr) -> StdResult<u32> {
+    let offense_storage = ReadonlyPrefixedStorage::new(OFFENSE_PREFIX, storage);
+    let count: Option<u32> = may_load(&offense_storage, &pair_key(recipient, sender))?;
+    Ok(count.unwrap_or_default())
+}
+
+/// Returns true if the recipient has blocked the sender
+pub fn is_blocked<S: ReadonlyStorage>(storage: &S, recipient: &CanonicalAddr, sender: &CanonicalAddr) -> bool {
+    let blocked_storage = ReadonlyPrefixedStorage::new(BLOCKED_PREFIX, storage);
+    blocked_storage.get(&pair_key(recipient, sender)).is_some()
+}
+
+fn viewing_key_key(owner: &CanonicalAddr, label: &str) -> Vec<u8> {
+    let mut key = owner.as_slice().to_vec();
+    key.extend_from_slice(label.as_bytes());
+    key
+}
+
+/// hash of a viewing key with its optional expiry
+pub struct StoredViewingKey {
+    pub hashed: Vec<u8>,
+    /// if set, the key no longer authenticates once the chain passes this height
+    pub expire_after_height: Option<u64>,
+}
+
+impl StoredViewingKey {
+    /// Returns true if the key has expired at the given block height
+    pub fn is_expired(&self, height: u64) -> bool {
+        matches!(self.expire_after_height, Some(expire_after_height) if height > expire_after_height)
+    }
+}
+
+/// Stores the hash of a viewing key for an address, replacing the key with the same label
+///
+/// # Arguments
+///
+/// * `storage` - a mutable reference to the contract storage
+/// * `owner` - the canonical address that owns the key
+/// * `label` - name of the key slot, empty for the unlabeled key
+/// * `key` - the viewing key to hash and store
+/// * `expire_after_height` - height after which the key no longer authenticates, None if it never expires
+pub fn write_viewing_key<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    label: &str,
+    key: &ViewingKey,
+    expire_after_height: Option<u64>,
+) -> StdResult<()> {
+    // the expiry, if any, is appended to the hash so keys set without one keep their old layout
+    let mut stored = key.to_hashed().to_vec();
+    if let Some(expire_after_height) = expire_after_height {
+        stored.extend_from_slice(&expire_after_height.to_be_bytes());
+    }
+    let mut vk_storage = PrefixedStorage::new(VIEWING_KEY_PREFIX, storage);
+    vk_storage.set(&viewing_key_key(owner, label), &stored);
+    let mut labels = read_viewing_key_labels(storage, owner)?;
+    if !label.is_empty() && !labels.iter().any(|l| l == label) {
+        labels.push(label.to_string());
+        save(&mut PrefixedStorage::new(VIEWING_KEY_LABELS_PREFIX, storage), owner.as_slice(), &labels)?;
+    }
+    Ok(())
+}
+
+/// Deletes the viewing key with the given label. Returns false if there was none
+pub fn remove_viewing_key<S: Storage>(storage: &mut S, owner: &CanonicalAddr, label: &str) -> StdResult<bool> {
+    if read_viewing_key(storage, owner, label).is_none() {
+        return Ok(false);
+    }
+    PrefixedStorage::new(VIEWING_KEY_PREFIX, storage).remove(&viewing_key_key(owner, label));
+    let mut labels = read_viewing_key_labels(storage, owner)?;
+    labels.retain(|l| l != label);
+    let mut labels_storage = PrefixedStorage::new(VIEWING_KEY_LABELS_PREFIX, storage);
+    if labels.is_empty() {
+        remove(&mut labels_storage, owner.as_slice());
+    } else {
+        save(&mut labels_storage, owner.as_slice(), &labels)?;
+    }
+    Ok(true)
+}
+
+/// Returns the hashed viewing key with the given label for an address, if one has been set
+///
+/// # Arguments
+///
+/// * `storage` - a reference to the contract storage
+/// * `owner` - the canonical address that owns the key
+/// * `label` - name of the key slot, empty for the unlabeled key
+pub fn read_viewing_key<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr, label: &str) -> Option<StoredViewingKey> {
+    let vk_storage = ReadonlyPrefixedStorage::new(VIEWING_KEY_PREFIX, storage);
+    let mut hashed = vk_storage.get(&viewing_key_key(owner, label))?;
+    let expire_after_height = if hashed.len() == VIEWING_KEY_SIZE + 8 {
+        let mut height_bytes = [0u8; 8];
+        height_bytes.copy_from_slice(&hashed[VIEWING_KEY_SIZE..]);
+        hashed.truncate(VIEWING_KEY_SIZE);
+        Some(u64::from_be_bytes(height_bytes))
+    } else {
+        None
+    };
+    Some(StoredViewingKey { hashed, expire_after_height })
+}
+
+/// Returns the labels of an address's labeled viewing keys, in the order they were created
+pub fn read_viewing_key_labels<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr) -> StdResult<Vec<String>> {
+    let labels_storage = ReadonlyPrefixedStorage::new(VIEWING_KEY_LABELS_PREFIX, storage);
+    let labels: Option<Vec<String>> = may_load(&labels_storage, owner.as_slice())?;
+    Ok(labels.unwrap_or_default())
+}
+
+/// Stores the hash of a key that lets `querier` run delegated queries for `owner`
+pub fn write_delegated_key<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    querier: &CanonicalAddr,
+    key: &ViewingKey,
+) {
+    let mut delegation_storage = PrefixedStorage::new(DELEGATION_PREFIX, storage);
+    let storage_key = [owner.as_slice(), querier.as_slice()].concat();
+    delegation_storage.set(&storage_key, &key.to_hashed());
+}
+
+/// Revokes the delegated query key of `querier` for `owner`
+pub fn remove_delegated_key<S: Storage>(storage: &mut S, owner: &CanonicalAddr, querier: &CanonicalAddr) {
+    let mut delegation_storage = PrefixedStorage::new(DELEGATION_PREFIX, storage);
+    let storage_key = [owner.as_slice(), querier.as_slice()].concat();
+    delegation_storage.remove(&storage_key);
+}
+
+/// Returns the hashed delegated query key of `querier` for `owner`, if one has been granted
+pub fn read_delegated_key<S: ReadonlyStorage>(
+    storage: &S,
+    owner: &CanonicalAddr,
+    querier: &CanonicalAddr,
+) -> Option<Vec<u8>> {
+    let delegation_storage = ReadonlyPrefixedStorage::new(DELEGATION_PREFIX, storage);
+    let storage_key = [owner.as_slice(), querier.as_slice()].concat();
+    delegation_storage.get(&storage_key)
+}
+
+/// version of the stored structs, written into the envelope of every record. Bump it when a
+/// stored struct changes in a way old records cannot be read as, and add the upgrade of the
+/// previous version to the migrations module
+pub const STATE_VERSION: u8 = 1;
+
+/// Records the state version the stored records of this instance were written with. Kept outside
+/// the envelope so it can always be read
+pub fn write_state_version<S: Storage>(storage: &mut S, version: u8) {
+    storage.set(STATE_VERSION_KEY, &[version]);
+}
+
+/// Returns the state version of this instance, 0 if it was created before the version was stored
+pub fn read_state_version<S: ReadonlyStorage>(storage: &S) -> u8 {
+    storage.get(STATE_VERSION_KEY).and_then(|version| version.first().copied()).unwrap_or(0)
+}
+
+/// Records the height of the latest handle, the closest to the current height a query can see.
+/// The key is only written by the first handle of a block, so transactions within a block do not
+/// all write the same key
+pub fn write_latest_height<S: Storage>(storage: &mut S, height: u64) {
+    if read_latest_height(storage) < height {
+        storage.set(LATEST_HEIGHT_KEY, &height.to_be_bytes());
+    }
+}
+
+/// Records `height` as the first height the address sent a message at, unless one is recorded
+pub fn record_first_seen<S: Storage>(storage: &mut S, address: &CanonicalAddr, height: u64) {
+    let mut first_seen_storage = PrefixedStorage::new(FIRST_SEEN_PREFIX, storage);
+    if first_seen_storage.get(address.as_slice()).is_none() {
+        first_seen_storage.set(address.as_slice(), &height.to_be_bytes());
+    }
 }
 
-/// Returns StdResult<()> resulting from saving an item to storage
+/// Returns the height the address first sent a message at, None if it never did
+pub fn read_first_seen<S: ReadonlyStorage>(storage: &S, address: &CanonicalAddr) -> Option<u64> {
+    let mut height_bytes = [0u8; 8];
+    match ReadonlyPrefixedStorage::new(FIRST_SEEN_PREFIX, storage).get(address.as_slice()) {
+        Some(stored) if stored.len() == 8 => {
+            height_bytes.copy_from_slice(&stored);
+            Some(u64::from_be_bytes(height_bytes))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the height of the latest handle, 0 if none was recorded
+pub fn read_latest_height<S: ReadonlyStorage>(storage: &S) -> u64 {
+    let mut height_bytes = [0u8; 8];
+    match storage.get(LATEST_HEIGHT_KEY) {
+        Some(stored) if stored.len() == 8 => {
+            height_bytes.copy_from_slice(&stored);
+            u64::from_be_bytes(height_bytes)
+        }
+        _ => 0,
+    }
+}
+
+/// serialization format of a stored record, the first byte of its envelope
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// compact, but fails on records written before a field was added
+    Bincode2 = 1,
+    /// larger, but fields added with `#[serde(default)]` can be read from older records. Cannot
+    /// hold u128 values
+    Json = 2,
+}
+
+impl Format {
+    fn from_byte(byte: u8) -> Option<Format> {
+        match byte {
+            1 => Some(Format::Bincode2),
+            2 => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Returns StdResult<()> resulting from saving an item to storage in Bincode2
 ///
 /// # Arguments
 ///
@@ -135,10 +2603,52 @@ pub struct Config {
 /// * `value` - a reference to the item to store
 // save(&mut deps.storage, CONFIG_KEY, &state)?;
 pub fn save<T: Serialize, S: Storage>(storage: &mut S, key: &[u8], value: &T) -> StdResult<()> {
-    storage.set(key, &Bincode2::serialize(value)?);
+    save_as(storage, key, value, Format::Bincode2)
+}
+
+/// Saves an item in the given format, wrapped in an envelope of the format and STATE_VERSION
+pub fn save_as<T: Serialize, S: Storage>(storage: &mut S, key: &[u8], value: &T, format: Format) -> StdResult<()> {
+    let mut record = vec![format as u8, STATE_VERSION];
+    match format {
+        Format::Bincode2 => record.extend(Bincode2::serialize(value)?),
+        Format::Json => record.extend(Json::serialize(value)?),
+    }
+    storage.set(key, &record);
     Ok(())
 }
 
+/// Unwraps a record saved by save_as, upgrading records of older versions, with errors naming the
+/// type, format and version
+fn deserialize<T: DeserializeOwned>(record: &[u8]) -> StdResult<T> {
+    let (format, version) = match record {
+        [format, version, ..] => (Format::from_byte(*format), *version),
+        _ => return Err(StdError::parse_err(type_name::<T>(), "record has no envelope")),
+    };
+    if version > STATE_VERSION {
+        return Err(StdError::parse_err(
+            type_name::<T>(),
+            format!("record of version {} is newer than this contract ({})", version, STATE_VERSION),
+        ));
+    }
+    let format_known = match format {
+        Some(format) => format,
+        None => return Err(StdError::parse_err(type_name::<T>(), format!("unknown format {}", record[0]))),
+    };
+    let payload = if version < STATE_VERSION {
+        upgrade_record(type_name::<T>(), format_known, version, record[2..].to_vec())?
+    } else {
+        record[2..].to_vec()
+    };
+    let result = match format_known {
+        Format::Bincode2 => Bincode2::deserialize(&payload),
+        Format::Json => Json::deserialize(&payload),
+    };
+    result.map_err(|e| StdError::parse_err(
+        type_name::<T>(),
+        format!("{:?} record of version {}: {}", format_known, version, e),
+    ))
+}
+
 /// Removes an item from storage
 ///
 /// # Arguments
@@ -157,7 +2667,7 @@ pub fn remove<S: Storage>(storage: &mut S, key: &[u8]) {
 /// * `storage` - a reference to the storage this item is in
 /// * `key` - a byte slice representing the key that accesses the stored item
 pub fn load<T: DeserializeOwned, S: ReadonlyStorage>(storage: &S, key: &[u8]) -> StdResult<T> {
-    Bincode2::deserialize(
+    deserialize(
         &storage
             .get(key)
             .ok_or_else(|| StdError::not_found(type_name::<T>()))?,
@@ -177,9 +2687,63 @@ pub fn may_load<T: DeserializeOwned, S: ReadonlyStorage>(
     key: &[u8],
 ) -> StdResult<Option<T>> {
     match storage.get(key) {
-        Some(value) => Bincode2::deserialize(&value).map(Some),
+        Some(value) => deserialize(&value).map(Some),
         None => Ok(None),
     }
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padded_to_bucket_boundaries() {
+        let buckets = [16, 64, 256];
+        assert_eq!(padded(vec![], &buckets).len(), 16);
+        assert_eq!(padded(vec![1; 16], &buckets).len(), 16);
+        assert_eq!(padded(vec![1; 17], &buckets).len(), 64);
+        assert_eq!(padded(vec![1; 64], &buckets).len(), 64);
+        assert_eq!(padded(vec![1; 256], &buckets).len(), 256);
+        // beyond the largest bucket, content is padded to a multiple of it
+        assert_eq!(padded(vec![1; 257], &buckets).len(), 512);
+        assert_eq!(padded(vec![1; 512], &buckets).len(), 512);
+        assert_eq!(padded(vec![1; 513], &buckets).len(), 768);
+    }
+
+    #[test]
+    fn padded_keeps_content() {
+        let content = padded(b"hello".to_vec(), &[16]);
+        assert_eq!(&content[..5], b"hello");
+        assert!(content[5..].iter().all(|byte| *byte == 0));
+
+        assert_eq!(padded(b"hello".to_vec(), &[]), b"hello".to_vec());
+    }
+
+    #[test]
+    fn send_fee_tier_edges() {
+        let fee = SendFee {
+            denom: "uscrt".to_string(),
+            base: 100,
+            per_byte: 2,
+            tiers: vec![FeeTier { max_size: 10, amount: 5 }, FeeTier { max_size: 100, amount: 50 }],
+        };
+        assert_eq!(fee.amount_for(0), 5);
+        assert_eq!(fee.amount_for(10), 5);
+        assert_eq!(fee.amount_for(11), 50);
+        assert_eq!(fee.amount_for(100), 50);
+        // larger than every tier
+        assert_eq!(fee.amount_for(101), 100 + 2 * 101);
+    }
+
+    #[test]
+    fn send_fee_without_tiers() {
+        let fee = SendFee { denom: "uscrt".to_string(), base: 100, per_byte: 2, tiers: vec![] };
+        assert_eq!(fee.amount_for(0), 100);
+        assert_eq!(fee.amount_for(10), 120);
+
+        let fee = SendFee { denom: "uscrt".to_string(), base: u128::MAX, per_byte: 2, tiers: vec![] };
+        assert_eq!(fee.amount_for(10), u128::MAX);
+    }
+}
@@ -0,0 +1,54 @@
+use cosmwasm_std::{Binary, Env};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use secret_toolkit::crypto::sha_256;
+
+pub const VIEWING_KEY_SIZE: usize = 32;
+/// maximum number of labeled viewing keys of an address, besides the unlabeled one
+pub const MAX_VIEWING_KEY_LABELS: usize = 8;
+/// maximum size of a viewing key label in bytes
+pub const MAX_VIEWING_KEY_LABEL_SIZE: usize = 32;
+/// prefix of generated viewing keys
+pub const VIEWING_KEY_PREFIX: &str = "api_key_";
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct ViewingKey(pub String);
+
+impl ViewingKey {
+    /// Generates a key from the contract's PRNG seed, the block, the sender and entropy supplied
+    /// by the sender. Returns the key and the seed to store for the next key
+    pub fn new(env: &Env, seed: &[u8], entropy: &[u8]) -> (Self, [u8; 32]) {
+        let mut preimage = seed.to_vec();
+        preimage.extend_from_slice(&env.block.height.to_be_bytes());
+        preimage.extend_from_slice(&env.block.time.to_be_bytes());
+        preimage.extend_from_slice(env.message.sender.0.as_bytes());
+        preimage.extend_from_slice(entropy);
+        let next_seed = sha_256(&preimage);
+        // the key is hashed again so it reveals nothing about the seed
+        let key = sha_256(&[&next_seed[..], b"viewing key"].concat());
+        (ViewingKey(format!("{}{}", VIEWING_KEY_PREFIX, Binary::from(&key[..]).to_base64())), next_seed)
+    }
+
+    /// Returns true if this key hashes to the stored hashed key
+    pub fn check_viewing_key(&self, hashed_pw: &[u8]) -> bool {
+        let mine_hashed = self.to_hashed();
+        ct_slice_compare(&mine_hashed, hashed_pw)
+    }
+
+    pub fn to_hashed(&self) -> [u8; VIEWING_KEY_SIZE] {
+        sha_256(self.0.as_bytes())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+// constant time comparison so checking a key does not leak how much of it matched
+fn ct_slice_compare(s1: &[u8], s2: &[u8]) -> bool {
+    if s1.len() != s2.len() {
+        return false;
+    }
+    s1.iter().zip(s2.iter()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}